@@ -21,6 +21,13 @@ impl AABB {
         Vector3::new(self.x.max, self.y.max, self.z.max)
     }
 
+    pub fn surface_area(&self) -> f64 {
+        let dx = self.x.length();
+        let dy = self.y.length();
+        let dz = self.z.length();
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
     pub fn axis_compare(a: Axis, this: &Self, that: &Self) -> Ordering {
         let x = this.idx(a).min;
         let y = that.idx(a).min;
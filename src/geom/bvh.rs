@@ -13,17 +13,20 @@ use super::{Geom, Geomable, bbox::Bbox, intersection::Intersection};
 
 struct BVHLeaf<T> {
     bbox: AABB,
+    // Index of this leaf in the list the BVH was constructed from, reported on hits.
+    id: usize,
     inner: T,
 }
 
 impl<T> BVHLeaf<T> {
-    pub fn new(inner: T) -> Self
+    pub fn new(id: usize, inner: T) -> Self
     where
         T: Bbox,
     {
         let bb = inner.bbox();
         BVHLeaf {
             bbox: bb,
+            id,
             inner: inner,
         }
     }
@@ -33,11 +36,20 @@ impl<T> BVHLeaf<T> {
         T: Intersectable,
     {
         if self.bbox.intersect(&ray, i) {
-            self.inner.intersect(ray, i)
+            let mut inter = self.inner.intersect(ray, i)?;
+            inter.set_prim_id(self.id);
+            Some(inter)
         } else {
             None
         }
     }
+
+    pub fn refit(&mut self)
+    where
+        T: Bbox,
+    {
+        self.bbox = self.inner.bbox();
+    }
 }
 
 // fn bbox(&self) -> AABB {
@@ -95,6 +107,18 @@ impl<T> BVHNode<T> {
             None
         }
     }
+
+    /// Recompute the child boxes bottom-up, keeping the tree topology as-is.
+    pub fn refit(&mut self)
+    where
+        T: Bbox,
+    {
+        self.left.refit();
+        self.right.refit();
+        self.bbox_left = self.left.bbox();
+        self.bbox_right = self.right.bbox();
+        self.bbox_union = AABB::union(&self.bbox_left, &self.bbox_right);
+    }
 }
 
 pub enum BVHTree<T> {
@@ -120,11 +144,11 @@ impl<T> BVHTree<T> {
         }
     }
 
-    pub fn leaf(inner: T) -> Self
+    pub fn leaf(id: usize, inner: T) -> Self
     where
         T: Bbox,
     {
-        BVHTree::Leaf(BVHLeaf::new(inner))
+        BVHTree::Leaf(BVHLeaf::new(id, inner))
     }
 
     pub fn node(left: BVHTree<T>, right: BVHTree<T>) -> Self
@@ -134,39 +158,110 @@ impl<T> BVHTree<T> {
         BVHTree::Node(BVHNode::new(left, right))
     }
 
-    pub fn depth(&self) -> usize {
+    pub fn size(&self) -> usize {
         match self {
             BVHTree::Leaf(_) => 0,
-            BVHTree::Node(bvhnode) => 1 + usize::max(bvhnode.left.depth(), bvhnode.right.depth()),
+            BVHTree::Node(bvhnode) => 1 + bvhnode.left.size() + bvhnode.right.size(),
         }
     }
 
-    pub fn size(&self) -> usize {
+    pub fn refit(&mut self)
+    where
+        T: Bbox,
+    {
         match self {
-            BVHTree::Leaf(_) => 0,
-            BVHTree::Node(bvhnode) => 1 + bvhnode.left.size() + bvhnode.right.size(),
+            BVHTree::Leaf(leaf) => leaf.refit(),
+            BVHTree::Node(node) => node.refit(),
+        }
+    }
+
+    pub fn for_each_leaf<F>(&self, f: &mut F)
+    where
+        F: FnMut(usize, &T),
+    {
+        match self {
+            BVHTree::Leaf(leaf) => f(leaf.id, &leaf.inner),
+            BVHTree::Node(node) => {
+                node.left.for_each_leaf(f);
+                node.right.for_each_leaf(f);
+            }
+        }
+    }
+
+    pub fn for_each_leaf_mut<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut T),
+    {
+        match self {
+            BVHTree::Leaf(leaf) => f(&mut leaf.inner),
+            BVHTree::Node(node) => {
+                node.left.for_each_leaf_mut(f);
+                node.right.for_each_leaf_mut(f);
+            }
+        }
+    }
+
+    pub fn find_leaf(&self, id: usize) -> Option<&T> {
+        match self {
+            BVHTree::Leaf(leaf) if leaf.id == id => Some(&leaf.inner),
+            BVHTree::Leaf(_) => None,
+            BVHTree::Node(node) => node.left.find_leaf(id).or_else(|| node.right.find_leaf(id)),
+        }
+    }
+
+    fn into_leaves(self, out: &mut Vec<(usize, T)>) {
+        match self {
+            BVHTree::Leaf(leaf) => out.push((leaf.id, leaf.inner)),
+            BVHTree::Node(node) => {
+                node.left.into_leaves(out);
+                node.right.into_leaves(out);
+            }
+        }
+    }
+
+    /// Surface area heuristic cost of the tree: the summed area of every interior
+    /// box, relative to the root. Lower is better. Refitting after a move can
+    /// raise or lower it, since the topology stays fixed while the boxes follow.
+    pub fn sah_cost(&self) -> f64 {
+        let root_area = self.bbox().surface_area();
+        if root_area <= 0.0 {
+            return 0.0;
         }
+        self.interior_area() / root_area
     }
 
-    fn construct(mut from: Vec<T>) -> Self
+    fn interior_area(&self) -> f64 {
+        match self {
+            BVHTree::Leaf(_) => 0.0,
+            BVHTree::Node(bvhnode) => {
+                bvhnode.bbox_union.surface_area()
+                    + bvhnode.left.interior_area()
+                    + bvhnode.right.interior_area()
+            }
+        }
+    }
+
+    fn construct(mut from: Vec<(usize, T)>) -> Self
     where
         T: Bbox,
     {
         let n = from.len();
         assert!(n > 0);
         if n == 1 {
-            let geom = from.remove(0);
-            return BVHTree::Leaf(BVHLeaf::new(geom));
+            let (id, geom) = from.remove(0);
+            BVHTree::Leaf(BVHLeaf::new(id, geom))
         } else if n == 2 {
-            let gr = from.remove(1);
-            let gl = from.remove(0);
-            let left = BVHTree::leaf(gl);
-            let right = BVHTree::leaf(gr);
+            let (idr, gr) = from.remove(1);
+            let (idl, gl) = from.remove(0);
+            let left = BVHTree::leaf(idl, gl);
+            let right = BVHTree::leaf(idr, gr);
             BVHTree::node(left, right)
         } else {
             let axis = Axis::random();
 
-            from.sort_by(|this, that| AABB::axis_compare(axis, &this.bbox(), &that.bbox()));
+            from.sort_by(|(_, this), (_, that)| {
+                AABB::axis_compare(axis, &this.bbox(), &that.bbox())
+            });
 
             let mid = n / 2;
 
@@ -181,8 +276,13 @@ impl<T> BVHTree<T> {
     }
 }
 
+// Once a refit tree's SAH cost grows past this multiple of its cost when
+// freshly built, it's cheaper to rebuild than to keep traversing it.
+const REBUILD_THRESHOLD: f64 = 1.5;
+
 pub struct BVH<T> {
     tree: BVHTree<T>,
+    built_cost: f64,
 }
 
 impl<T> BVH<T> {
@@ -190,10 +290,73 @@ impl<T> BVH<T> {
     where
         T: Bbox,
     {
-        let t = BVHTree::construct(geoms);
-        dbg!(t.depth());
-        dbg!(t.size());
-        BVH { tree: t }
+        Self::from_leaves(geoms.into_iter().enumerate().collect())
+    }
+
+    fn from_leaves(leaves: Vec<(usize, T)>) -> Self
+    where
+        T: Bbox,
+    {
+        let t = BVHTree::construct(leaves);
+        let built_cost = t.sah_cost();
+        BVH {
+            tree: t,
+            built_cost,
+        }
+    }
+
+    /// Update every bounding box in place after the leaves have moved.
+    pub fn refit(&mut self)
+    where
+        T: Bbox,
+    {
+        self.tree.refit();
+    }
+
+    /// Mutate each primitive (e.g. to advance an animation), then refit the tree around them.
+    pub fn update<F>(&mut self, mut f: F)
+    where
+        T: Bbox,
+        F: FnMut(&mut T),
+    {
+        self.tree.for_each_leaf_mut(&mut f);
+        self.refit();
+    }
+
+    /// How much worse the tree is than when it was built. 1.0 means no degradation.
+    pub fn degradation(&self) -> f64 {
+        if self.built_cost <= 0.0 {
+            return 1.0;
+        }
+        self.tree.sah_cost() / self.built_cost
+    }
+
+    /// Throw the tree away and rebuild it from scratch if refitting has
+    /// degraded it past `REBUILD_THRESHOLD`.
+    pub fn rebuild_if_degraded(self) -> Self
+    where
+        T: Bbox,
+    {
+        if self.degradation() > REBUILD_THRESHOLD {
+            let mut leaves = Vec::with_capacity(self.tree.size() + 1);
+            self.tree.into_leaves(&mut leaves);
+            BVH::from_leaves(leaves)
+        } else {
+            self
+        }
+    }
+
+    /// Visit every primitive along with its object id.
+    pub fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(usize, &T),
+    {
+        self.tree.for_each_leaf(&mut f);
+    }
+
+    /// The primitive with the given id, as reported by `Intersection::prim_id`.
+    pub fn prim(&self, id: usize) -> Option<&T> {
+        self.tree.find_leaf(id)
     }
 
     pub fn top(&self) -> &BVHTree<T> {
@@ -215,3 +378,164 @@ impl<T> BVH<T> {
 //     //     self.tree.bbox()
 //     // }
 // }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::Vector3;
+
+    use crate::{
+        geom::{Geom, Geomable, sphere::Sphere, translation::Translation},
+        lighting::{
+            color::Color, lambertian::Lambertian, material::Material,
+            texture::solidcolor::SolidColor,
+        },
+        math::{interval::Interval, ray::Ray},
+    };
+
+    use super::{AABB, BVH, BVHTree, Bbox};
+
+    fn mat() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(
+            0.5, 0.5, 0.5,
+        )))))
+    }
+
+    fn spheres_at(positions: &[Vector3<f64>]) -> Vec<Geom> {
+        let mat = mat();
+        positions
+            .iter()
+            .flat_map(|&p| {
+                Translation::new(p, Sphere::new(Vector3::zeros(), 0.5, mat.clone())).into_geoms()
+            })
+            .collect()
+    }
+
+    fn contains(outer: &AABB, inner: &AABB) -> bool {
+        (0..3).all(|a| outer.min()[a] <= inner.min()[a] && inner.max()[a] <= outer.max()[a])
+    }
+
+    fn assert_boxes_contain_children(tree: &BVHTree<Geom>) {
+        match tree {
+            BVHTree::Leaf(leaf) => assert!(contains(&leaf.bbox, &leaf.inner.bbox())),
+            BVHTree::Node(node) => {
+                assert!(contains(&node.bbox_left, &node.left.bbox()));
+                assert!(contains(&node.bbox_right, &node.right.bbox()));
+                assert!(contains(&node.bbox_union, &node.bbox_left));
+                assert!(contains(&node.bbox_union, &node.bbox_right));
+                assert_boxes_contain_children(&node.left);
+                assert_boxes_contain_children(&node.right);
+            }
+        }
+    }
+
+    /// (prim id, distance) of the nearest hit for each ray of a grid aimed at the spheres.
+    fn shoot(bvh: &BVH<Geom>) -> Vec<Option<(usize, f64)>> {
+        let mut hits = Vec::new();
+        for y in -20..=20 {
+            for x in -20..=20 {
+                let target = Vector3::new(x as f64 * 0.5, y as f64 * 0.5, 0.0);
+                let ray = Ray::through_points(Vector3::new(0.0, 0.0, 30.0), target);
+                let hit = bvh.intersect(ray, Interval::new(0.0, f64::MAX));
+                hits.push(hit.map(|h| (h.prim_id().unwrap(), h.dist())));
+            }
+        }
+        hits
+    }
+
+    #[test]
+    fn refit_matches_fresh_construct() {
+        let start: Vec<Vector3<f64>> = (0..40)
+            .map(|i| {
+                let i = i as f64;
+                Vector3::new(
+                    (i * 1.7) % 9.0 - 4.5,
+                    (i * 2.3) % 9.0 - 4.5,
+                    (i * 0.9) % 3.0,
+                )
+            })
+            .collect();
+        let mut bvh = BVH::construct(spheres_at(&start));
+
+        // Move every sphere by a different amount, some far enough to reshuffle the tree
+        let mut n = 0.0;
+        bvh.update(|g| {
+            if let Geom::Trans(t) = g {
+                n += 1.0;
+                let by = Vector3::new((n * 0.37) % 2.0 - 1.0, (n * 0.61) % 4.0 - 2.0, 0.0);
+                t.set_trans(t.trans() + by);
+            }
+        });
+        assert_boxes_contain_children(bvh.top());
+
+        let mut moved = Vec::new();
+        bvh.for_each(|id, g| {
+            if let Geom::Trans(t) = g {
+                moved.push((id, t.trans()));
+            }
+        });
+        moved.sort_by_key(|(id, _)| *id);
+        let moved: Vec<_> = moved.into_iter().map(|(_, p)| p).collect();
+        let fresh = BVH::construct(spheres_at(&moved));
+
+        let refit_hits = shoot(&bvh);
+        assert!(refit_hits.iter().any(Option::is_some));
+        assert_eq!(refit_hits, shoot(&fresh));
+
+        // Rebuilding keeps ids, so it must agree too
+        let rebuilt = bvh.rebuild_if_degraded();
+        assert_boxes_contain_children(rebuilt.top());
+        assert_eq!(shoot(&rebuilt), shoot(&fresh));
+    }
+
+    #[test]
+    fn scattering_the_spheres_triggers_a_rebuild() {
+        // Two tight clusters, apart along every axis so any split separates them
+        let start: Vec<Vector3<f64>> = (0..32)
+            .map(|i| {
+                let side = if i % 2 == 0 { -8.0 } else { 8.0 };
+                Vector3::repeat(side + (i / 2) as f64 * 0.05)
+            })
+            .collect();
+        let mut bvh = BVH::construct(spheres_at(&start));
+        assert_eq!(bvh.degradation(), 1.0);
+
+        // Swap every other sphere over to the other side so both halves of the
+        // tree span the whole scene
+        let mut n = 0;
+        bvh.update(|g| {
+            if let Geom::Trans(t) = g {
+                n += 1;
+                if n % 2 == 0 {
+                    t.set_trans(-t.trans());
+                }
+            }
+        });
+        assert!(bvh.degradation() > super::REBUILD_THRESHOLD);
+        let before = shoot(&bvh);
+
+        let rebuilt = bvh.rebuild_if_degraded();
+        assert_eq!(rebuilt.degradation(), 1.0);
+        assert_boxes_contain_children(rebuilt.top());
+        assert_eq!(shoot(&rebuilt), before);
+        assert!(before.iter().any(Option::is_some));
+    }
+
+    #[test]
+    fn small_moves_keep_the_tree() {
+        let start: Vec<Vector3<f64>> = (0..16)
+            .map(|i| Vector3::new(i as f64 * 1.5 - 12.0, 0.0, 0.0))
+            .collect();
+        let mut bvh = BVH::construct(spheres_at(&start));
+        bvh.update(|g| {
+            if let Geom::Trans(t) = g {
+                t.set_trans(t.trans() + Vector3::new(0.0, 0.1, 0.0));
+            }
+        });
+        let degradation = bvh.degradation();
+        assert!(degradation <= super::REBUILD_THRESHOLD);
+        // Not rebuilt, so still measured against the original cost
+        assert_eq!(bvh.rebuild_if_degraded().degradation(), degradation);
+    }
+}
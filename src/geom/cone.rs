@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use nalgebra::{Unit, Vector2, Vector3};
+
+use crate::{
+    lighting::material::Material,
+    math::{interval::Interval, ray::Ray},
+    util::gamma,
+};
+
+use super::{
+    Geom, Geomable,
+    aabb::AABB,
+    bbox::Bbox,
+    disk,
+    frame::{self, Frame},
+    intersectable::Intersectable,
+    intersection::Intersection,
+};
+
+/// A cone with its base circle at `base` and its tip at `base + axis`, open
+/// at the bottom unless it's given a cap. u goes around the axis, v up to the tip.
+pub struct Cone {
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat: Arc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(
+        base: Vector3<f64>,
+        axis: Vector3<f64>,
+        radius: f64,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        assert!(radius > 0.0);
+        let height = axis.norm();
+        assert!(height > 0.0);
+        Cone {
+            frame: Frame::new(base, Unit::new_normalize(axis)),
+            radius,
+            height,
+            capped: false,
+            mat,
+        }
+    }
+
+    pub fn with_caps(mut self, capped: bool) -> Self {
+        self.capped = capped;
+        self
+    }
+
+    fn hit_side<'r>(&'r self, ray: &Ray, i: Interval) -> Option<Intersection<'r>> {
+        // x² + y² = k² (h - z)², with both nappes; the one above the tip is cut off below
+        let (o, d) = (ray.origin(), ray.dir());
+        let k2 = (self.radius / self.height).powi(2);
+        let hz = self.height - o.z;
+        let a = d.x * d.x + d.y * d.y - k2 * d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.y * d.y + k2 * hz * d.z);
+        let c = o.x * o.x + o.y * o.y - k2 * hz * hz;
+        let (t0, t1) = frame::solve_quadratic(a, b, c)?;
+
+        let t = [t0, t1]
+            .into_iter()
+            .find(|&t| i.contains(t) && (0.0..=self.height).contains(&ray.at(t).z))?;
+
+        let p = ray.at(t);
+        let gradient = Vector3::new(p.x, p.y, k2 * (self.height - p.z));
+        let normal = Unit::try_new(gradient, 1e-12).unwrap_or(Vector3::z_axis());
+
+        let phi = frame::turn_fraction(p.x, p.y);
+        let (sin, cos) = (2.0 * std::f64::consts::PI * phi).sin_cos();
+        let dpdv = Vector3::new(-self.radius * cos, -self.radius * sin, self.height);
+
+        let uv = Vector2::new(phi, p.z / self.height);
+        Some(
+            Intersection::new(p, t, normal, self.mat.as_ref(), uv)
+                .with_partials(frame::dp_dphi(&p), dpdv)
+                .with_error(p.abs().scale(gamma(7))),
+        )
+    }
+}
+
+impl Intersectable for Cone {
+    fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>> {
+        let local = self.frame.ray_to_local(&ray);
+        let mut hit = self.hit_side(&local, i);
+
+        if self.capped {
+            let max = hit.as_ref().map_or(i.max, |h| h.dist());
+            let cap = disk::hit_local(
+                &local,
+                Interval::new(i.min, max),
+                0.0,
+                self.radius,
+                0.0,
+                -Vector3::z_axis(),
+                self.mat.as_ref(),
+            );
+            if cap.is_some() {
+                hit = cap;
+            }
+        }
+
+        hit.map(|h| self.frame.hit_to_world(h))
+    }
+}
+
+impl Bbox for Cone {
+    fn bbox(&self) -> AABB {
+        let r = self.radius;
+        self.frame
+            .bbox(Vector3::new(-r, -r, 0.0), Vector3::new(r, r, self.height))
+    }
+}
+
+impl Geomable for Cone {
+    fn into_geoms(self) -> impl Iterator<Item = Geom> {
+        std::iter::once(Geom::Cone(Box::new(self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector2, Vector3};
+
+    use crate::{
+        geom::{bbox::Bbox, intersectable::Intersectable},
+        lighting::{
+            color::Color, lambertian::Lambertian, material::Material,
+            texture::solidcolor::SolidColor,
+        },
+        math::{interval::Interval, ray::Ray},
+    };
+
+    use super::Cone;
+
+    fn mat() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(
+            0.5, 0.5, 0.5,
+        )))))
+    }
+
+    fn near(a: Vector3<f64>, b: Vector3<f64>) -> bool {
+        (a - b).norm() < 1e-3
+    }
+
+    #[test]
+    fn side_and_base() {
+        let open = Cone::new(Vector3::zeros(), Vector3::new(0.0, 0.0, 2.0), 1.0, mat());
+        let capped =
+            Cone::new(Vector3::zeros(), Vector3::new(0.0, 0.0, 2.0), 1.0, mat()).with_caps(true);
+        let shoot = |c: &Cone, o: Vector3<f64>, d: Vector3<f64>| {
+            c.intersect(Ray::new_normalize(o, d), Interval::new(0.0, f64::MAX))
+                .map(|h| (h.dist(), h.normal().into_inner(), h.uv()))
+        };
+
+        // Halfway up the radius is 0.5, and the side leans back at 1 in 2
+        let (dist, n, uv) = shoot(&open, Vector3::new(5.0, 0.0, 1.0), -Vector3::x()).unwrap();
+        assert!((dist - 4.5).abs() < 1e-9);
+        assert!(near(n, Vector3::new(2.0, 0.0, 1.0).normalize()));
+        assert!((uv - Vector2::new(0.0, 0.5)).norm() < 1e-9);
+
+        // From below, the cap if there is one, else the inside of the side
+        let below = (Vector3::new(0.5, 0.0, -3.0), Vector3::z());
+        let (dist, n, uv) = shoot(&capped, below.0, below.1).unwrap();
+        assert!((dist - 3.0).abs() < 1e-9);
+        assert!(near(n, -Vector3::z()));
+        assert!((uv - Vector2::new(0.0, 0.5)).norm() < 1e-9);
+        let (dist, _, _) = shoot(&open, below.0, below.1).unwrap();
+        assert!((dist - 4.0).abs() < 1e-9);
+
+        // The other nappe, above the tip, isn't there
+        assert!(shoot(&open, Vector3::new(5.0, 0.0, 3.0), -Vector3::x()).is_none());
+
+        let bb = open.bbox();
+        assert!(near(bb.min(), Vector3::new(-1.0, -1.0, 0.0)));
+        assert!(near(bb.max(), Vector3::new(1.0, 1.0, 2.0)));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::math::{interval::Interval, ray::Ray};
+
+use super::{
+    Geom, Geomable, aabb::AABB, bbox::Bbox, intersectable::Intersectable,
+    intersection::Intersection,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // a with b cut out of it
+    Difference,
+}
+
+impl CsgOp {
+    pub fn name(self) -> &'static str {
+        match self {
+            CsgOp::Union => "union",
+            CsgOp::Intersection => "intersection",
+            CsgOp::Difference => "difference",
+        }
+    }
+
+    fn contains(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+/// A solid made by combining two others. Each side is a set of geoms that
+/// together should close off a volume, with normals pointing out: which way
+/// the ray goes through a surface is read off its normal. Open surfaces (quads,
+/// disks, uncapped cylinders and cones) aren't supported and give garbage; the
+/// scene parser refuses them. Mirroring can't flip the normals inwards since
+/// `Scaling` only takes positive factors.
+pub struct Csg {
+    op: CsgOp,
+    a: Vec<Geom>,
+    b: Vec<Geom>,
+    bbox: AABB,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, a: Vec<Geom>, b: Vec<Geom>) -> Self {
+        assert!(!a.is_empty() && !b.is_empty(), "csg needs two solids");
+        let bbox = match op {
+            CsgOp::Union => AABB::union(&bbox_of(&a), &bbox_of(&b)),
+            // Nothing outside a survives
+            CsgOp::Intersection | CsgOp::Difference => bbox_of(&a),
+        };
+        Csg { op, a, b, bbox }
+    }
+
+    pub fn describe(&self) -> String {
+        let side = |geoms: &[Geom]| {
+            geoms
+                .iter()
+                .map(Geom::describe)
+                .collect::<Vec<_>>()
+                .join(" + ")
+        };
+        format!("{}({}, {})", self.op.name(), side(&self.a), side(&self.b))
+    }
+}
+
+fn bbox_of(geoms: &[Geom]) -> AABB {
+    geoms[1..]
+        .iter()
+        .fold(geoms[0].bbox(), |bb, g| AABB::union(&bb, &g.bbox()))
+}
+
+/// Where the ray crosses any of `side`, nearest first.
+fn crossings<'r>(side: &'r [Geom], ray: Ray, i: Interval) -> Vec<Intersection<'r>> {
+    let mut hits: Vec<_> = side.iter().flat_map(|g| g.intersect_all(ray, i)).collect();
+    hits.sort_by(Intersection::dist_compare);
+    hits
+}
+
+impl Intersectable for Csg {
+    fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>> {
+        self.intersect_all(ray, i).into_iter().next()
+    }
+
+    fn intersect_all<'r>(&'r self, ray: Ray, i: Interval) -> Vec<Intersection<'r>> {
+        // Look all the way along the ray, even past i.max, so a side the ray
+        // starts inside of always shows up as an exit first.
+        let ahead = Interval::new(i.min, f64::MAX);
+        let dir = ray.dir().into_inner();
+        let a = crossings(&self.a, ray, ahead);
+        let b = crossings(&self.b, ray, ahead);
+
+        let mut in_a = a.first().is_some_and(|h| !h.is_entering(&dir));
+        let mut in_b = b.first().is_some_and(|h| !h.is_entering(&dir));
+        let mut inside = self.op.contains(in_a, in_b);
+
+        let mut events: Vec<(bool, Intersection<'r>)> = a
+            .into_iter()
+            .map(|h| (true, h))
+            .chain(b.into_iter().map(|h| (false, h)))
+            .collect();
+        events.sort_by(|x, y| x.1.dist_compare(&y.1));
+
+        // The combined solid's surface is wherever being inside it changes
+        let mut hits = Vec::new();
+        for (from_a, hit) in events {
+            if hit.dist() > i.max {
+                break;
+            }
+            let entering = hit.is_entering(&dir);
+            if from_a {
+                in_a = entering;
+            } else {
+                in_b = entering;
+            }
+            let now = self.op.contains(in_a, in_b);
+            if now != inside {
+                inside = now;
+                // Where b is cut out of a, b's surface faces into b
+                let hit = if !from_a && self.op == CsgOp::Difference {
+                    hit.flipped()
+                } else {
+                    hit
+                };
+                hits.push(hit);
+            }
+        }
+        hits
+    }
+}
+
+impl Bbox for Csg {
+    fn bbox(&self) -> AABB {
+        self.bbox.clone()
+    }
+}
+
+impl Geomable for Csg {
+    fn into_geoms(self) -> impl Iterator<Item = Geom> {
+        std::iter::once(Geom::Csg(Box::new(self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::Vector3;
+
+    use crate::{
+        geom::{
+            Geom, Geomable, intersectable::Intersectable, scaling::Scaling, sphere::Sphere,
+            translation::Translation,
+        },
+        lighting::{color::Color, lambertian::Lambertian, texture::solidcolor::SolidColor},
+        math::{interval::Interval, ray::Ray},
+    };
+
+    use super::{Csg, CsgOp};
+
+    // Unit spheres at the origin and at x = 1, so along the x axis a spans
+    // [-1, 1] and b spans [0, 2]
+    fn spheres(op: CsgOp) -> Csg {
+        let mat = Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::white()))));
+        let sphere = |x: f64| Sphere::new(Vector3::new(x, 0.0, 0.0), 1.0, mat.clone());
+        Csg::new(
+            op,
+            sphere(0.0).into_geoms().collect(),
+            sphere(1.0).into_geoms().collect(),
+        )
+    }
+
+    // Distance and the x of the normal of every crossing along +x from `x`
+    fn crossings(g: &impl Intersectable, x: f64, offset: Vector3<f64>) -> Vec<(f64, f64)> {
+        let ray = Ray::new(Vector3::new(x, 0.0, 0.0) + offset, Vector3::x_axis());
+        g.intersect_all(ray, Interval::new(0.0, f64::MAX))
+            .iter()
+            .map(|h| (h.dist(), h.normal().x))
+            .collect()
+    }
+
+    fn assert_close(got: Vec<(f64, f64)>, want: &[(f64, f64)]) {
+        assert_eq!(got.len(), want.len(), "{:?}", got);
+        for (g, w) in got.iter().zip(want) {
+            assert!(
+                (g.0 - w.0).abs() < 1e-9 && (g.1 - w.1).abs() < 1e-9,
+                "{:?}",
+                got
+            );
+        }
+    }
+
+    #[test]
+    fn ops_keep_the_right_surfaces() {
+        let at = Vector3::zeros();
+        // In at a's near side, out at b's far side
+        assert_close(
+            crossings(&spheres(CsgOp::Union), -5.0, at),
+            &[(4.0, -1.0), (7.0, 1.0)],
+        );
+        // In at b's near side, out at a's far side
+        assert_close(
+            crossings(&spheres(CsgOp::Intersection), -5.0, at),
+            &[(5.0, -1.0), (6.0, 1.0)],
+        );
+        // Out where b starts, with b's normal turned to face out of what's left
+        assert_close(
+            crossings(&spheres(CsgOp::Difference), -5.0, at),
+            &[(4.0, -1.0), (5.0, 1.0)],
+        );
+    }
+
+    #[test]
+    fn rays_starting_inside() {
+        let at = Vector3::zeros();
+        // Inside a only: the first thing hit is the cut b makes
+        assert_close(
+            crossings(&spheres(CsgOp::Difference), -0.5, at),
+            &[(0.5, 1.0)],
+        );
+        // Inside both
+        assert_close(
+            crossings(&spheres(CsgOp::Intersection), 0.5, at),
+            &[(0.5, 1.0)],
+        );
+        assert_close(crossings(&spheres(CsgOp::Union), 0.5, at), &[(1.5, 1.0)]);
+        // Inside b, past the end of a, where the difference is empty
+        assert_close(crossings(&spheres(CsgOp::Difference), 1.5, at), &[]);
+    }
+
+    #[test]
+    fn moved_csg_walks_its_crossings() {
+        // Goes through walk_crossings rather than Csg::intersect_all
+        let by = Vector3::new(0.0, 3.0, 10.0);
+        for (op, want) in [
+            (CsgOp::Union, [(4.0, -1.0), (7.0, 1.0)]),
+            (CsgOp::Intersection, [(5.0, -1.0), (6.0, 1.0)]),
+            (CsgOp::Difference, [(4.0, -1.0), (5.0, 1.0)]),
+        ] {
+            let moved: Vec<Geom> =
+                Translation::new(by, spheres(op).into_geoms().collect::<Vec<_>>())
+                    .into_geoms()
+                    .collect();
+            assert!(matches!(moved[..], [Geom::Trans(_)]));
+            assert_close(crossings(&moved[0], -5.0, by), &want);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn operands_cant_be_mirrored() {
+        Scaling::new(Vector3::new(-1.0, 1.0, 1.0), spheres(CsgOp::Union));
+    }
+}
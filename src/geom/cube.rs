@@ -1,102 +1,247 @@
 use std::sync::Arc;
 
-use nalgebra::Vector3;
+use nalgebra::{Unit, UnitVector3, Vector2, Vector3};
 
 use crate::{
     lighting::material::Material,
     math::{interval::Interval, ray::Ray},
+    util::gamma,
 };
 
 use super::{
-    aabb::AABB,
-    intersectable::{self, Intersectable},
+    Geom, Geomable, aabb::AABB, bbox::Bbox, intersectable::Intersectable,
     intersection::Intersection,
-    quad::Quad,
 };
 
-pub struct Cube {
-    faces: Arc<[Box<Quad>; 6]>,
+/// One side of a `Cube`, by the way it faces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Face {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
 }
 
-impl Cube {
-    pub fn new(c: Vector3<f64>, r: f64, mat: Arc<dyn Material>) -> Self {
-        // c is the center of the cube, r is the half-width (radius)
-
-        // Note: For each face, we need to order the vertices correctly so that
-        // the cross product of u_hat and v_hat points outward
-
-        // Front face (positive Z)
-        let front = Quad::new(
-            Vector3::new(c.x - r, c.y - r, c.z + r), // bottom-left front
-            Vector3::new(2.0 * r, 0.0, 0.0),         // u vector along positive x
-            Vector3::new(0.0, 2.0 * r, 0.0),         // v vector along positive y
-            mat.clone(),
-        );
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::PosX,
+        Face::NegX,
+        Face::PosY,
+        Face::NegY,
+        Face::PosZ,
+        Face::NegZ,
+    ];
 
-        // Back face (negative Z)
-        let back = Quad::new(
-            Vector3::new(c.x - r, c.y - r, c.z - r), // bottom-left back
-            Vector3::new(0.0, 2.0 * r, 0.0),         // u vector along positive y
-            Vector3::new(2.0 * r, 0.0, 0.0),         // v vector along positive x
-            mat.clone(),
-        );
+    fn axis(self) -> usize {
+        self as usize / 2
+    }
 
-        // Right face (positive X)
-        let right = Quad::new(
-            Vector3::new(c.x + r, c.y - r, c.z - r), // bottom-right back
-            Vector3::new(0.0, 2.0 * r, 0.0),         // u vector along positive y
-            Vector3::new(0.0, 0.0, 2.0 * r),         // v vector along positive z
-            mat.clone(),
-        );
+    fn normal(self) -> UnitVector3<f64> {
+        let mut n = Vector3::zeros();
+        n[self.axis()] = if (self as usize).is_multiple_of(2) {
+            1.0
+        } else {
+            -1.0
+        };
+        Unit::new_unchecked(n)
+    }
 
-        // Left face (negative X)
-        let left = Quad::new(
-            Vector3::new(c.x - r, c.y - r, c.z - r), // bottom-left back
-            Vector3::new(0.0, 0.0, 2.0 * r),         // u vector along positive z
-            Vector3::new(0.0, 2.0 * r, 0.0),         // v vector along positive y
-            mat.clone(),
+    /// uv for a point `rel` of the way across the box, with partials for a box
+    /// of `size`. Seen from outside, u runs left to right and v top to bottom;
+    /// the top and bottom are seen with -z up.
+    fn uv(
+        self,
+        rel: Vector3<f64>,
+        size: Vector3<f64>,
+    ) -> (Vector2<f64>, Vector3<f64>, Vector3<f64>) {
+        let (x, y, z) = (
+            Vector3::new(size.x, 0.0, 0.0),
+            Vector3::new(0.0, size.y, 0.0),
+            Vector3::new(0.0, 0.0, size.z),
         );
+        match self {
+            Face::PosZ => (Vector2::new(rel.x, 1.0 - rel.y), x, -y),
+            Face::NegZ => (Vector2::new(1.0 - rel.x, 1.0 - rel.y), -x, -y),
+            Face::PosX => (Vector2::new(1.0 - rel.z, 1.0 - rel.y), -z, -y),
+            Face::NegX => (Vector2::new(rel.z, 1.0 - rel.y), z, -y),
+            Face::PosY => (Vector2::new(rel.x, rel.z), x, z),
+            Face::NegY => (Vector2::new(1.0 - rel.x, rel.z), -x, z),
+        }
+    }
+}
 
-        // Top face (positive Y)
-        let top = Quad::new(
-            Vector3::new(c.x - r, c.y + r, c.z - r), // top-left back
-            Vector3::new(2.0 * r, 0.0, 0.0),         // u vector along positive x
-            Vector3::new(0.0, 0.0, 2.0 * r),         // v vector along positive z
-            mat.clone(),
-        );
+/// An axis-aligned box, with a material for each face.
+pub struct Cube {
+    min: Vector3<f64>,
+    max: Vector3<f64>,
+    // indexed by `Face`
+    mats: [Arc<dyn Material>; 6],
+}
 
-        // Bottom face (negative Y)
-        let bottom = Quad::new(
-            Vector3::new(c.x - r, c.y - r, c.z - r), // bottom-left back
-            Vector3::new(0.0, 0.0, 2.0 * r),         // u vector along positive z
-            Vector3::new(2.0 * r, 0.0, 0.0),         // v vector along positive x
-            mat.clone(),
-        );
+impl Cube {
+    /// c is the center of the cube, r is the half-width (radius)
+    pub fn new(c: Vector3<f64>, r: f64, mat: Arc<dyn Material>) -> Self {
+        let r = Vector3::repeat(r);
+        Self::from_corners(c - r, c + r, mat)
+    }
 
+    /// The box between two opposite corners, in any order.
+    pub fn from_corners(a: Vector3<f64>, b: Vector3<f64>, mat: Arc<dyn Material>) -> Self {
+        let (min, max) = (a.inf(&b), a.sup(&b));
+        assert!((max - min).iter().all(|&s| s > 0.0), "box has no volume");
         Cube {
-            faces: [
-                Box::new(front),
-                Box::new(back),
-                Box::new(top),
-                Box::new(bottom),
-                Box::new(right),
-                Box::new(left),
-            ]
-            .into(),
+            min,
+            max,
+            mats: std::array::from_fn(|_| mat.clone()),
         }
     }
+
+    pub fn with_face_mat(mut self, face: Face, mat: Arc<dyn Material>) -> Self {
+        self.mats[face as usize] = mat;
+        self
+    }
 }
 
 impl Intersectable for Cube {
     fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>> {
-        (self.faces)
-            .as_ref()
-            .into_iter()
-            .filter_map(|face| face.intersect(ray, i))
-            .min_by(Intersection::dist_compare)
+        let (o, d) = (ray.origin(), ray.dir());
+
+        // Slab test, remembering which face each end of the overlap is on
+        let mut near = (f64::NEG_INFINITY, Face::PosX);
+        let mut far = (f64::INFINITY, Face::PosX);
+        for a in 0..3 {
+            let inv = 1.0 / d[a];
+            let mut enter = ((self.min[a] - o[a]) * inv, Face::ALL[2 * a + 1]);
+            let mut exit = ((self.max[a] - o[a]) * inv, Face::ALL[2 * a]);
+            if enter.0 > exit.0 {
+                std::mem::swap(&mut enter, &mut exit);
+            }
+            if enter.0 > near.0 {
+                near = enter;
+            }
+            if exit.0 < far.0 {
+                far = exit;
+            }
+        }
+        if near.0 > far.0 {
+            return None;
+        }
+
+        // From inside the box, the hit is on the way out
+        let (t, face) = if i.contains(near.0) {
+            near
+        } else if i.contains(far.0) {
+            far
+        } else {
+            return None;
+        };
+
+        // Snap onto the face's plane, which leaves no error across it
+        let axis = face.axis();
+        let mut point = ray.at(t);
+        point[axis] = if (face as usize).is_multiple_of(2) {
+            self.max[axis]
+        } else {
+            self.min[axis]
+        };
+        let mut error = point.abs().scale(gamma(3));
+        error[axis] = 0.0;
+
+        let size = self.max - self.min;
+        let rel = (point - self.min).component_div(&size);
+        let (uv, dpdu, dpdv) = face.uv(rel, size);
+
+        Some(
+            Intersection::new(
+                point,
+                t,
+                face.normal(),
+                self.mats[face as usize].as_ref(),
+                uv,
+            )
+            .with_partials(dpdu, dpdv)
+            .with_error(error),
+        )
+    }
+}
+
+impl Bbox for Cube {
+    fn bbox(&self) -> AABB {
+        AABB::from_points(self.min, self.max)
+    }
+}
+
+impl Geomable for Cube {
+    fn into_geoms(self) -> impl Iterator<Item = Geom> {
+        std::iter::once(Geom::Cube(Box::new(self)))
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector2, Vector3};
+
+    use crate::{
+        geom::intersectable::Intersectable,
+        lighting::{
+            color::Color, lambertian::Lambertian, material::Material,
+            texture::solidcolor::SolidColor,
+        },
+        math::{interval::Interval, ray::Ray},
+    };
 
-    // fn bbox(&self) -> AABB {
-    //     AABB::union_all(self.faces.as_ref().into_iter().map(|obj| obj.bbox()))
-    // }
+    use super::{Cube, Face};
+
+    #[test]
+    fn faces_have_their_own_uvs_and_materials() {
+        let mats: Vec<Arc<dyn Material>> = (0..6)
+            .map(|i| -> Arc<dyn Material> {
+                let g = i as f64 / 6.0;
+                Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(
+                    g, g, g,
+                )))))
+            })
+            .collect();
+        let cube = Face::ALL.iter().zip(&mats).fold(
+            Cube::from_corners(Vector3::zeros(), Vector3::repeat(1.0), mats[0].clone()),
+            |cube, (&face, mat)| cube.with_face_mat(face, mat.clone()),
+        );
+
+        // A point a tenth of the way in from each face's top left corner, as
+        // seen from outside with +y up, or -z up for the top and bottom
+        let near_corner = [
+            Vector3::new(1.0, 0.9, 0.9),
+            Vector3::new(0.0, 0.9, 0.1),
+            Vector3::new(0.1, 1.0, 0.1),
+            Vector3::new(0.9, 0.0, 0.1),
+            Vector3::new(0.1, 0.9, 1.0),
+            Vector3::new(0.9, 0.9, 0.0),
+        ];
+        for ((&face, mat), corner) in Face::ALL.iter().zip(&mats).zip(near_corner) {
+            let n = face.normal().into_inner();
+            let center = Vector3::repeat(0.5) + n * 0.5;
+            for (target, uv) in [
+                (center, Vector2::new(0.5, 0.5)),
+                (corner, Vector2::new(0.1, 0.1)),
+            ] {
+                let hit = cube
+                    .intersect(
+                        Ray::new_normalize(target + n, -n),
+                        Interval::new(0.0, f64::MAX),
+                    )
+                    .unwrap();
+                assert!((hit.uv() - uv).norm() < 1e-9, "{:?}: {:?}", face, hit.uv());
+                assert!((hit.normal().into_inner() - n).norm() < 1e-9);
+                assert!(
+                    std::ptr::addr_eq(hit.material(), Arc::as_ptr(mat)),
+                    "{:?}",
+                    face
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use nalgebra::{Unit, Vector2, Vector3};
+
+use crate::{
+    lighting::material::Material,
+    math::{interval::Interval, ray::Ray},
+    util::gamma,
+};
+
+use super::{
+    Geom, Geomable,
+    aabb::AABB,
+    bbox::Bbox,
+    disk,
+    frame::{self, Frame},
+    intersectable::Intersectable,
+    intersection::Intersection,
+};
+
+/// A cylinder running from `base` to `base + axis`, open unless it's given caps.
+/// u goes around the axis, v along it.
+pub struct Cylinder {
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Vector3<f64>,
+        axis: Vector3<f64>,
+        radius: f64,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        assert!(radius > 0.0);
+        let height = axis.norm();
+        assert!(height > 0.0);
+        Cylinder {
+            frame: Frame::new(base, Unit::new_normalize(axis)),
+            radius,
+            height,
+            capped: false,
+            mat,
+        }
+    }
+
+    pub fn with_caps(mut self, capped: bool) -> Self {
+        self.capped = capped;
+        self
+    }
+
+    fn hit_side<'r>(&'r self, ray: &Ray, i: Interval) -> Option<Intersection<'r>> {
+        let (o, d) = (ray.origin(), ray.dir());
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+        let (t0, t1) = frame::solve_quadratic(a, b, c)?;
+
+        let t = [t0, t1]
+            .into_iter()
+            .find(|&t| i.contains(t) && (0.0..=self.height).contains(&ray.at(t).z))?;
+
+        // Push the point back out onto the surface
+        let p = ray.at(t);
+        let out = Vector2::new(p.x, p.y).normalize();
+        let point = Vector3::new(out.x * self.radius, out.y * self.radius, p.z);
+        let error = Vector3::new(point.x.abs(), point.y.abs(), 0.0).scale(gamma(3));
+        let normal = Unit::new_unchecked(Vector3::new(out.x, out.y, 0.0));
+
+        let uv = Vector2::new(frame::turn_fraction(p.x, p.y), p.z / self.height);
+        Some(
+            Intersection::new(point, t, normal, self.mat.as_ref(), uv)
+                .with_partials(frame::dp_dphi(&point), Vector3::new(0.0, 0.0, self.height))
+                .with_error(error),
+        )
+    }
+}
+
+impl Intersectable for Cylinder {
+    fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>> {
+        let local = self.frame.ray_to_local(&ray);
+        let mut hit = self.hit_side(&local, i);
+
+        if self.capped {
+            for (z, normal) in [(0.0, -Vector3::z_axis()), (self.height, Vector3::z_axis())] {
+                let max = hit.as_ref().map_or(i.max, |h| h.dist());
+                let cap = disk::hit_local(
+                    &local,
+                    Interval::new(i.min, max),
+                    z,
+                    self.radius,
+                    0.0,
+                    normal,
+                    self.mat.as_ref(),
+                );
+                if cap.is_some() {
+                    hit = cap;
+                }
+            }
+        }
+
+        hit.map(|h| self.frame.hit_to_world(h))
+    }
+}
+
+impl Bbox for Cylinder {
+    fn bbox(&self) -> AABB {
+        let r = self.radius;
+        self.frame
+            .bbox(Vector3::new(-r, -r, 0.0), Vector3::new(r, r, self.height))
+    }
+}
+
+impl Geomable for Cylinder {
+    fn into_geoms(self) -> impl Iterator<Item = Geom> {
+        std::iter::once(Geom::Cylinder(Box::new(self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector2, Vector3};
+
+    use crate::{
+        geom::{bbox::Bbox, intersectable::Intersectable},
+        lighting::{
+            color::Color, lambertian::Lambertian, material::Material,
+            texture::solidcolor::SolidColor,
+        },
+        math::{interval::Interval, ray::Ray},
+    };
+
+    use super::Cylinder;
+
+    fn mat() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(
+            0.5, 0.5, 0.5,
+        )))))
+    }
+
+    fn near(a: Vector3<f64>, b: Vector3<f64>) -> bool {
+        (a - b).norm() < 1e-3
+    }
+
+    #[test]
+    fn sides_and_caps() {
+        let cyl = Cylinder::new(Vector3::zeros(), Vector3::new(0.0, 0.0, 4.0), 1.0, mat());
+        let capped = Cylinder::new(Vector3::zeros(), Vector3::new(0.0, 0.0, 4.0), 1.0, mat())
+            .with_caps(true);
+        let shoot = |c: &Cylinder, o: Vector3<f64>, d: Vector3<f64>| {
+            c.intersect(Ray::new_normalize(o, d), Interval::new(0.0, f64::MAX))
+                .map(|h| (h.dist(), h.normal().into_inner(), h.uv()))
+        };
+
+        // The side, with or without caps
+        for c in [&cyl, &capped] {
+            let (dist, n, uv) = shoot(c, Vector3::new(5.0, 0.0, 1.0), -Vector3::x()).unwrap();
+            assert!((dist - 4.0).abs() < 1e-9);
+            assert!(near(n, Vector3::x()));
+            assert!((uv - Vector2::new(0.0, 0.25)).norm() < 1e-9);
+            let (_, n, uv) = shoot(c, Vector3::new(0.0, 5.0, 2.0), -Vector3::y()).unwrap();
+            assert!(near(n, Vector3::y()));
+            assert!((uv - Vector2::new(0.25, 0.5)).norm() < 1e-9);
+        }
+
+        // Down the tube: through an open end, onto a cap
+        let top = (Vector3::new(0.5, 0.0, 10.0), -Vector3::z());
+        assert!(shoot(&cyl, top.0, top.1).is_none());
+        let (dist, n, uv) = shoot(&capped, top.0, top.1).unwrap();
+        assert!((dist - 6.0).abs() < 1e-9);
+        assert!(near(n, Vector3::z()));
+        assert!((uv - Vector2::new(0.0, 0.5)).norm() < 1e-9);
+        let (dist, n, _) = shoot(&capped, Vector3::new(0.0, 0.5, -3.0), Vector3::z()).unwrap();
+        assert!((dist - 3.0).abs() < 1e-9);
+        assert!(near(n, -Vector3::z()));
+
+        // Aimed at the side past the top, a capped one is hit on the cap first
+        let o = Vector3::new(-3.0, 0.0, 7.0);
+        let d = Vector3::new(3.0, 0.0, -3.5);
+        let (dist, n, _) = shoot(&capped, o, d).unwrap();
+        assert!(near(n, Vector3::z()));
+        assert!((dist - d.norm() * 6.0 / 7.0).abs() < 1e-9);
+        let (_, n, _) = shoot(&cyl, o, d).unwrap();
+        assert!(n.z.abs() < 1e-9);
+
+        let bb = cyl.bbox();
+        assert!(near(bb.min(), Vector3::new(-1.0, -1.0, 0.0)));
+        assert!(near(bb.max(), Vector3::new(1.0, 1.0, 4.0)));
+        let lying = Cylinder::new(Vector3::zeros(), Vector3::new(4.0, 0.0, 0.0), 1.0, mat()).bbox();
+        assert!(near(lying.min(), Vector3::new(0.0, -1.0, -1.0)));
+        assert!(near(lying.max(), Vector3::new(4.0, 1.0, 1.0)));
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use nalgebra::{UnitVector3, Vector2, Vector3};
+
+use crate::{
+    lighting::material::Material,
+    math::{interval::Interval, ray::Ray},
+    util::gamma,
+};
+
+use super::{
+    Geom, Geomable,
+    aabb::AABB,
+    bbox::Bbox,
+    frame::{self, Frame},
+    intersectable::Intersectable,
+    intersection::Intersection,
+};
+
+/// A flat disk facing along `normal`, or a ring if `inner_radius` isn't zero.
+/// u goes around the center, v from the outer edge in.
+pub struct Disk {
+    frame: Frame,
+    radius: f64,
+    inner_radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(
+        center: Vector3<f64>,
+        normal: UnitVector3<f64>,
+        radius: f64,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        assert!(radius > 0.0);
+        Disk {
+            frame: Frame::new(center, normal),
+            radius,
+            inner_radius: 0.0,
+            mat,
+        }
+    }
+
+    pub fn with_inner_radius(mut self, inner_radius: f64) -> Self {
+        assert!(0.0 <= inner_radius && inner_radius < self.radius);
+        self.inner_radius = inner_radius;
+        self
+    }
+}
+
+/// Where a local-space ray crosses the disk at height `z`, facing `normal`.
+/// Shared with the caps of cylinders and cones.
+pub fn hit_local<'r>(
+    ray: &Ray,
+    i: Interval,
+    z: f64,
+    radius: f64,
+    inner_radius: f64,
+    normal: UnitVector3<f64>,
+    mat: &'r dyn Material,
+) -> Option<Intersection<'r>> {
+    if ray.dir().z == 0.0 {
+        return None;
+    }
+    let t = (z - ray.origin().z) / ray.dir().z;
+    if !i.contains(t) {
+        return None;
+    }
+
+    let p = ray.at(t);
+    let r = p.xy().norm();
+    if r > radius || r < inner_radius {
+        return None;
+    }
+
+    // z is exact, and only error across the normal matters for offsetting rays
+    let point = Vector3::new(p.x, p.y, z);
+    let error = Vector3::new(p.x.abs(), p.y.abs(), 0.0).scale(gamma(5));
+
+    let width = radius - inner_radius;
+    let uv = Vector2::new(frame::turn_fraction(p.x, p.y), (radius - r) / width);
+    let dpdv = if r > 0.0 {
+        Vector3::new(p.x, p.y, 0.0).scale(-width / r)
+    } else {
+        Vector3::zeros()
+    };
+
+    Some(
+        Intersection::new(point, t, normal, mat, uv)
+            .with_partials(frame::dp_dphi(&point), dpdv)
+            .with_error(error),
+    )
+}
+
+impl Intersectable for Disk {
+    fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>> {
+        let local = self.frame.ray_to_local(&ray);
+        let hit = hit_local(
+            &local,
+            i,
+            0.0,
+            self.radius,
+            self.inner_radius,
+            Vector3::z_axis(),
+            self.mat.as_ref(),
+        )?;
+        Some(self.frame.hit_to_world(hit))
+    }
+}
+
+impl Bbox for Disk {
+    fn bbox(&self) -> AABB {
+        let r = self.radius;
+        self.frame
+            .bbox(Vector3::new(-r, -r, 0.0), Vector3::new(r, r, 0.0))
+    }
+}
+
+impl Geomable for Disk {
+    fn into_geoms(self) -> impl Iterator<Item = Geom> {
+        std::iter::once(Geom::Disk(Box::new(self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector2, Vector3};
+
+    use crate::{
+        geom::{bbox::Bbox, intersectable::Intersectable},
+        lighting::{
+            color::Color, lambertian::Lambertian, material::Material,
+            texture::solidcolor::SolidColor,
+        },
+        math::{interval::Interval, ray::Ray},
+    };
+
+    use super::Disk;
+
+    fn mat() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(
+            0.5, 0.5, 0.5,
+        )))))
+    }
+
+    fn near(a: Vector3<f64>, b: Vector3<f64>) -> bool {
+        (a - b).norm() < 1e-3
+    }
+
+    #[test]
+    fn rings_hit_between_their_radii() {
+        let ring = Disk::new(Vector3::new(1.0, 2.0, 3.0), Vector3::z_axis(), 2.0, mat())
+            .with_inner_radius(0.5);
+        let down = |x: f64, y: f64| {
+            ring.intersect(
+                Ray::new(Vector3::new(x, y, 10.0), -Vector3::z_axis()),
+                Interval::new(0.0, f64::MAX),
+            )
+        };
+
+        let hit = down(2.0, 2.0).unwrap();
+        assert!((hit.dist() - 7.0).abs() < 1e-9);
+        assert!(near(hit.point(), Vector3::new(2.0, 2.0, 3.0)));
+        assert!(near(hit.normal().into_inner(), Vector3::z()));
+        // u around from +x, v from the outer edge in
+        assert!((hit.uv() - Vector2::new(0.0, 2.0 / 3.0)).norm() < 1e-9);
+        let hit = down(1.0, 3.5).unwrap();
+        assert!((hit.uv() - Vector2::new(0.25, 1.0 / 3.0)).norm() < 1e-9);
+
+        assert!(down(1.2, 2.0).is_none());
+        assert!(down(3.5, 2.0).is_none());
+
+        let bb = ring.bbox();
+        assert!(near(bb.min(), Vector3::new(-1.0, 0.0, 3.0)));
+        assert!(near(bb.max(), Vector3::new(3.0, 4.0, 3.0)));
+        let side_on = Disk::new(Vector3::zeros(), Vector3::x_axis(), 2.0, mat()).bbox();
+        assert!(near(side_on.min(), Vector3::new(0.0, -2.0, -2.0)));
+        assert!(near(side_on.max(), Vector3::new(0.0, 2.0, 2.0)));
+    }
+}
@@ -0,0 +1,96 @@
+use std::f64::consts::PI;
+
+use nalgebra::{Rotation3, UnitVector3, Vector3};
+
+use crate::{math::ray::Ray, util::gamma};
+
+use super::{aabb::AABB, intersection::Intersection};
+
+/// Where a primitive built around the z axis sits in the world: its origin, and
+/// which way its axis points. Rays are carried in, hits carried back out.
+pub struct Frame {
+    origin: Vector3<f64>,
+    // local to world
+    rot: Rotation3<f64>,
+    rot_inv: Rotation3<f64>,
+}
+
+impl Frame {
+    pub fn new(origin: Vector3<f64>, axis: UnitVector3<f64>) -> Self {
+        // Only fails when the axis is -z
+        let rot = Rotation3::rotation_between(&Vector3::z(), &axis)
+            .unwrap_or(Rotation3::from_axis_angle(&Vector3::x_axis(), PI));
+        Frame {
+            origin,
+            rot,
+            rot_inv: rot.inverse(),
+        }
+    }
+
+    /// The ray in local space. Distances along it are the same in both.
+    pub fn ray_to_local(&self, ray: &Ray) -> Ray {
+        Ray::new(
+            self.rot_inv * (ray.origin() - self.origin),
+            self.rot_inv * ray.dir(),
+        )
+    }
+
+    /// Carry a hit worked out in local space back into the world, error bound included.
+    pub fn hit_to_world<'r>(&self, hit: Intersection<'r>) -> Intersection<'r> {
+        let point = self.origin + self.rot * hit.point();
+        let normal = self.rot * hit.normal();
+        let geo_normal = self.rot * hit.geo_normal();
+
+        let abs_rot = self.rot.matrix().abs();
+        let error = (abs_rot * hit.error()).scale(1.0 + gamma(4))
+            + (abs_rot * hit.point().abs() + self.origin.abs()).scale(gamma(4));
+
+        hit.transformed(point, hit.dist(), normal)
+            .with_geo_normal(geo_normal)
+            .with_error(error)
+            .map_partials(|d| self.rot * d)
+    }
+
+    /// World bounds of the local box from `min` to `max`.
+    pub fn bbox(&self, min: Vector3<f64>, max: Vector3<f64>) -> AABB {
+        let corners = (0..8).map(|i| {
+            let corner = Vector3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+            self.origin + self.rot * corner
+        });
+        let (lo, hi) = corners.fold(
+            (
+                Vector3::repeat(f64::INFINITY),
+                Vector3::repeat(f64::NEG_INFINITY),
+            ),
+            |(lo, hi), p| (lo.inf(&p), hi.sup(&p)),
+        );
+        AABB::from_points(lo, hi)
+    }
+}
+
+/// Angle around the z axis as a fraction of a turn, in [0, 1).
+pub fn turn_fraction(x: f64, y: f64) -> f64 {
+    let phi = f64::atan2(y, x);
+    (if phi < 0.0 { phi + 2.0 * PI } else { phi }) / (2.0 * PI)
+}
+
+/// How a point on a surface of revolution about z moves as its angle goes
+/// around one full turn.
+pub fn dp_dphi(p: &Vector3<f64>) -> Vector3<f64> {
+    Vector3::new(-p.y, p.x, 0.0).scale(2.0 * PI)
+}
+
+/// Both roots of at² + bt + c, nearest first, solved so neither cancels badly.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 || a == 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + b.signum() * disc.sqrt());
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some(if t0 <= t1 { (t0, t1) } else { (t1, t0) })
+}
@@ -7,9 +7,34 @@ use super::{aabb::AABB, intersection::Intersection};
 pub trait Intersectable: Send + Sync {
     fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>>;
 
+    /// Every place the ray crosses the surface in `i`, nearest first.
+    fn intersect_all<'r>(&'r self, ray: Ray, i: Interval) -> Vec<Intersection<'r>> {
+        walk_crossings(self, ray, i)
+    }
+
     // fn bbox(&self) -> AABB;
 }
 
+/// All the crossings in `i`, found by asking for the nearest hit again and
+/// again, each time starting just past the last one.
+pub fn walk_crossings<'r, T: Intersectable + ?Sized>(
+    inner: &'r T,
+    ray: Ray,
+    mut i: Interval,
+) -> Vec<Intersection<'r>> {
+    let mut hits = Vec::new();
+    while let Some(hit) = inner.intersect(ray, i) {
+        // Always move forward, even if a hit lands before the start
+        let next = hit.dist().max(i.min).next_up();
+        hits.push(hit);
+        if next > i.max {
+            break;
+        }
+        i = Interval::new(next, i.max);
+    }
+    hits
+}
+
 impl Intersectable for Arc<dyn Intersectable> {
     fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>> {
         (**self).intersect(ray, i)
@@ -3,14 +3,29 @@ use std::f64::NAN;
 
 use nalgebra::{Unit, UnitVector3, Vector2, Vector3};
 
-use crate::lighting::material::Material;
+use crate::{
+    lighting::{color::Color, material::Material, texture::TextureContext},
+    math::ray::{Differentials, Ray},
+    util::gamma,
+};
 
 pub struct Intersection<'r> {
     point: Vector3<f64>,
+    local_point: Vector3<f64>, // the hit point in the space of the primitive, before any transforms
     dist: f64, //note: this can always be recomputed from the point and the ray at top level.
     normal: Unit<Vector3<f64>>,
+    // The true surface normal, which `normal` may be smoothed away from.
+    geo_normal: UnitVector3<f64>,
+    // Per-axis bound on how far `point` can be from the exact hit.
+    error: Vector3<f64>,
     material: &'r dyn Material,
     uv: Vector2<f64>,
+    // How the surface moves per unit of u and v; zero if the primitive doesn't say.
+    dpdu: Vector3<f64>,
+    dpdv: Vector3<f64>,
+    vertex_color: Option<Color>,
+    prim_id: Option<usize>,   // filled in by the BVH leaf that found this hit
+    object_id: Option<usize>, // filled in by the scene, from the prim id or plane
 }
 
 impl<'r> Intersection<'r> {
@@ -23,13 +38,171 @@ impl<'r> Intersection<'r> {
     ) -> Self {
         Intersection {
             point: point,
+            local_point: point,
             dist: dist,
             normal: normal,
+            geo_normal: normal,
+            // A guess for primitives that don't work theirs out
+            error: point.abs().scale(gamma(7)),
             material: material,
             uv,
+            dpdu: Vector3::zeros(),
+            dpdv: Vector3::zeros(),
+            vertex_color: None,
+            prim_id: None,
+            object_id: None,
         }
     }
 
+    pub fn with_partials(mut self, dpdu: Vector3<f64>, dpdv: Vector3<f64>) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    pub fn with_geo_normal(mut self, n: UnitVector3<f64>) -> Self {
+        self.geo_normal = n;
+        self
+    }
+
+    pub fn with_error(mut self, error: Vector3<f64>) -> Self {
+        self.error = error;
+        self
+    }
+
+    pub fn with_vertex_color(mut self, c: Color) -> Self {
+        self.vertex_color = Some(c);
+        self
+    }
+
+    /// The same hit, seen from the other side of the surface.
+    pub fn flipped(mut self) -> Self {
+        self.normal = -self.normal;
+        self.geo_normal = -self.geo_normal;
+        self
+    }
+
+    /// Whether a ray along `dir` is going into the solid this is the surface of.
+    pub fn is_entering(&self, dir: &Vector3<f64>) -> bool {
+        self.geo_normal.dot(dir) < 0.0
+    }
+
+    /// Carry the surface partials through a linear transform.
+    pub fn map_partials(mut self, f: impl Fn(Vector3<f64>) -> Vector3<f64>) -> Self {
+        self.dpdu = f(self.dpdu);
+        self.dpdv = f(self.dpdv);
+        self
+    }
+
+    /// The same hit, carried out of a transform into its parent's space. The
+    /// geometric normal and the error bound have to be carried over by the caller.
+    pub fn transformed(&self, point: Vector3<f64>, dist: f64, normal: Unit<Vector3<f64>>) -> Self {
+        Intersection {
+            point,
+            local_point: self.local_point,
+            dist,
+            normal,
+            geo_normal: self.geo_normal,
+            error: self.error,
+            material: self.material,
+            uv: self.uv,
+            dpdu: self.dpdu,
+            dpdv: self.dpdv,
+            vertex_color: self.vertex_color,
+            prim_id: self.prim_id,
+            object_id: self.object_id,
+        }
+    }
+
+    /// What textures get to see of this hit, found by `ray`.
+    pub fn tex_context(&self, ray: &Ray) -> TextureContext {
+        let duv = ray
+            .differentials()
+            .and_then(|diff| self.differential_duv(diff))
+            .unwrap_or_else(|| self.cone_duv(ray));
+
+        TextureContext {
+            uv: self.uv,
+            duv,
+            local_point: self.local_point,
+            vertex_color: self.vertex_color,
+        }
+    }
+
+    /// How far uv moves per pixel, from where the neighbouring pixels' rays
+    /// cross the tangent plane.
+    fn differential_duv(&self, diff: &Differentials) -> Option<Vector2<f64>> {
+        let n = self.normal.into_inner();
+        let d = n.dot(&self.point);
+        let on_plane = |o: Vector3<f64>, dir: Vector3<f64>| {
+            let t = (d - n.dot(&o)) / n.dot(&dir);
+            t.is_finite().then(|| o + dir * t - self.point)
+        };
+        let dpdx = on_plane(diff.rx_origin, diff.rx_dir)?;
+        let dpdy = on_plane(diff.ry_origin, diff.ry_dir)?;
+
+        // Least squares for dp = du dpdu + dv dpdv
+        let (uu, uv, vv) = (
+            self.dpdu.dot(&self.dpdu),
+            self.dpdu.dot(&self.dpdv),
+            self.dpdv.dot(&self.dpdv),
+        );
+        let det = uu * vv - uv * uv;
+        if det.abs() < 1e-20 {
+            return None;
+        }
+        let solve = |dp: Vector3<f64>| {
+            let (a, b) = (self.dpdu.dot(&dp), self.dpdv.dot(&dp));
+            Vector2::new((vv * a - uv * b) / det, (uu * b - uv * a) / det)
+        };
+        let (dx, dy) = (solve(dpdx), solve(dpdy));
+
+        Some(Vector2::new(
+            dx.x.abs().max(dy.x.abs()),
+            dx.y.abs().max(dy.y.abs()),
+        ))
+    }
+
+    /// The same from the ray's cone, for rays without differentials.
+    fn cone_duv(&self, ray: &Ray) -> Vector2<f64> {
+        let footprint = ray.width_at(self.dist);
+
+        // The cone's cross-section stretches out as it hits the surface at a glancing angle.
+        let cos = self.normal.dot(&ray.dir()).abs().max(0.05);
+        let on_surface = footprint / cos;
+        let per_uv = |d: &Vector3<f64>| {
+            let n = d.norm();
+            if n > 0.0 { on_surface / n } else { 0.0 }
+        };
+        Vector2::new(per_uv(&self.dpdu), per_uv(&self.dpdv))
+    }
+
+    /// Where a ray leaving the surface towards `dir` should start: pushed off
+    /// along the geometric normal just past the point's error, so it can't hit
+    /// the surface it's leaving, whatever the scene's scale.
+    pub fn offset_origin(&self, dir: &Vector3<f64>) -> Vector3<f64> {
+        let n = self.geo_normal.into_inner();
+        let n = if dir.dot(&n) < 0.0 { -n } else { n };
+        let p = self.point + n * n.abs().dot(&self.error);
+
+        // Adding the offset rounds too, so step one more float outwards. That
+        // also moves points with no error at all off the surface.
+        Vector3::from_fn(|i, _| {
+            if n[i] > 0.0 {
+                p[i].next_up()
+            } else if n[i] < 0.0 {
+                p[i].next_down()
+            } else {
+                p[i]
+            }
+        })
+    }
+
+    /// A ray leaving the surface towards `dir`.
+    pub fn spawn_ray(&self, dir: UnitVector3<f64>) -> Ray {
+        Ray::new(self.offset_origin(&dir), dir)
+    }
+
     pub fn dist_compare(&self, other: &Self) -> Ordering {
         assert!(!self.dist.is_nan());
         assert!(!other.dist.is_nan());
@@ -51,15 +224,86 @@ impl<'r> Intersection<'r> {
         self.point
     }
 
-    pub fn uv(&self) -> Vector2<f64> {
-        self.uv
+    pub fn geo_normal(&self) -> UnitVector3<f64> {
+        self.geo_normal
     }
 
-    pub fn point_mut(&mut self) -> &mut Vector3<f64> {
-        &mut self.point
+    pub fn error(&self) -> Vector3<f64> {
+        self.error
+    }
+
+    pub fn uv(&self) -> Vector2<f64> {
+        self.uv
     }
 
     pub fn material(&self) -> &'r dyn Material {
         self.material
     }
+
+    pub fn dist(&self) -> f64 {
+        self.dist
+    }
+
+    pub fn prim_id(&self) -> Option<usize> {
+        self.prim_id
+    }
+
+    pub fn set_prim_id(&mut self, id: usize) {
+        self.prim_id = Some(id);
+    }
+
+    /// Which source object (scene entry, mesh, ...) was hit; shared by all its primitives.
+    pub fn object_id(&self) -> Option<usize> {
+        self.object_id
+    }
+
+    pub fn set_object_id(&mut self, id: usize) {
+        self.object_id = Some(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::Vector3;
+
+    use crate::{
+        geom::{intersectable::Intersectable, quad::Quad},
+        lighting::{
+            color::Color, lambertian::Lambertian, material::Material,
+            texture::solidcolor::SolidColor,
+        },
+        math::{
+            interval::Interval,
+            ray::{Differentials, Ray},
+        },
+    };
+
+    #[test]
+    fn differentials_give_pixel_footprint_in_uv() {
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+            Color::new(0.5, 0.5, 0.5),
+        ))));
+        // 10 world units per unit of uv along both edges
+        let quad = Quad::new(
+            Vector3::new(-5.0, -5.0, 0.0),
+            Vector3::new(10.0, 0.0, 0.0),
+            Vector3::new(0.0, 10.0, 0.0),
+            mat,
+        );
+        let origin = Vector3::new(0.0, 0.0, 10.0);
+        // Neighbouring pixels land 0.1 over in x and 0.2 over in y
+        let ray = Ray::through_points(origin, Vector3::zeros()).with_differentials(Differentials {
+            rx_origin: origin,
+            rx_dir: (Vector3::new(0.1, 0.0, 0.0) - origin).normalize(),
+            ry_origin: origin,
+            ry_dir: (Vector3::new(0.0, 0.2, 0.0) - origin).normalize(),
+        });
+
+        let hit = quad.intersect(ray, Interval::new(0.0, f64::MAX)).unwrap();
+        let ctx = hit.tex_context(&ray);
+        assert!((ctx.duv.x - 0.01).abs() < 1e-9, "{}", ctx.duv.x);
+        assert!((ctx.duv.y - 0.02).abs() < 1e-9, "{}", ctx.duv.y);
+    }
 }
@@ -0,0 +1,255 @@
+use std::sync::Arc;
+
+use nalgebra::{Unit, Vector2, Vector3};
+
+use crate::{
+    lighting::{color::Color, material::Material},
+    math::{interval::Interval, ray::Ray},
+};
+
+use super::{
+    Geom,
+    aabb::AABB,
+    bbox::Bbox,
+    intersectable::Intersectable,
+    intersection::Intersection,
+    triangle::{self, VertexAttrs},
+};
+
+/// Vertex positions, at full or half the memory.
+pub enum Positions {
+    F64(Vec<Vector3<f64>>),
+    F32(Vec<Vector3<f32>>),
+}
+
+impl Positions {
+    fn get(&self, i: u32) -> Vector3<f64> {
+        match self {
+            Positions::F64(p) => p[i as usize],
+            Positions::F32(p) => p[i as usize].cast(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Positions::F64(p) => p.len(),
+            Positions::F32(p) => p.len(),
+        }
+    }
+}
+
+/// An indexed triangle mesh with one material. Vertices are shared between
+/// faces, and each face is only three `u32`s, so big scans stay small. The BVH
+/// sees it as one `MeshTri` per face.
+pub struct Mesh {
+    positions: Positions,
+    // Shading attributes don't need the precision, so they're always f32
+    normals: Option<Vec<Vector3<f32>>>,
+    uvs: Option<Vec<Vector2<f32>>>,
+    colors: Option<Vec<Vector3<f32>>>,
+    indices: Vec<[u32; 3]>,
+    mat: Arc<dyn Material>,
+}
+
+impl Mesh {
+    /// All the attribute lists that are given must be as long as `positions`.
+    pub fn new(
+        positions: Positions,
+        normals: Option<Vec<Vector3<f32>>>,
+        uvs: Option<Vec<Vector2<f32>>>,
+        colors: Option<Vec<Vector3<f32>>>,
+        indices: Vec<[u32; 3]>,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        let n = positions.len();
+        assert!(normals.as_ref().is_none_or(|a| a.len() == n));
+        assert!(uvs.as_ref().is_none_or(|a| a.len() == n));
+        assert!(colors.as_ref().is_none_or(|a| a.len() == n));
+        assert!(indices.iter().flatten().all(|&i| (i as usize) < n));
+        Mesh {
+            positions,
+            normals,
+            uvs,
+            colors,
+            indices,
+            mat,
+        }
+    }
+
+    /// The same mesh with its positions stored as f32.
+    pub fn into_f32(self) -> Self {
+        let positions = match self.positions {
+            Positions::F64(p) => Positions::F32(p.iter().map(|v| v.cast()).collect()),
+            f32 => f32,
+        };
+        Mesh { positions, ..self }
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn mat(&self) -> &Arc<dyn Material> {
+        &self.mat
+    }
+
+    fn verts(&self, face: u32) -> [Vector3<f64>; 3] {
+        self.indices[face as usize].map(|i| self.positions.get(i))
+    }
+}
+
+/// One face of a `Mesh`.
+pub struct MeshTri {
+    mesh: Arc<Mesh>,
+    face: u32,
+}
+
+impl MeshTri {
+    /// Every face of `mesh`.
+    pub fn all(mesh: Arc<Mesh>) -> impl Iterator<Item = MeshTri> {
+        assert!(mesh.face_count() <= u32::MAX as usize);
+        (0..mesh.face_count() as u32).map(move |face| MeshTri {
+            mesh: mesh.clone(),
+            face,
+        })
+    }
+}
+
+impl Intersectable for MeshTri {
+    fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>> {
+        let [a, b, c] = self.mesh.verts(self.face);
+        let (t, u, v) = triangle::hit_barycentric(&ray, i, &a, &b, &c)?;
+
+        let idx = self.mesh.indices[self.face as usize];
+        let attrs = VertexAttrs {
+            normals: self
+                .mesh
+                .normals
+                .as_ref()
+                .map(|n| idx.map(|i| Unit::new_normalize(n[i as usize].cast()))),
+            uvs: self
+                .mesh
+                .uvs
+                .as_ref()
+                .map(|t| idx.map(|i| t[i as usize].cast())),
+            colors: self
+                .mesh
+                .colors
+                .as_ref()
+                .map(|c| idx.map(|i| Color::from_vec(c[i as usize].cast()))),
+        };
+        let flat = Unit::try_new((b - a).cross(&(c - a)), 1e-20)
+            .unwrap_or(Unit::new_unchecked(Vector3::y()));
+        Some(triangle::surface_hit(
+            t,
+            (u, v),
+            [a, b, c],
+            flat,
+            &attrs,
+            self.mesh.mat.as_ref(),
+        ))
+    }
+}
+
+impl Bbox for MeshTri {
+    fn bbox(&self) -> AABB {
+        let [a, b, c] = self.mesh.verts(self.face);
+        AABB::union(&AABB::from_points(a, b), &AABB::from_points(a, c))
+    }
+}
+
+impl super::Geomable for MeshTri {
+    fn into_geoms(self) -> impl Iterator<Item = Geom> {
+        std::iter::once(Geom::MeshTri(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use nalgebra::Vector3;
+
+    use crate::{
+        geom::intersectable::Intersectable,
+        lighting::{color::Color, lambertian::Lambertian, texture::solidcolor::SolidColor},
+        math::{interval::Interval, ray::Ray},
+    };
+
+    use super::{Mesh, MeshTri, Positions};
+
+    // A [-1, 1] cube with each side cut into n x n quads, sharing every
+    // vertex between the faces around it
+    fn subdivided_cube(n: usize) -> Mesh {
+        let mut positions = Vec::new();
+        let mut ids: HashMap<[usize; 3], u32> = HashMap::new();
+        let mut vertex = |p: [usize; 3]| {
+            *ids.entry(p).or_insert_with(|| {
+                positions.push(p.map(|c| 2.0 * c as f64 / n as f64 - 1.0).into());
+                (positions.len() - 1) as u32
+            })
+        };
+
+        let mut indices = Vec::new();
+        for axis in 0..3 {
+            for side in [0, n] {
+                let at = |i: usize, j: usize| {
+                    let mut p = [0; 3];
+                    p[axis] = side;
+                    p[(axis + 1) % 3] = i;
+                    p[(axis + 2) % 3] = j;
+                    p
+                };
+                for i in 0..n {
+                    for j in 0..n {
+                        let [a, b, c, d] = [at(i, j), at(i + 1, j), at(i + 1, j + 1), at(i, j + 1)]
+                            .map(&mut vertex);
+                        indices.push([a, b, c]);
+                        indices.push([a, c, d]);
+                    }
+                }
+            }
+        }
+
+        let mat = Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::white()))));
+        Mesh::new(Positions::F64(positions), None, None, None, indices, mat)
+    }
+
+    #[test]
+    fn closed_mesh_is_watertight() {
+        let mesh = Arc::new(subdivided_cube(4));
+        let tris: Vec<MeshTri> = MeshTri::all(mesh.clone()).collect();
+        let hits = |ray: Ray| {
+            tris.iter()
+                .any(|t| t.intersect(ray, Interval::new(0.0, f64::MAX)).is_some())
+        };
+
+        let mut rays = Vec::new();
+        // A grid over the top, lined up with its vertices, edges and diagonals,
+        // both straight down and at an angle
+        for i in 0..=12 {
+            for j in 0..=12 {
+                let target = Vector3::new(-0.75 + 0.125 * i as f64, -0.75 + 0.125 * j as f64, 1.0);
+                for dir in [Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.3, -0.2, -1.0)] {
+                    rays.push(Ray::new_normalize(target - 5.0 * dir, dir));
+                }
+            }
+        }
+        // Every vertex and edge midpoint, corners and the cube's own edges
+        // included, from outside towards the center
+        for face in &mesh.indices {
+            let [a, b, c] = face.map(|i| mesh.positions.get(i));
+            for target in [a, b, c, (a + b) / 2.0, (b + c) / 2.0, (c + a) / 2.0] {
+                rays.push(Ray::new_normalize(3.0 * target, -target));
+            }
+        }
+
+        let misses: Vec<_> = rays.iter().filter(|r| !hits(**r)).collect();
+        assert!(
+            misses.is_empty(),
+            "{} of {} rays missed",
+            misses.len(),
+            rays.len()
+        );
+    }
+}
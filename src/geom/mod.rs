@@ -6,25 +6,42 @@ use crate::math::ray::Ray;
 use crate::math::raypacket::RayPacket;
 use aabb::AABB;
 use bbox::Bbox;
-use intersectable::Intersectable;
+use cone::Cone;
+use csg::Csg;
+use cube::Cube;
+use cylinder::Cylinder;
+use disk::Disk;
+use intersectable::{Intersectable, walk_crossings};
 use intersection::Intersection;
+use mesh::MeshTri;
 use quad::Quad;
 use rotation::Rotation;
 use scaling::Scaling;
 use sphere::Sphere;
+use torus::Torus;
 use translation::Translation;
 use triangle::Triangle;
 
 pub mod aabb;
 pub mod bbox;
 pub mod bvh;
+pub mod cone;
+pub mod csg;
 pub mod cube;
+pub mod cylinder;
+pub mod disk;
+pub mod frame;
 pub mod intersectable;
 pub mod intersection;
+pub mod mesh;
+pub mod plane;
+pub mod ply;
 pub mod quad;
 pub mod rotation;
 pub mod scaling;
 pub mod sphere;
+pub mod stl;
+pub mod torus;
 pub mod translation;
 pub mod triangle;
 pub mod trimesh;
@@ -32,7 +49,15 @@ pub mod trimesh;
 pub enum Geom {
     Quad(Box<Quad>),
     Tri(Box<Triangle>),
+    // Not boxed: it's already just a pointer to the mesh and a face index
+    MeshTri(MeshTri),
     Sphere(Box<Sphere>),
+    Cube(Box<Cube>),
+    Disk(Box<Disk>),
+    Cylinder(Box<Cylinder>),
+    Cone(Box<Cone>),
+    Torus(Box<Torus>),
+    Csg(Box<Csg>),
     Rot(Box<Rotation<Geom>>),
     Scale(Box<Scaling<Geom>>),
     Trans(Box<Translation<Geom>>),
@@ -43,12 +68,27 @@ impl Intersectable for Geom {
         match self {
             Geom::Quad(q) => q.intersect(ray, i),
             Geom::Tri(triangle) => triangle.intersect(ray, i),
+            Geom::MeshTri(tri) => tri.intersect(ray, i),
             Geom::Sphere(sphere) => sphere.intersect(ray, i),
+            Geom::Cube(cube) => cube.intersect(ray, i),
+            Geom::Disk(disk) => disk.intersect(ray, i),
+            Geom::Cylinder(cylinder) => cylinder.intersect(ray, i),
+            Geom::Cone(cone) => cone.intersect(ray, i),
+            Geom::Torus(torus) => torus.intersect(ray, i),
+            Geom::Csg(csg) => csg.intersect(ray, i),
             Geom::Rot(rotation) => rotation.intersect(ray, i),
             Geom::Scale(scaling) => scaling.intersect(ray, i),
             Geom::Trans(translation) => translation.intersect(ray, i),
         }
     }
+
+    fn intersect_all<'r>(&'r self, ray: Ray, i: Interval) -> Vec<Intersection<'r>> {
+        match self {
+            // Works out all its crossings in one go anyway
+            Geom::Csg(csg) => csg.intersect_all(ray, i),
+            _ => walk_crossings(self, ray, i),
+        }
+    }
 }
 
 impl Bbox for Geom {
@@ -56,7 +96,14 @@ impl Bbox for Geom {
         match self {
             Geom::Quad(quad) => quad.bbox(),
             Geom::Tri(triangle) => triangle.bbox(),
+            Geom::MeshTri(tri) => tri.bbox(),
             Geom::Sphere(sphere) => sphere.bbox(),
+            Geom::Cube(cube) => cube.bbox(),
+            Geom::Disk(disk) => disk.bbox(),
+            Geom::Cylinder(cylinder) => cylinder.bbox(),
+            Geom::Cone(cone) => cone.bbox(),
+            Geom::Torus(torus) => torus.bbox(),
+            Geom::Csg(csg) => csg.bbox(),
             Geom::Rot(rotation) => rotation.bbox(),
             Geom::Scale(scaling) => scaling.bbox(),
             Geom::Trans(translation) => translation.bbox(),
@@ -64,6 +111,27 @@ impl Bbox for Geom {
     }
 }
 
+impl Geom {
+    /// Human-readable description of the geometry, transforms included, e.g. `translate(scale(triangle))`.
+    pub fn describe(&self) -> String {
+        match self {
+            Geom::Quad(_) => String::from("quad"),
+            Geom::Tri(_) => String::from("triangle"),
+            Geom::MeshTri(_) => String::from("mesh triangle"),
+            Geom::Sphere(_) => String::from("sphere"),
+            Geom::Cube(_) => String::from("cube"),
+            Geom::Disk(_) => String::from("disk"),
+            Geom::Cylinder(_) => String::from("cylinder"),
+            Geom::Cone(_) => String::from("cone"),
+            Geom::Torus(_) => String::from("torus"),
+            Geom::Csg(csg) => csg.describe(),
+            Geom::Rot(rotation) => format!("rotate({})", rotation.inner().describe()),
+            Geom::Scale(scaling) => format!("scale({})", scaling.inner().describe()),
+            Geom::Trans(translation) => format!("translate({})", translation.inner().describe()),
+        }
+    }
+}
+
 pub trait Geomable {
     fn into_geoms(self) -> impl Iterator<Item = Geom>;
 }
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use nalgebra::{UnitVector3, Vector2, Vector3};
+
+use crate::{
+    lighting::material::Material,
+    math::{interval::Interval, ray::Ray},
+    util::gamma,
+};
+
+use super::{frame::Frame, intersectable::Intersectable, intersection::Intersection};
+
+/// A plane through `point` that goes on forever. It has no bounding box, so the
+/// scene keeps planes beside the BVH rather than in it.
+pub struct Plane {
+    point: Vector3<f64>,
+    normal: UnitVector3<f64>,
+    frame: Frame,
+    // World units per unit of uv
+    uv_scale: f64,
+    mat: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Vector3<f64>, normal: UnitVector3<f64>, mat: Arc<dyn Material>) -> Self {
+        Plane {
+            point,
+            normal,
+            frame: Frame::new(point, normal),
+            uv_scale: 1.0,
+            mat,
+        }
+    }
+
+    pub fn with_uv_scale(mut self, uv_scale: f64) -> Self {
+        assert!(uv_scale > 0.0);
+        self.uv_scale = uv_scale;
+        self
+    }
+
+    pub fn point(&self) -> Vector3<f64> {
+        self.point
+    }
+
+    pub fn normal(&self) -> UnitVector3<f64> {
+        self.normal
+    }
+}
+
+impl Intersectable for Plane {
+    fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>> {
+        let local = self.frame.ray_to_local(&ray);
+        if local.dir().z == 0.0 {
+            return None;
+        }
+        let t = -local.origin().z / local.dir().z;
+        if !i.contains(t) {
+            return None;
+        }
+
+        let p = local.at(t);
+        let point = Vector3::new(p.x, p.y, 0.0);
+        let error = Vector3::new(p.x.abs(), p.y.abs(), 0.0).scale(gamma(3));
+        let s = self.uv_scale;
+        let hit = Intersection::new(
+            point,
+            t,
+            Vector3::z_axis(),
+            self.mat.as_ref(),
+            Vector2::new(p.x / s, p.y / s),
+        )
+        .with_partials(Vector3::new(s, 0.0, 0.0), Vector3::new(0.0, s, 0.0))
+        .with_error(error);
+        Some(self.frame.hit_to_world(hit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector2, Vector3};
+
+    use crate::{
+        geom::intersectable::Intersectable,
+        lighting::{
+            color::Color, lambertian::Lambertian, material::Material,
+            texture::solidcolor::SolidColor,
+        },
+        math::{interval::Interval, ray::Ray},
+    };
+
+    use super::Plane;
+
+    fn mat() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(
+            0.5, 0.5, 0.5,
+        )))))
+    }
+
+    fn near(a: Vector3<f64>, b: Vector3<f64>) -> bool {
+        (a - b).norm() < 1e-3
+    }
+
+    #[test]
+    fn hits_and_uvs() {
+        let ground = Plane::new(Vector3::zeros(), Vector3::z_axis(), mat()).with_uv_scale(2.0);
+        let hit = ground
+            .intersect(
+                Ray::new(Vector3::new(3.0, -4.0, 5.0), -Vector3::z_axis()),
+                Interval::new(0.0, f64::MAX),
+            )
+            .unwrap();
+        assert!((hit.dist() - 5.0).abs() < 1e-9);
+        assert!(near(hit.normal().into_inner(), Vector3::z()));
+        assert!((hit.uv() - Vector2::new(1.5, -2.0)).norm() < 1e-9);
+
+        // Parallel rays never get there
+        let along = Ray::new(Vector3::new(0.0, 0.0, 1.0), Vector3::x_axis());
+        assert!(
+            ground
+                .intersect(along, Interval::new(0.0, f64::MAX))
+                .is_none()
+        );
+
+        let wall = Plane::new(Vector3::new(0.0, 1.0, 0.0), Vector3::y_axis(), mat());
+        let hit = wall
+            .intersect(
+                Ray::new_normalize(Vector3::new(3.0, 5.0, -4.0), -Vector3::y()),
+                Interval::new(0.0, f64::MAX),
+            )
+            .unwrap();
+        assert!((hit.dist() - 4.0).abs() < 1e-9);
+        assert!(near(hit.point(), Vector3::new(3.0, 1.0, -4.0)));
+        assert!(near(hit.normal().into_inner(), Vector3::y()));
+        assert!((hit.uv().norm() - 5.0).abs() < 1e-9);
+        // Behind the ray
+        let away = Ray::new_normalize(Vector3::new(3.0, 5.0, -4.0), Vector3::y());
+        assert!(wall.intersect(away, Interval::new(0.0, f64::MAX)).is_none());
+    }
+}
@@ -0,0 +1,418 @@
+use std::fs;
+
+use nalgebra::{Unit, Vector2, Vector3};
+
+use crate::lighting::color::{Color, srgb_to_linear};
+
+use super::trimesh::{MeshData, MeshError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLe,
+    BinaryBe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self, MeshError> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(parse_err(format!("unknown property type {}", name))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    // count type, item type
+    List(String, Scalar, Scalar),
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar(n, _) | Property::List(n, _, _) => n,
+        }
+    }
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    props: Vec<Property>,
+}
+
+fn parse_err(msg: impl Into<String>) -> MeshError {
+    MeshError::Parse(msg.into())
+}
+
+/// Reads values out of the body of the file, one at a time.
+struct Body<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Body<'_> {
+    fn read(&mut self, ty: Scalar) -> Result<f64, MeshError> {
+        if self.format == Format::Ascii {
+            return self.read_ascii();
+        }
+
+        let n = ty.size();
+        let Some(raw) = self.bytes.get(self.pos..self.pos + n) else {
+            return Err(parse_err("file ends early"));
+        };
+        self.pos += n;
+        let mut b = [0u8; 8];
+        b[..n].copy_from_slice(raw);
+        if self.format == Format::BinaryBe {
+            b[..n].reverse();
+        }
+        Ok(match ty {
+            Scalar::I8 => b[0] as i8 as f64,
+            Scalar::U8 => b[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(b),
+        })
+    }
+
+    fn read_ascii(&mut self) -> Result<f64, MeshError> {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(parse_err("file ends early"));
+        }
+        let tok = std::str::from_utf8(&self.bytes[start..self.pos])
+            .map_err(|_| parse_err("non-ascii number"))?;
+        tok.parse()
+            .map_err(|_| parse_err(format!("{} isn't a number", tok)))
+    }
+}
+
+fn parse_header(text: &str) -> Result<(Format, Vec<Element>), MeshError> {
+    let mut lines = text.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(parse_err("missing ply magic"));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", f, _] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLe,
+                    "binary_big_endian" => Format::BinaryBe,
+                    _ => return Err(parse_err(format!("unknown format {}", f))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| parse_err(format!("bad count for {}", name)))?,
+                props: Vec::new(),
+            }),
+            ["property", "list", count_ty, item_ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| parse_err("property before any element"))?
+                .props
+                .push(Property::List(
+                    name.to_string(),
+                    Scalar::parse(count_ty)?,
+                    Scalar::parse(item_ty)?,
+                )),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| parse_err("property before any element"))?
+                .props
+                .push(Property::Scalar(name.to_string(), Scalar::parse(ty)?)),
+            ["end_header"] => break,
+            // comment, obj_info, blank lines
+            _ => {}
+        }
+    }
+
+    let format = format.ok_or_else(|| parse_err("missing format line"))?;
+    Ok((format, elements))
+}
+
+/// Read an ASCII or binary PLY file. Vertices can carry normals (`nx ny nz`),
+/// texture coordinates (`u v`, `s t` or `texture_u texture_v`) and colors
+/// (`red green blue`, as sRGB bytes or floats). Faces come from the
+/// `vertex_indices` (or `vertex_index`) list of the `face` element.
+pub fn read(fname: &str) -> Result<MeshData, MeshError> {
+    let bytes = fs::read(fname)?;
+
+    const END: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END.len())
+        .position(|w| w == END)
+        .ok_or_else(|| parse_err("missing end_header"))?;
+    // The body starts after the newline ending the header
+    let body_start = bytes[end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|p| end + p + 1)
+        .ok_or_else(|| parse_err("missing end_header"))?;
+    let header = String::from_utf8_lossy(&bytes[..body_start]);
+    let (format, elements) = parse_header(&header)?;
+
+    let mut body = Body {
+        format,
+        bytes: &bytes[body_start..],
+        pos: 0,
+    };
+
+    let mut data = MeshData {
+        positions: Vec::new(),
+        normals: None,
+        uvs: None,
+        colors: None,
+        faces: Vec::new(),
+    };
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut colors = Vec::new();
+
+    for element in &elements {
+        let index_of =
+            |names: &[&str]| element.props.iter().position(|p| names.contains(&p.name()));
+        let xyz = [index_of(&["x"]), index_of(&["y"]), index_of(&["z"])];
+        let nxyz = [index_of(&["nx"]), index_of(&["ny"]), index_of(&["nz"])];
+        let uv = [
+            index_of(&["u", "s", "texture_u"]),
+            index_of(&["v", "t", "texture_v"]),
+        ];
+        let rgb = [
+            index_of(&["red", "r"]),
+            index_of(&["green", "g"]),
+            index_of(&["blue", "b"]),
+        ];
+        let color_is_byte =
+            rgb[0].is_some_and(|i| matches!(element.props[i], Property::Scalar(_, Scalar::U8)));
+        let indices = index_of(&["vertex_indices", "vertex_index"]);
+
+        for _ in 0..element.count {
+            let mut scalars = vec![0.0; element.props.len()];
+            let mut list = Vec::new();
+            for (i, prop) in element.props.iter().enumerate() {
+                match prop {
+                    Property::Scalar(_, ty) => scalars[i] = body.read(*ty)?,
+                    Property::List(_, count_ty, item_ty) => {
+                        let n = body.read(*count_ty)? as usize;
+                        let items = (0..n)
+                            .map(|_| body.read(*item_ty).map(|v| v as usize))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        if Some(i) == indices {
+                            list = items;
+                        }
+                    }
+                }
+            }
+
+            match element.name.as_str() {
+                "vertex" => {
+                    let get = |idx: [Option<usize>; 3]| {
+                        let [x, y, z] = idx.map(|i| i.map(|i| scalars[i]));
+                        Some(Vector3::new(x?, y?, z?))
+                    };
+                    data.positions
+                        .push(get(xyz).ok_or_else(|| parse_err("vertex without x, y, z"))?);
+                    if let Some(n) = get(nxyz) {
+                        normals.push(Unit::try_new(n, 1e-20).unwrap_or(Vector3::y_axis()));
+                    }
+                    if let [Some(u), Some(v)] = uv {
+                        // Like OBJ, v = 0 is the bottom of the image
+                        uvs.push(Vector2::new(scalars[u], 1.0 - scalars[v]));
+                    }
+                    if let Some(c) = get(rgb) {
+                        // Byte colors are sRGB encoded; float ones are taken as linear
+                        let c = if color_is_byte {
+                            c.map(|x| srgb_to_linear(x / 255.0))
+                        } else {
+                            c
+                        };
+                        let mut c = Color::from_vec(c);
+                        c.clamp();
+                        colors.push(c);
+                    }
+                }
+                "face" => data.faces.push(list),
+                _ => {}
+            }
+        }
+    }
+
+    let n = data.positions.len();
+    data.normals = (normals.len() == n && n > 0).then_some(normals);
+    data.uvs = (uvs.len() == n && n > 0).then_some(uvs);
+    data.colors = (colors.len() == n && n > 0).then_some(colors);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector2, Vector3};
+
+    use super::read;
+    use crate::{
+        geom::{
+            Geomable,
+            intersectable::Intersectable,
+            trimesh::{DEFAULT_CREASE_ANGLE, MeshData, TriMesh},
+        },
+        lighting::{
+            color::{Color, srgb_to_linear},
+            lambertian::Lambertian,
+            texture::{Texture, solidcolor::SolidColor, vertexcolor::VertexColor},
+        },
+        math::{interval::Interval, ray::Ray},
+    };
+
+    fn load(name: &str, bytes: &[u8]) -> MeshData {
+        let path =
+            std::env::temp_dir().join(format!("ply_test_{}_{}.ply", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        let loaded = read(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        loaded.unwrap()
+    }
+
+    #[test]
+    fn ascii_quad_with_normals_and_uvs() {
+        let text = "ply
+format ascii 1.0
+comment a unit square
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float s
+property float t
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 0 0
+1 0 0 0 0 1 1 0
+1 1 0 0 0 1 1 1
+0 1 0 0 0 1 0 1
+4 0 1 2 3
+";
+        let data = load("ascii", text.as_bytes());
+        assert_eq!(data.positions[2], Vector3::new(1.0, 1.0, 0.0));
+        assert_eq!(data.faces, vec![vec![0, 1, 2, 3]]);
+        let normals = data.normals.unwrap();
+        assert!(normals.iter().all(|n| n.into_inner() == Vector3::z()));
+        // v is flipped, as for OBJ
+        let uvs = data.uvs.unwrap();
+        assert_eq!(uvs[1], Vector2::new(1.0, 1.0));
+        assert_eq!(uvs[3], Vector2::new(0.0, 0.0));
+        assert!(data.colors.is_none());
+    }
+
+    #[test]
+    fn binary_vertex_colors_reach_the_texture() {
+        let header = |format: &str| {
+            format!(
+                "ply\nformat {} 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+                 property float z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\n\
+                 element face 1\nproperty list uchar int vertex_indices\nend_header\n",
+                format
+            )
+        };
+        let verts = [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let rgb = [255u8, 128, 0];
+
+        let little = {
+            let mut bytes = header("binary_little_endian").into_bytes();
+            for v in verts {
+                bytes.extend(v.iter().flat_map(|c| c.to_le_bytes()));
+                bytes.extend(rgb);
+            }
+            bytes.push(3);
+            bytes.extend([0i32, 1, 2].iter().flat_map(|i| i.to_le_bytes()));
+            bytes
+        };
+        let big = {
+            let mut bytes = header("binary_big_endian").into_bytes();
+            for v in verts {
+                bytes.extend(v.iter().flat_map(|c| c.to_be_bytes()));
+                bytes.extend(rgb);
+            }
+            bytes.push(3);
+            bytes.extend([0i32, 1, 2].iter().flat_map(|i| i.to_be_bytes()));
+            bytes
+        };
+
+        let fallback: Arc<dyn Texture> = Arc::new(SolidColor::new(Color::white()));
+        let tex = VertexColor::new(fallback.clone());
+        let expected = Vector3::new(1.0, srgb_to_linear(128.0 / 255.0), 0.0);
+        for (name, bytes) in [("le", little), ("be", big)] {
+            let data = load(name, &bytes);
+            assert_eq!(data.positions[1], Vector3::new(1.0, 0.0, 0.0), "{}", name);
+            assert_eq!(data.faces, vec![vec![0, 1, 2]], "{}", name);
+
+            let mat = Arc::new(Lambertian::new(fallback.clone()));
+            let mesh = TriMesh::from_data(data, mat, DEFAULT_CREASE_ANGLE).unwrap();
+            let tri = mesh.into_geoms().next().unwrap();
+            let ray = Ray::new(Vector3::new(0.25, 0.25, 1.0), -Vector3::z_axis());
+            let hit = tri.intersect(ray, Interval::new(0.0, f64::MAX)).unwrap();
+            let c = tex.color_at(&hit.tex_context(&ray)).inner_vec();
+            assert!((c - expected).norm() < 1e-6, "{}: {:?}", name, c);
+        }
+    }
+}
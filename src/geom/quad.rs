@@ -6,6 +6,7 @@ use crate::{
     geom::Geom,
     lighting::material::Material,
     math::{interval::Interval, ray::Ray},
+    util::gamma,
 };
 
 use super::{
@@ -70,19 +71,25 @@ impl Intersectable for Quad {
 
         let p = ray.at(t);
         let planar_hitpt_vector = p - self.q;
-        let u = self.w.dot(&planar_hitpt_vector.cross(&self.v_hat));
-        let v = 1.0 - self.w.dot(&self.u_hat.cross(&planar_hitpt_vector));
+        let alpha = self.w.dot(&planar_hitpt_vector.cross(&self.v_hat));
+        let beta = self.w.dot(&self.u_hat.cross(&planar_hitpt_vector));
+        let (u, v) = (alpha, 1.0 - beta);
 
         if !(Interval::UNIT.contains(u) && Interval::UNIT.contains(v)) {
             return None;
         }
-        return Some(Intersection::new(
-            ray.at(t),
-            t,
-            self.normal,
-            self.mat.as_ref(),
-            Vector2::new(u, v),
-        ));
+
+        // Rebuilt from the quad's own edges, the point is exactly as far off as its terms
+        let point = self.q + self.u_hat.scale(alpha) + self.v_hat.scale(beta);
+        let error = (self.q.abs() + self.u_hat.scale(alpha).abs() + self.v_hat.scale(beta).abs())
+            .scale(gamma(7));
+
+        Some(
+            Intersection::new(point, t, self.normal, self.mat.as_ref(), Vector2::new(u, v))
+                // v runs from the far edge back towards q
+                .with_partials(self.u_hat, -self.v_hat)
+                .with_error(error),
+        )
     }
 }
 
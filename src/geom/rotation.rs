@@ -2,7 +2,10 @@ use std::sync::Arc;
 
 use nalgebra::{Matrix3, Rotation3, Unit, Vector3};
 
-use crate::math::{interval::Interval, ray::Ray};
+use crate::{
+    math::{interval::Interval, ray::Ray},
+    util::gamma,
+};
 
 use super::{
     Geomable, aabb::AABB, bbox::Bbox, intersectable::Intersectable, intersection::Intersection,
@@ -26,6 +29,10 @@ impl<T> Rotation<T> {
         }
     }
 
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
     // Alternative constructor using Euler angles (roll, pitch, yaw)
     pub fn from_euler(roll: f64, pitch: f64, yaw: f64, inner: T) -> Self {
         let rotation = Rotation3::from_euler_angles(roll, pitch, yaw);
@@ -59,14 +66,19 @@ impl<T: Intersectable> Intersectable for Rotation<T> {
 
         // Transform normal back to world space
         let normal = Unit::new_normalize(self.rotation * int.normal().into_inner());
-
-        Some(Intersection::new(
-            point,
-            dist,
-            normal,
-            int.material(),
-            int.uv(),
-        ))
+        let geo_normal = Unit::new_normalize(self.rotation * int.geo_normal().into_inner());
+
+        // The point's old error gets rotated, and rotating it adds some more
+        let abs_rot = self.rotation.matrix().abs();
+        let error = (abs_rot * int.error()).scale(1.0 + gamma(3))
+            + (abs_rot * int.point().abs()).scale(gamma(3));
+
+        Some(
+            int.transformed(point, dist, normal)
+                .with_geo_normal(geo_normal)
+                .with_error(error)
+                .map_partials(|d| self.rotation * d),
+        )
     }
 }
 
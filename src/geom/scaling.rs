@@ -2,7 +2,10 @@ use std::sync::Arc;
 
 use nalgebra::{SimdPartialOrd, Unit, Vector3};
 
-use crate::math::{interval::Interval, ray::Ray};
+use crate::{
+    math::{interval::Interval, ray::Ray},
+    util::gamma,
+};
 
 use super::{
     Geomable, aabb::AABB, bbox::Bbox, intersectable::Intersectable, intersection::Intersection,
@@ -25,6 +28,10 @@ impl<T> Scaling<T> {
             inner,
         }
     }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
 }
 
 impl<T: Intersectable> Intersectable for Scaling<T> {
@@ -47,14 +54,17 @@ impl<T: Intersectable> Intersectable for Scaling<T> {
             return None;
         }
         let scaled_normal = Unit::new_normalize(scaled_normal);
-
-        Some(Intersection::new(
-            point,
-            dist,
-            scaled_normal,
-            int.material(),
-            int.uv(),
-        ))
+        let geo_normal = Unit::try_new(int.geo_normal().component_mul(&self.scale_inv), 1e-10)
+            .unwrap_or(scaled_normal);
+        let error = int.error().component_mul(&self.scale).scale(1.0 + gamma(1))
+            + point.abs().scale(gamma(1));
+
+        Some(
+            int.transformed(point, dist, scaled_normal)
+                .with_geo_normal(geo_normal)
+                .with_error(error)
+                .map_partials(|d| d.component_mul(&self.scale)),
+        )
     }
 
     // fn bbox(&self) -> super::aabb::AABB
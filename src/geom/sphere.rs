@@ -6,6 +6,7 @@ use crate::{
     geom::{intersectable::Intersectable, intersection::Intersection},
     lighting::material::Material,
     math::{interval::Interval, ray::Ray},
+    util::gamma,
 };
 
 use super::{Geom, Geomable, aabb::AABB, bbox::Bbox};
@@ -40,6 +41,15 @@ impl Sphere {
 
         Vector2::new(u, v)
     }
+
+    /// Derivatives of the surface point with respect to the uv from `unit_sphere_uv`.
+    fn uv_partials(&self, n: &UnitVector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
+        // Distance from the poles' axis; guarded so the poles don't divide by zero.
+        let s = f64::sqrt(n.x * n.x + n.z * n.z).max(1e-12);
+        let dpdu = Vector3::new(n.z, 0.0, -n.x).scale(2.0 * PI * self.radius);
+        let dpdv = Vector3::new(-n.y * n.x / s, s, -n.y * n.z / s).scale(PI * self.radius);
+        (dpdu, dpdv)
+    }
 }
 
 impl Intersectable for Sphere {
@@ -64,18 +74,19 @@ impl Intersectable for Sphere {
             }
         }
 
-        let point = ray.at(dist);
-        let normal = Unit::new_normalize(point - self.center); // can also divide by the radius...
+        let normal = Unit::new_normalize(ray.at(dist) - self.center); // can also divide by the radius...
+        // Put the point back on the sphere; that's much closer than ray.at(dist) is
+        let point = self.center + normal.scale(self.radius);
+        let error = (self.center.abs() + normal.abs().scale(self.radius)).scale(gamma(6));
 
         let uv = Sphere::unit_sphere_uv(&normal);
+        let (dpdu, dpdv) = self.uv_partials(&normal);
 
-        Some(Intersection::new(
-            point,
-            dist,
-            normal,
-            self.material.as_ref(),
-            uv,
-        ))
+        Some(
+            Intersection::new(point, dist, normal, self.material.as_ref(), uv)
+                .with_partials(dpdu, dpdv)
+                .with_error(error),
+        )
     }
 }
 
@@ -0,0 +1,147 @@
+use std::{collections::HashMap, fs};
+
+use nalgebra::Vector3;
+
+use super::trimesh::{MeshData, MeshError};
+
+/// Read an ASCII or binary STL file. STL stores every triangle separately, so
+/// vertices at the same position are merged to let normals be smoothed across them.
+pub fn read(fname: &str) -> Result<MeshData, MeshError> {
+    let bytes = fs::read(fname)?;
+
+    // ASCII files start with "solid" too, but binary ones have an exact size.
+    let binary_len = bytes
+        .get(80..84)
+        .map(|n| 84 + 50 * u32::from_le_bytes([n[0], n[1], n[2], n[3]]) as usize);
+    let tris = if binary_len == Some(bytes.len()) {
+        read_binary(&bytes)
+    } else {
+        read_ascii(&bytes)?
+    };
+
+    let mut data = MeshData {
+        positions: Vec::new(),
+        normals: None,
+        uvs: None,
+        colors: None,
+        faces: Vec::with_capacity(tris.len()),
+    };
+    let mut welded: HashMap<[u64; 3], usize> = HashMap::new();
+    for tri in tris {
+        let face = tri
+            .iter()
+            .map(|p| {
+                // -0.0 and 0.0 have different bits; adding 0.0 turns the one into the other
+                let key = p.map(|c| (c + 0.0).to_bits()).into();
+                *welded.entry(key).or_insert_with(|| {
+                    data.positions.push(*p);
+                    data.positions.len() - 1
+                })
+            })
+            .collect();
+        data.faces.push(face);
+    }
+    Ok(data)
+}
+
+fn read_binary(bytes: &[u8]) -> Vec<[Vector3<f64>; 3]> {
+    // Each record: normal, three vertices, two bytes of attributes. The stored
+    // normal is often wrong, so it's ignored in favor of the winding.
+    bytes[84..]
+        .chunks_exact(50)
+        .map(|rec| {
+            let f32_at = |at: usize| f32::from_le_bytes(rec[at..at + 4].try_into().unwrap()) as f64;
+            let vec_at = |at: usize| Vector3::new(f32_at(at), f32_at(at + 4), f32_at(at + 8));
+            [vec_at(12), vec_at(24), vec_at(36)]
+        })
+        .collect()
+}
+
+fn read_ascii(bytes: &[u8]) -> Result<Vec<[Vector3<f64>; 3]>, MeshError> {
+    let text = std::str::from_utf8(bytes).map_err(|_| MeshError::Parse("not valid stl".into()))?;
+    let mut words = text.split_whitespace();
+    if words.next() != Some("solid") {
+        return Err(MeshError::Parse("missing solid".into()));
+    }
+
+    let mut verts = Vec::new();
+    while let Some(word) = words.next() {
+        if word != "vertex" {
+            continue;
+        }
+        let mut coord = || -> Result<f64, MeshError> {
+            let w = words
+                .next()
+                .ok_or_else(|| MeshError::Parse("file ends early".into()))?;
+            w.parse()
+                .map_err(|_| MeshError::Parse(format!("{} isn't a number", w)))
+        };
+        verts.push(Vector3::new(coord()?, coord()?, coord()?));
+    }
+
+    if verts.len() % 3 != 0 {
+        return Err(MeshError::Parse("facet without three vertices".into()));
+    }
+    Ok(verts.chunks_exact(3).map(|v| [v[0], v[1], v[2]]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::read;
+    use crate::geom::trimesh::MeshData;
+
+    fn load(name: &str, bytes: &[u8]) -> MeshData {
+        let path =
+            std::env::temp_dir().join(format!("stl_test_{}_{}.stl", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        let loaded = read(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        loaded.unwrap()
+    }
+
+    // Two triangles sharing the edge from (1, 0, 0) to (0, 1, 0)
+    const SQUARE: [[[f32; 3]; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        [[1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+    ];
+
+    fn assert_welded_square(data: &MeshData) {
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.faces, vec![vec![0, 1, 2], vec![1, 3, 2]]);
+        assert_eq!(data.positions[3], Vector3::new(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn ascii_vertices_are_welded() {
+        let mut text = String::from("solid square\n");
+        for tri in SQUARE {
+            text += "facet normal 0 0 1\nouter loop\n";
+            for [x, y, z] in tri {
+                text += &format!("vertex {} {} {}\n", x, y, z);
+            }
+            text += "endloop\nendfacet\n";
+        }
+        text += "endsolid square\n";
+        assert_welded_square(&load("ascii", text.as_bytes()));
+    }
+
+    #[test]
+    fn binary_vertices_are_welded_across_signed_zeros() {
+        // Starts with "solid" like an ASCII file would, which must not fool the reader
+        let mut bytes = b"solid binary".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend(2u32.to_le_bytes());
+        for (t, tri) in SQUARE.iter().enumerate() {
+            bytes.extend([0.0f32, 0.0, 1.0].iter().flat_map(|c| c.to_le_bytes()));
+            for v in tri {
+                // The second triangle has its zeros negative
+                let v = v.map(|c| if t == 1 && c == 0.0 { -0.0 } else { c });
+                bytes.extend(v.iter().flat_map(|c| c.to_le_bytes()));
+            }
+            bytes.extend([0, 0]);
+        }
+        assert_welded_square(&load("binary", &bytes));
+    }
+}
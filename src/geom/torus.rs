@@ -0,0 +1,265 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use nalgebra::{Unit, UnitVector3, Vector2, Vector3};
+
+use crate::{
+    lighting::material::Material,
+    math::{interval::Interval, ray::Ray},
+    util::gamma,
+};
+
+use super::{
+    Geom, Geomable,
+    aabb::AABB,
+    bbox::Bbox,
+    frame::{self, Frame},
+    intersectable::Intersectable,
+    intersection::Intersection,
+};
+
+/// A ring around `axis`: a tube of radius `minor` swept around a circle of
+/// radius `major`. u goes around the axis, v around the tube.
+pub struct Torus {
+    frame: Frame,
+    major: f64,
+    minor: f64,
+    mat: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vector3<f64>,
+        axis: UnitVector3<f64>,
+        major: f64,
+        minor: f64,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        assert!(major > 0.0 && minor > 0.0);
+        Torus {
+            frame: Frame::new(center, axis),
+            major,
+            minor,
+            mat,
+        }
+    }
+}
+
+/// Real roots of the polynomial with coefficients `c` (constant first) in
+/// [lo, hi], in order. Between roots of the derivative the polynomial is
+/// monotonic, so each sign change there is bisected down to one root.
+fn roots_in(c: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    if c.len() == 2 {
+        let t = -c[0] / c[1];
+        return if lo <= t && t <= hi { vec![t] } else { vec![] };
+    }
+    let eval = |t: f64| c.iter().rev().fold(0.0, |acc, &k| acc * t + k);
+    let deriv: Vec<f64> = c
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(k, &ck)| k as f64 * ck)
+        .collect();
+
+    let mut bounds = vec![lo];
+    bounds.extend(roots_in(&deriv, lo, hi));
+    bounds.push(hi);
+
+    let mut roots = Vec::new();
+    for w in bounds.windows(2) {
+        let (mut a, mut b) = (w[0], w[1]);
+        let (mut fa, fb) = (eval(a), eval(b));
+        if fa == 0.0 {
+            roots.push(a);
+            continue;
+        }
+        if fb == 0.0 {
+            roots.push(b);
+            continue;
+        }
+        if (fa < 0.0) == (fb < 0.0) {
+            continue;
+        }
+        for _ in 0..64 {
+            let m = 0.5 * (a + b);
+            if m <= a || m >= b {
+                break;
+            }
+            let fm = eval(m);
+            if (fm < 0.0) == (fa < 0.0) {
+                a = m;
+                fa = fm;
+            } else {
+                b = m;
+            }
+        }
+        roots.push(0.5 * (a + b));
+    }
+    roots.dedup();
+    roots
+}
+
+impl Intersectable for Torus {
+    fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>> {
+        let local = self.frame.ray_to_local(&ray);
+        let (big, small) = (self.major, self.minor);
+
+        // Only look where the ray is inside the bounding sphere, and measure from
+        // where it goes in so the quartic's coefficients stay small. The sphere
+        // touches the outer rim, so it's padded to keep roots off its edge.
+        let f = local.origin().dot(&local.dir());
+        let reach = (big + small) * 1.01;
+        let (enter, exit) =
+            frame::solve_quadratic(1.0, 2.0 * f, local.origin().norm_squared() - reach * reach)?;
+        let (lo, hi) = (enter.max(i.min), exit.min(i.max));
+        if lo > hi {
+            return None;
+        }
+
+        // (|p|² - R² - r²)² = 4R² (r² - z²), along p = o + s d
+        let (o, d) = (local.at(lo), local.dir());
+        let f = o.dot(&d);
+        let e = o.norm_squared() - big * big - small * small;
+        let four_r2 = 4.0 * big * big;
+        let coeffs = [
+            e * e - four_r2 * (small * small - o.z * o.z),
+            4.0 * f * e + 2.0 * four_r2 * o.z * d.z,
+            4.0 * f * f + 2.0 * e + four_r2 * d.z * d.z,
+            4.0 * f,
+            1.0,
+        ];
+        let s = *roots_in(&coeffs, 0.0, hi - lo).first()?;
+        let t = lo + s;
+
+        // Snap onto the surface: out from the tube's center circle by the tube radius
+        let p = local.at(t);
+        let ring = Vector3::new(p.x, p.y, 0.0)
+            .try_normalize(1e-12)
+            .unwrap_or(Vector3::x());
+        let normal = Unit::try_new(p - ring.scale(big), 1e-12).unwrap_or(Unit::new_unchecked(ring));
+        let point = ring.scale(big) + normal.scale(small);
+        let error = (ring.scale(big).abs() + normal.abs().scale(small)).scale(gamma(7));
+
+        let u = frame::turn_fraction(p.x, p.y);
+        let theta = f64::atan2(normal.z, normal.dot(&ring));
+        let v = (if theta < 0.0 { theta + 2.0 * PI } else { theta }) / (2.0 * PI);
+        let dpdv = Vector3::new(-theta.sin() * ring.x, -theta.sin() * ring.y, theta.cos())
+            .scale(2.0 * PI * small);
+
+        let hit = Intersection::new(point, t, normal, self.mat.as_ref(), Vector2::new(u, v))
+            .with_partials(frame::dp_dphi(&point), dpdv)
+            .with_error(error);
+        Some(self.frame.hit_to_world(hit))
+    }
+}
+
+impl Bbox for Torus {
+    fn bbox(&self) -> AABB {
+        let (r, h) = (self.major + self.minor, self.minor);
+        self.frame
+            .bbox(Vector3::new(-r, -r, -h), Vector3::new(r, r, h))
+    }
+}
+
+impl Geomable for Torus {
+    fn into_geoms(self) -> impl Iterator<Item = Geom> {
+        std::iter::once(Geom::Torus(Box::new(self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector2, Vector3};
+
+    use crate::{
+        geom::{bbox::Bbox, intersectable::Intersectable},
+        lighting::{
+            color::Color, lambertian::Lambertian, material::Material,
+            texture::solidcolor::SolidColor,
+        },
+        math::{interval::Interval, ray::Ray},
+    };
+
+    use super::{Torus, roots_in};
+
+    fn mat() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(
+            0.5, 0.5, 0.5,
+        )))))
+    }
+
+    fn near(a: Vector3<f64>, b: Vector3<f64>) -> bool {
+        (a - b).norm() < 1e-3
+    }
+
+    /// Coefficients, constant first, of the polynomial with these roots.
+    fn with_roots(roots: &[f64]) -> Vec<f64> {
+        let mut c = vec![1.0];
+        for r in roots {
+            let mut next = vec![0.0; c.len() + 1];
+            for (k, ck) in c.iter().enumerate() {
+                next[k] -= r * ck;
+                next[k + 1] += ck;
+            }
+            c = next;
+        }
+        c
+    }
+
+    #[test]
+    fn roots_in_finds_close_roots_in_order() {
+        let found = roots_in(&with_roots(&[4.0, 1.0, 3.0, 2.0]), 0.0, 10.0);
+        assert_eq!(found.len(), 4);
+        for (t, r) in found.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!((t - r).abs() < 1e-9, "{:?}", found);
+        }
+        assert_eq!(
+            roots_in(&with_roots(&[1.0, 2.0, 3.0, 4.0]), 2.5, 10.0).len(),
+            2
+        );
+
+        // A nearly grazing ray: two roots either side of the derivative's
+        let found = roots_in(&with_roots(&[1.0 - 1e-4, 1.0 + 1e-4, 3.0, 4.0]), 0.0, 10.0);
+        assert_eq!(found.len(), 4, "{:?}", found);
+        assert!((found[0] - (1.0 - 1e-4)).abs() < 1e-9);
+        assert!((found[1] - (1.0 + 1e-4)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tube_hits_and_the_hole() {
+        let torus = Torus::new(Vector3::zeros(), Vector3::z_axis(), 2.0, 0.5, mat());
+        let shoot = |o: Vector3<f64>, d: Vector3<f64>| {
+            torus
+                .intersect(Ray::new_normalize(o, d), Interval::new(0.0, f64::MAX))
+                .map(|h| (h.dist(), h.normal().into_inner(), h.uv()))
+        };
+
+        let (dist, n, uv) = shoot(Vector3::new(2.0, 0.0, 5.0), -Vector3::z()).unwrap();
+        assert!((dist - 4.5).abs() < 1e-9);
+        assert!(near(n, Vector3::z()));
+        // v goes around the tube from the outside, up over the top
+        assert!((uv - Vector2::new(0.0, 0.25)).norm() < 1e-9);
+        let (dist, n, uv) = shoot(Vector3::new(-5.0, 0.0, 0.0), Vector3::x()).unwrap();
+        assert!((dist - 2.5).abs() < 1e-9);
+        assert!(near(n, -Vector3::x()));
+        assert!((uv - Vector2::new(0.5, 0.0)).norm() < 1e-9);
+
+        // Straight down the hole, and out from the middle of it
+        assert!(shoot(Vector3::new(0.0, 0.0, 5.0), -Vector3::z()).is_none());
+        let (dist, n, uv) = shoot(Vector3::zeros(), Vector3::x()).unwrap();
+        assert!((dist - 1.5).abs() < 1e-9);
+        assert!(near(n, -Vector3::x()));
+        assert!((uv - Vector2::new(0.0, 0.5)).norm() < 1e-9);
+
+        // Just under the top of the tube skims it; just over misses
+        let (dist, n, _) = shoot(Vector3::new(-5.0, 0.0, 0.49), Vector3::x()).unwrap();
+        assert!((dist - (3.0 - f64::sqrt(0.5 * 0.5 - 0.49 * 0.49))).abs() < 1e-6);
+        assert!(n.z > 0.9);
+        assert!(shoot(Vector3::new(-5.0, 0.0, 0.51), Vector3::x()).is_none());
+
+        let bb = torus.bbox();
+        assert!(near(bb.min(), Vector3::new(-2.5, -2.5, -0.5)));
+        assert!(near(bb.max(), Vector3::new(2.5, 2.5, 0.5)));
+    }
+}
@@ -5,12 +5,15 @@ use nalgebra::Vector3;
 use crate::{
     geom::{intersectable::Intersectable, intersection::Intersection},
     math::{interval::Interval, ray::Ray},
+    util::gamma,
 };
 
 use super::{Geomable, aabb::AABB, bbox::Bbox};
 
 pub struct Translation<T> {
     trans: Vector3<f64>,
+    // How far `trans` moves each frame of an animation
+    velocity: Vector3<f64>,
     inner: T,
 }
 
@@ -18,9 +21,34 @@ impl<T> Translation<T> {
     pub fn new(trans: Vector3<f64>, inner: T) -> Self {
         Translation {
             trans: trans,
+            velocity: Vector3::zeros(),
             inner: inner,
         }
     }
+
+    pub fn with_velocity(self, velocity: Vector3<f64>) -> Self {
+        Translation { velocity, ..self }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn velocity(&self) -> Vector3<f64> {
+        self.velocity
+    }
+
+    pub fn trans(&self) -> Vector3<f64> {
+        self.trans
+    }
+
+    pub fn set_trans(&mut self, trans: Vector3<f64>) {
+        self.trans = trans;
+    }
 }
 
 impl<T: Intersectable> Intersectable for Translation<T> {
@@ -28,9 +56,15 @@ impl<T: Intersectable> Intersectable for Translation<T> {
         let new_ray = Ray::new(ray.origin() - self.trans, ray.dir());
         match self.inner.intersect(new_ray, i) {
             None => None,
-            Some(mut inter) => {
-                *inter.point_mut() = inter.point() + self.trans;
-                Some(inter)
+            Some(inter) => {
+                let point = inter.point() + self.trans;
+                let error =
+                    inter.error() + (inter.point().abs() + self.trans.abs()).scale(gamma(1));
+                Some(
+                    inter
+                        .transformed(point, inter.dist(), inter.normal())
+                        .with_error(error),
+                )
             }
         }
     }
@@ -41,6 +75,7 @@ impl<T: Geomable> Geomable for Translation<T> {
         self.inner.into_geoms().map(move |g| {
             super::Geom::Trans(Box::new(Translation {
                 trans: self.trans,
+                velocity: self.velocity,
                 inner: g,
             }))
         })
@@ -1,10 +1,11 @@
 use std::sync::Arc;
 
-use nalgebra::{UnitVector3, Vector2, Vector3};
+use nalgebra::{Unit, UnitVector3, Vector2, Vector3};
 
 use crate::{
-    lighting::material::Material,
+    lighting::{color::Color, material::Material},
     math::{interval::Interval, ray::Ray},
+    util::gamma,
 };
 
 use super::{
@@ -19,6 +20,11 @@ pub struct Triangle {
     c: Vector3<f64>,
     normal: UnitVector3<f64>,
 
+    // Per-vertex shading normals and texture coordinates, if the mesh has them
+    vertex_normals: Option<[UnitVector3<f64>; 3]>,
+    vertex_uvs: Option<[Vector2<f64>; 3]>,
+    vertex_colors: Option<[Color; 3]>,
+
     bbox: AABB,
 
     mat: Arc<dyn Material>,
@@ -40,48 +46,178 @@ impl Triangle {
             b: b,
             c: c,
             normal: n,
+            vertex_normals: None,
+            vertex_uvs: None,
+            vertex_colors: None,
             bbox: bbox,
             mat,
         }
     }
+
+    /// Shade with normals interpolated from these, given in vertex order.
+    pub fn with_vertex_normals(mut self, normals: [UnitVector3<f64>; 3]) -> Self {
+        self.vertex_normals = Some(normals);
+        self
+    }
+
+    /// Texture with uvs interpolated from these, instead of the barycentrics.
+    pub fn with_vertex_uvs(mut self, uvs: [Vector2<f64>; 3]) -> Self {
+        self.vertex_uvs = Some(uvs);
+        self
+    }
+
+    pub fn with_vertex_colors(mut self, colors: [Color; 3]) -> Self {
+        self.vertex_colors = Some(colors);
+        self
+    }
+
+    /// Move the triangle's vertices, e.g. for a deforming mesh. The normal is
+    /// recomputed from the new winding; vertex normals are left for the caller to update.
+    pub fn set_vertices(&mut self, a: Vector3<f64>, b: Vector3<f64>, c: Vector3<f64>) {
+        self.normal = Unit::new_normalize((b - a).cross(&(c - a)));
+        let bb1 = AABB::from_points(a, b);
+        let bb2 = AABB::from_points(a, c);
+        self.bbox = AABB::union(&bb1, &bb2);
+        self.a = a;
+        self.b = b;
+        self.c = c;
+    }
 }
 
 impl Intersectable for Triangle {
     fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>> {
-        //Stolen from the wiki page on Möller–Trumbore.
+        let (t, u, v) = hit_barycentric(&ray, i, &self.a, &self.b, &self.c)?;
+        let attrs = VertexAttrs {
+            normals: self.vertex_normals,
+            uvs: self.vertex_uvs,
+            colors: self.vertex_colors,
+        };
+        Some(surface_hit(
+            t,
+            (u, v),
+            [self.a, self.b, self.c],
+            self.normal,
+            &attrs,
+            self.mat.as_ref(),
+        ))
+    }
+}
 
-        let e1 = self.b - self.a;
-        let e2 = self.c - self.a;
+/// Where `ray` crosses the triangle abc, as (t, u, v) with the hit at a + u (b - a) + v (c - a).
+///
+/// This is the watertight test from Woop, Benthin and Wald (2013): the triangle is
+/// moved into a space where the ray runs along +z from the origin, and the edge
+/// functions are computed there. A shared edge gives exactly opposite values in
+/// the two triangles, so no ray slips between them.
+pub(super) fn hit_barycentric(
+    ray: &Ray,
+    i: Interval,
+    a: &Vector3<f64>,
+    b: &Vector3<f64>,
+    c: &Vector3<f64>,
+) -> Option<(f64, f64, f64)> {
+    let dir = ray.dir();
+
+    // z is the axis the ray moves along the most; x and y keep the winding
+    let kz = dir.iamax();
+    let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    if dir[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
 
-        let ray_cross_e2 = ray.dir().cross(&e2);
-        let det = e1.dot(&ray_cross_e2);
+    // Shear so the ray points along z
+    let sx = dir[kx] / dir[kz];
+    let sy = dir[ky] / dir[kz];
+    let sz = 1.0 / dir[kz];
+    let [a, b, c] = [a, b, c].map(|p| p - ray.origin());
+    let [ax, bx, cx] = [a, b, c].map(|p| p[kx] - sx * p[kz]);
+    let [ay, by, cy] = [a, b, c].map(|p| p[ky] - sy * p[kz]);
+
+    // Scaled barycentrics; they all need the same sign
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
 
-        if det > -f64::EPSILON && det < f64::EPSILON {
-            return None; // This ray is parallel to this triangle.
-        }
+    let det = u + v + w;
+    if det == 0.0 {
+        return None; // The ray is in the plane of the triangle.
+    }
 
-        let inv_det = 1.0 / det;
-        let s = ray.origin() - self.a;
-        let u = inv_det * s.dot(&ray_cross_e2);
-        if u < 0.0 || u > 1.0 {
-            return None;
-        }
+    let t = (u * a[kz] + v * b[kz] + w * c[kz]) * sz / det;
+    if !i.contains(t) {
+        return None;
+    }
+    Some((t, v / det, w / det))
+}
 
-        let s_cross_e1 = s.cross(&e1);
-        let v = inv_det * ray.dir().dot(&s_cross_e1);
-        if v < 0.0 || u + v > 1.0 {
-            return None;
-        }
-        let t = inv_det * e2.dot(&s_cross_e1);
+/// Per-vertex values to interpolate across a triangle, in vertex order.
+pub(super) struct VertexAttrs {
+    pub normals: Option<[UnitVector3<f64>; 3]>,
+    pub uvs: Option<[Vector2<f64>; 3]>,
+    pub colors: Option<[Color; 3]>,
+}
 
-        if !i.contains(t) {
-            return None;
+/// The intersection at barycentrics (u, v) of the triangle `verts`, with the
+/// vertex attributes interpolated.
+pub(super) fn surface_hit<'r>(
+    t: f64,
+    (u, v): (f64, f64),
+    verts: [Vector3<f64>; 3],
+    flat_normal: UnitVector3<f64>,
+    attrs: &VertexAttrs,
+    mat: &'r dyn Material,
+) -> Intersection<'r> {
+    let w = 1.0 - u - v;
+    // From the barycentrics rather than ray.at(t), so the error stays proportional to the vertices
+    let [a, b, c] = verts;
+    let pt = a.scale(w) + b.scale(u) + c.scale(v);
+    let error = (a.scale(w).abs() + b.scale(u).abs() + c.scale(v).abs()).scale(gamma(7));
+    let normal = match attrs.normals {
+        Some([na, nb, nc]) => {
+            Unit::try_new(na.scale(w) + nb.scale(u) + nc.scale(v), 1e-12).unwrap_or(flat_normal)
         }
+        None => flat_normal,
+    };
+    let uv = match attrs.uvs {
+        Some([ta, tb, tc]) => ta.scale(w) + tb.scale(u) + tc.scale(v),
+        None => Vector2::new(u, v),
+    };
+    let e1 = verts[1] - verts[0];
+    let e2 = verts[2] - verts[0];
+    let (dpdu, dpdv) = uv_partials(&attrs.uvs, &e1, &e2);
+    let mut inter = Intersection::new(pt, t, normal, mat, uv)
+        .with_partials(dpdu, dpdv)
+        .with_geo_normal(flat_normal)
+        .with_error(error);
+    if let Some([ca, cb, cc]) = attrs.colors {
+        let c = ca.inner_vec().scale(w) + cb.inner_vec().scale(u) + cc.inner_vec().scale(v);
+        inter = inter.with_vertex_color(Color::from_vec(c));
+    }
+    inter
+}
 
-        let pt = ray.at(t);
-        let uv = Vector2::new(u, v);
-        Some(Intersection::new(pt, t, self.normal, self.mat.as_ref(), uv))
+/// Surface partials with respect to the vertex uvs, or the barycentrics if there are none.
+fn uv_partials(
+    uvs: &Option<[Vector2<f64>; 3]>,
+    e1: &Vector3<f64>,
+    e2: &Vector3<f64>,
+) -> (Vector3<f64>, Vector3<f64>) {
+    let Some([ta, tb, tc]) = uvs else {
+        return (*e1, *e2);
+    };
+    let (d1, d2) = (tb - ta, tc - ta);
+    let det = d1.x * d2.y - d1.y * d2.x;
+    if det.abs() < 1e-12 {
+        // Degenerate uv mapping, the footprint can't be worked out
+        return (Vector3::zeros(), Vector3::zeros());
     }
+    let inv = 1.0 / det;
+    let dpdu = (e1.scale(d2.y) - e2.scale(d1.y)).scale(inv);
+    let dpdv = (e2.scale(d1.x) - e1.scale(d2.x)).scale(inv);
+    (dpdu, dpdv)
 }
 
 impl Geomable for Triangle {
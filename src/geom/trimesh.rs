@@ -1,56 +1,491 @@
-use std::sync::Arc;
+use std::{collections::HashMap, fmt, io, path::Path, sync::Arc};
 
-use nalgebra::{Unit, Vector3};
-use obj::{IndexTuple, SimplePolygon};
+use nalgebra::{Unit, UnitVector3, Vector2, Vector3};
+use obj::{IndexTuple, MtlLibsLoadError, ObjError, ObjMaterial, SimplePolygon};
 
-use crate::{
-    geom::aabb::AABB,
-    lighting::material::Material,
-    math::{interval::Interval, ray::Ray},
+use crate::lighting::{
+    color::{Color, ColorSpace},
+    diffuselight::DiffuseLight,
+    lambertian::Lambertian,
+    material::Material,
+    metal::Metal,
+    texture::{Texture, image::Image, solidcolor::SolidColor},
 };
 
 use super::{
-    Geomable, bvh::BVH, intersectable::Intersectable, intersection::Intersection,
-    triangle::Triangle,
+    Geomable,
+    mesh::{Mesh, MeshTri, Positions},
+    ply, stl,
 };
 
+/// Faces meeting at more than this are kept sharp when generating normals.
+pub const DEFAULT_CREASE_ANGLE: f64 = 60.0 * std::f64::consts::PI / 180.0;
+
+#[derive(Debug)]
+pub enum MeshError {
+    Io(io::Error),
+    /// The file isn't in the format its extension says.
+    Parse(String),
+    UnknownFormat(String),
+    Obj(ObjError),
+    Mtl(MtlLibsLoadError),
+    /// A face refers to a position, normal or uv that isn't in the file.
+    BadIndex {
+        face: usize,
+    },
+    MissingTexture(String),
+    Image(image::ImageError),
+}
+
+impl From<io::Error> for MeshError {
+    fn from(e: io::Error) -> Self {
+        MeshError::Io(e)
+    }
+}
+
+impl From<ObjError> for MeshError {
+    fn from(e: ObjError) -> Self {
+        MeshError::Obj(e)
+    }
+}
+
+impl From<image::ImageError> for MeshError {
+    fn from(e: image::ImageError) -> Self {
+        MeshError::Image(e)
+    }
+}
+
+impl From<MtlLibsLoadError> for MeshError {
+    fn from(e: MtlLibsLoadError) -> Self {
+        MeshError::Mtl(e)
+    }
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::Io(e) => write!(f, "{}", e),
+            MeshError::Parse(e) => write!(f, "couldn't parse mesh: {}", e),
+            MeshError::UnknownFormat(ext) => write!(f, "don't know how to load .{} meshes", ext),
+            MeshError::Obj(e) => write!(f, "couldn't read obj: {}", e),
+            MeshError::Mtl(e) => write!(f, "couldn't read mtl: {}", e),
+            MeshError::BadIndex { face } => write!(f, "face {} has an out of range index", face),
+            MeshError::MissingTexture(t) => write!(f, "texture {} doesn't exist", t),
+            MeshError::Image(e) => write!(f, "couldn't read texture: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+/// An indexed mesh as read from a file, with optional per-vertex attributes.
+pub struct MeshData {
+    pub positions: Vec<Vector3<f64>>,
+    pub normals: Option<Vec<UnitVector3<f64>>>,
+    pub uvs: Option<Vec<Vector2<f64>>>,
+    pub colors: Option<Vec<Color>>,
+    /// Polygons, as indices into the vertex lists.
+    pub faces: Vec<Vec<usize>>,
+}
+
+// One corner of a face, with its attributes looked up
+struct Vertex {
+    pos: Vector3<f64>,
+    /// `None` if the normal has to be generated.
+    normal: Option<Vector3<f32>>,
+    uv: Option<Vector2<f32>>,
+    color: Option<Vector3<f32>>,
+}
+
+/// A loaded mesh, split into one shared-vertex `Mesh` per material.
 pub struct TriMesh {
-    faces: Vec<Triangle>,
+    meshes: Vec<Mesh>,
 }
 
 impl TriMesh {
-    pub fn from_fname(fname: &str, mat: Arc<dyn Material>) -> Self {
-        let obj = obj::Obj::load(fname).unwrap();
+    pub fn from_fname(fname: &str, mat: Arc<dyn Material>) -> Result<Self, MeshError> {
+        Self::load(fname, mat, DEFAULT_CREASE_ANGLE)
+    }
+
+    /// Load an OBJ, PLY or STL file, going by the extension.
+    pub fn from_path(
+        fname: &str,
+        mat: Arc<dyn Material>,
+        crease_angle: f64,
+    ) -> Result<Self, MeshError> {
+        let ext = Path::new(fname)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match ext.as_str() {
+            "obj" => Self::load(fname, mat, crease_angle),
+            "ply" => Self::from_data(ply::read(fname)?, mat, crease_angle),
+            "stl" => Self::from_data(stl::read(fname)?, mat, crease_angle),
+            _ => Err(MeshError::UnknownFormat(ext)),
+        }
+    }
+
+    /// Build a mesh from already loaded data, all with one material. Missing
+    /// normals are generated as in `load`.
+    pub fn from_data(
+        data: MeshData,
+        mat: Arc<dyn Material>,
+        crease_angle: f64,
+    ) -> Result<Self, MeshError> {
+        let n_verts = data.positions.len();
+        let mut builder = MeshBuilder::new(mat);
 
-        let verts = obj.data.position;
-        let faces = &obj.data.objects[0].groups[0].polys;
+        for (face, poly) in data.faces.iter().enumerate() {
+            if poly.iter().any(|&v| v >= n_verts) {
+                return Err(MeshError::BadIndex { face });
+            }
+            for i in 1..poly.len().saturating_sub(1) {
+                let tri = [poly[0], poly[i], poly[i + 1]];
+                let mut indices = [0; 3];
+                for (index, v) in indices.iter_mut().zip(tri) {
+                    // Every attribute goes with the position, so it alone tells corners apart
+                    *index = builder.vertex((v, None, None), || {
+                        Ok(Vertex {
+                            pos: data.positions[v],
+                            normal: data.normals.as_ref().map(|n| n[v].into_inner().cast()),
+                            uv: data.uvs.as_ref().map(|t| t[v].cast()),
+                            color: data.colors.as_ref().map(|c| c[v].inner_vec().cast()),
+                        })
+                    })?;
+                }
+                builder.indices.push(indices);
+            }
+        }
+
+        Ok(finish(vec![builder], n_verts, crease_angle))
+    }
 
-        let mut tris: Vec<Triangle> = Vec::with_capacity(faces.len());
-        for SimplePolygon(face) in faces {
-            let IndexTuple(vidx0, _, _) = face[0];
-            let IndexTuple(vidx1, _, _) = face[1];
-            let IndexTuple(vidx2, _, _) = face[2];
-            let a: Vector3<f64> = Vector3::from_column_slice(verts.get(vidx0).unwrap()).cast();
-            let b: Vector3<f64> = Vector3::from_column_slice(verts.get(vidx1).unwrap()).cast();
-            let c: Vector3<f64> = Vector3::from_column_slice(verts.get(vidx2).unwrap()).cast();
+    /// Load every object and group in an OBJ file, smooth shaded. Polygons are fan
+    /// triangulated, so they should be convex. Groups with a `usemtl` get a material
+    /// built from the MTL file; the rest get `mat`.
+    ///
+    /// Faces without normals in the file get generated ones, smoothed across
+    /// edges sharper than `crease_angle` (radians).
+    pub fn load(fname: &str, mat: Arc<dyn Material>, crease_angle: f64) -> Result<Self, MeshError> {
+        let mut obj = obj::Obj::load(fname)?;
+        obj.load_mtls()?;
+        let data = &obj.data;
 
-            let edge1 = b - a;
-            let edge2 = c - a;
+        let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+        let mut builders: Vec<MeshBuilder> = Vec::new();
+        let mut n_polys = 0;
 
-            let normal = Unit::new_normalize(edge1.cross(&edge2));
+        for group in data.objects.iter().flat_map(|o| o.groups.iter()) {
+            let group_mat = match &group.material {
+                Some(ObjMaterial::Mtl(m)) => match materials.get(&m.name) {
+                    Some(mat) => mat.clone(),
+                    None => {
+                        let built = from_mtl(m, &obj.path)?;
+                        materials.insert(m.name.clone(), built.clone());
+                        built
+                    }
+                },
+                // usemtl naming something no mtllib defined
+                Some(ObjMaterial::Ref(_)) | None => mat.clone(),
+            };
 
-            tris.push(Triangle::new(a, b, c, normal, mat.clone()));
+            let mut slot = None;
+            for SimplePolygon(poly) in &group.polys {
+                let face = n_polys;
+                n_polys += 1;
+                for i in 1..poly.len().saturating_sub(1) {
+                    let slot = *slot.get_or_insert_with(|| builder_for(&mut builders, &group_mat));
+                    let tri = [&poly[0], &poly[i], &poly[i + 1]];
+                    let indices = corners(&mut builders[slot], data, tri, face)?;
+                    builders[slot].indices.push(indices);
+                }
+            }
         }
 
-        TriMesh { faces: tris }
+        Ok(finish(builders, data.position.len(), crease_angle))
+    }
+
+    /// Store positions as f32, for scenes too big to fit otherwise.
+    pub fn into_f32(self) -> Self {
+        TriMesh {
+            meshes: self.meshes.into_iter().map(Mesh::into_f32).collect(),
+        }
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.meshes.iter().map(Mesh::face_count).sum()
+    }
+
+    /// The mesh's materials, in the order they first show up in the file.
+    pub fn materials(&self) -> impl Iterator<Item = &Arc<dyn Material>> {
+        self.meshes.iter().map(Mesh::mat)
+    }
+}
+
+// The file's indices for a corner's position, uv and normal
+type CornerKey = (usize, Option<usize>, Option<usize>);
+
+/// Collects the vertices and faces of one material, sharing vertices between
+/// corners that use the same position, uv and normal from the file.
+struct MeshBuilder {
+    mat: Arc<dyn Material>,
+    positions: Vec<Vector3<f64>>,
+    normals: Vec<Vector3<f32>>,
+    uvs: Vec<Vector2<f32>>,
+    colors: Vec<Vector3<f32>>,
+    has_uvs: bool,
+    has_colors: bool,
+    indices: Vec<[u32; 3]>,
+    // For each vertex, the position it came from in the file, and whether its
+    // normal still has to be generated
+    source: Vec<usize>,
+    generate: Vec<bool>,
+    seen: HashMap<CornerKey, u32>,
+}
+
+impl MeshBuilder {
+    fn new(mat: Arc<dyn Material>) -> Self {
+        MeshBuilder {
+            mat,
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            colors: Vec::new(),
+            has_uvs: false,
+            has_colors: false,
+            indices: Vec::new(),
+            source: Vec::new(),
+            generate: Vec::new(),
+            seen: HashMap::new(),
+        }
+    }
+
+    /// The index of the vertex for `key`, adding it with `make` the first time.
+    fn vertex(
+        &mut self,
+        key: CornerKey,
+        make: impl FnOnce() -> Result<Vertex, MeshError>,
+    ) -> Result<u32, MeshError> {
+        if let Some(&i) = self.seen.get(&key) {
+            return Ok(i);
+        }
+        let v = make()?;
+        self.has_uvs |= v.uv.is_some();
+        self.has_colors |= v.color.is_some();
+        self.positions.push(v.pos);
+        self.normals.push(v.normal.unwrap_or_default());
+        self.uvs.push(v.uv.unwrap_or_default());
+        self.colors.push(v.color.unwrap_or_default());
+        self.source.push(key.0);
+        self.generate.push(v.normal.is_none());
+        let i = (self.positions.len() - 1) as u32;
+        self.seen.insert(key, i);
+        Ok(i)
+    }
+
+    // A copy of vertex `i` with a different normal
+    fn split(&mut self, i: u32, normal: Vector3<f32>) -> u32 {
+        let i = i as usize;
+        self.positions.push(self.positions[i]);
+        self.normals.push(normal);
+        self.uvs.push(self.uvs[i]);
+        self.colors.push(self.colors[i]);
+        self.source.push(self.source[i]);
+        self.generate.push(false);
+        (self.positions.len() - 1) as u32
+    }
+
+    fn finish(self) -> Mesh {
+        Mesh::new(
+            Positions::F64(self.positions),
+            Some(self.normals),
+            self.has_uvs.then_some(self.uvs),
+            self.has_colors.then_some(self.colors),
+            self.indices,
+            self.mat,
+        )
+    }
+}
+
+// Materials are told apart by identity, in the order they first show up
+fn builder_for(builders: &mut Vec<MeshBuilder>, mat: &Arc<dyn Material>) -> usize {
+    match builders.iter().position(|b| Arc::ptr_eq(&b.mat, mat)) {
+        Some(slot) => slot,
+        None => {
+            builders.push(MeshBuilder::new(mat.clone()));
+            builders.len() - 1
+        }
+    }
+}
+
+fn finish(mut builders: Vec<MeshBuilder>, n_verts: usize, crease_angle: f64) -> TriMesh {
+    if builders.iter().any(|b| b.generate.contains(&true)) {
+        smooth_normals(&mut builders, n_verts, crease_angle);
+    }
+    TriMesh {
+        meshes: builders.into_iter().map(MeshBuilder::finish).collect(),
+    }
+}
+
+/// Add the corners of one OBJ triangle to `builder`.
+fn corners(
+    builder: &mut MeshBuilder,
+    data: &obj::ObjData,
+    tri: [&IndexTuple; 3],
+    face: usize,
+) -> Result<[u32; 3], MeshError> {
+    let bad = || MeshError::BadIndex { face };
+
+    // Only use normals and uvs if the whole triangle has them
+    let has_normals = tri.iter().all(|IndexTuple(_, _, n)| n.is_some());
+    let has_uvs = tri.iter().all(|IndexTuple(_, t, _)| t.is_some());
+
+    let mut indices = [0; 3];
+    for (index, &&IndexTuple(v, t, n)) in indices.iter_mut().zip(&tri) {
+        let t = t.filter(|_| has_uvs);
+        let n = n.filter(|_| has_normals);
+        *index = builder.vertex((v, t, n), || {
+            let pos = Vector3::from_column_slice(data.position.get(v).ok_or_else(bad)?).cast();
+            let normal = match n {
+                Some(n) => {
+                    let raw = Vector3::from_column_slice(data.normal.get(n).ok_or_else(bad)?);
+                    let unit: UnitVector3<f64> =
+                        Unit::try_new(raw.cast(), 1e-20).ok_or_else(bad)?;
+                    Some(unit.into_inner().cast())
+                }
+                None => None,
+            };
+            let uv = match t {
+                // OBJ puts v = 0 at the bottom of the image, we put it at the top.
+                Some(t) => data
+                    .texture
+                    .get(t)
+                    .map(|raw| Vector2::new(raw[0] as f64, 1.0 - raw[1] as f64).cast())
+                    .ok_or_else(bad)
+                    .map(Some)?,
+                None => None,
+            };
+            Ok(Vertex {
+                pos,
+                normal,
+                uv,
+                color: None,
+            })
+        })?;
+    }
+    Ok(indices)
+}
+
+fn mtl_color(c: Option<[f32; 3]>) -> Option<Color> {
+    c.map(|[r, g, b]| {
+        let mut c = Color::from_vec(Vector3::new(r, g, b).cast());
+        c.clamp();
+        c
+    })
+}
+
+/// Pick the closest of our materials for an MTL entry: emissive surfaces become
+/// lights, mirror-like illumination models become metal, everything else is diffuse.
+fn from_mtl(m: &obj::Material, dir: &Path) -> Result<Arc<dyn Material>, MeshError> {
+    if let Some(ke) = mtl_color(m.ke).filter(|c| c.luminance() > 0.0) {
+        return Ok(Arc::new(DiffuseLight::solid(ke)));
+    }
+
+    // illum 3 and up are the reflective models
+    if m.illum.is_some_and(|i| i >= 3) {
+        let albedo = mtl_color(m.ks).unwrap_or(Color::white());
+        // Shininess runs 0..1000, sharper reflections the higher it goes
+        let fuzz = 1.0 - (m.ns.unwrap_or(0.0) as f64 / 1000.0).clamp(0.0, 1.0);
+        return Ok(Arc::new(Metal::new(albedo, fuzz)));
+    }
+
+    let tex: Arc<dyn Texture> = match &m.map_kd {
+        Some(map) => {
+            let path = dir.join(map);
+            if !path.exists() {
+                return Err(MeshError::MissingTexture(path.display().to_string()));
+            }
+            Arc::new(Image::open(&path, ColorSpace::from_path(&path))?)
+        }
+        None => Arc::new(SolidColor::new(
+            mtl_color(m.kd).unwrap_or(Color::new(0.8, 0.8, 0.8)),
+        )),
+    };
+    Ok(Arc::new(Lambertian::new(tex)))
+}
+
+/// Fill in the normals the file didn't give: the area-weighted average of the
+/// face normals around that position, leaving out faces bent away by more than
+/// `crease_angle`. Vertices that end up with different normals on different
+/// faces are split.
+fn smooth_normals(builders: &mut [MeshBuilder], n_verts: usize, crease_angle: f64) {
+    // Unnormalized, so bigger faces count for more
+    let face_normals: Vec<Vec<Vector3<f64>>> = builders
+        .iter()
+        .map(|b| {
+            b.indices
+                .iter()
+                .map(|face| {
+                    let [p0, p1, p2] = face.map(|i| b.positions[i as usize]);
+                    (p1 - p0).cross(&(p2 - p0))
+                })
+                .collect()
+        })
+        .collect();
+
+    // Faces around each position in the file, across materials
+    let mut adjacent: Vec<Vec<(usize, usize)>> = vec![Vec::new(); n_verts];
+    for (m, b) in builders.iter().enumerate() {
+        for (f, face) in b.indices.iter().enumerate() {
+            for &i in face {
+                adjacent[b.source[i as usize]].push((m, f));
+            }
+        }
+    }
+
+    let cos_crease = crease_angle.cos();
+    for (m, b) in builders.iter_mut().enumerate() {
+        let mut done = vec![false; b.positions.len()];
+        let mut splits: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+        for f in 0..b.indices.len() {
+            let flat = Unit::try_new(face_normals[m][f], 1e-20)
+                .unwrap_or(Unit::new_unchecked(Vector3::y()));
+            for k in 0..3 {
+                let i = b.indices[f][k];
+                if !b.generate[i as usize] {
+                    continue;
+                }
+                let sum: Vector3<f64> = adjacent[b.source[i as usize]]
+                    .iter()
+                    .map(|&(g_m, g)| face_normals[g_m][g])
+                    .filter(|n| {
+                        Unit::try_new(*n, 1e-20).is_some_and(|n| n.dot(&flat) >= cos_crease)
+                    })
+                    .sum();
+                let n: Vector3<f32> = Unit::try_new(sum, 1e-20)
+                    .unwrap_or(flat)
+                    .into_inner()
+                    .cast();
+
+                if !done[i as usize] {
+                    done[i as usize] = true;
+                    b.normals[i as usize] = n;
+                } else if b.normals[i as usize] != n {
+                    let bits = n.map(f32::to_bits).into();
+                    b.indices[f][k] = *splits.entry((i, bits)).or_insert_with(|| b.split(i, n));
+                }
+            }
+        }
     }
 }
 
 impl Geomable for TriMesh {
     fn into_geoms(self) -> impl Iterator<Item = super::Geom> {
-        self.faces
+        self.meshes
             .into_iter()
-            .map(|t| super::Geom::Tri(Box::new(t)))
+            .flat_map(|m| MeshTri::all(Arc::new(m)))
+            .map(super::Geom::MeshTri)
     }
 }
 // impl Intersectable for TriMesh {
@@ -62,3 +497,30 @@ impl Geomable for TriMesh {
 //         self.faces.bbox()
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::lighting::{color::Color, lambertian::Lambertian, texture::solidcolor::SolidColor};
+
+    use super::{MeshError, TriMesh};
+
+    #[test]
+    fn unreadable_texture_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("bad_map_kd_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tri.mtl"), "newmtl skin\nmap_Kd skin.png\n").unwrap();
+        std::fs::write(dir.join("skin.png"), "not a png").unwrap();
+        std::fs::write(
+            dir.join("tri.obj"),
+            "mtllib tri.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl skin\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let mat = Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::white()))));
+        let loaded = TriMesh::from_fname(dir.join("tri.obj").to_str().unwrap(), mat);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(loaded, Err(MeshError::Image(_))));
+    }
+}
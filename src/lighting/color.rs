@@ -1,4 +1,7 @@
-use std::ops::{Add, AddAssign, Mul};
+use std::{
+    ops::{Add, AddAssign, Mul},
+    path::Path,
+};
 
 use image::Rgb;
 use nalgebra::{UnitVector3, Vector3};
@@ -8,19 +11,64 @@ pub struct Color {
     v: Vector3<f64>,
 }
 
+/// A color encoded for display with the sRGB transfer curve. Rendering happens
+/// on linear `Color`s; this is only for writing 8/16-bit images and the window.
 pub struct GammaColor {
     v: Vector3<f64>,
 }
 
 impl From<Color> for GammaColor {
     fn from(c: Color) -> Self {
-        let v = c.v;
-        GammaColor {
-            v: Vector3::new(v.x.sqrt(), v.y.sqrt(), v.z.sqrt()),
+        let v = c.v.map(|x| linear_to_srgb(x.clamp(0.0, 1.0)));
+        GammaColor { v }
+    }
+}
+
+/// How the values stored in an image file relate to light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+impl ColorSpace {
+    /// Float formats hold linear values; everything else is assumed to be sRGB encoded.
+    pub fn from_path(path: &Path) -> Self {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("exr" | "hdr" | "pfm") => ColorSpace::Linear,
+            _ => ColorSpace::Srgb,
+        }
+    }
+
+    /// Convert a value stored in this space to linear.
+    pub fn to_linear(self, x: f64) -> f64 {
+        match self {
+            ColorSpace::Srgb => srgb_to_linear(x),
+            ColorSpace::Linear => x,
         }
     }
 }
 
+pub fn srgb_to_linear(x: f64) -> f64 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(x: f64) -> f64 {
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
 impl Default for Color {
     fn default() -> Self {
         Self::black()
@@ -28,10 +76,10 @@ impl Default for Color {
 }
 
 impl Color {
+    /// Linear RGB. Anything above 1 is fine, e.g. for bright lights; it's only
+    /// clamped when written out as an 8/16-bit image.
     pub fn new(r: f64, g: f64, b: f64) -> Self {
-        assert!(0.0 <= r && r <= 1.0);
-        assert!(0.0 <= g && g <= 1.0);
-        assert!(0.0 <= b && b <= 1.0);
+        assert!(0.0 <= r && 0.0 <= g && 0.0 <= b);
 
         Color {
             v: Vector3::new(r, g, b),
@@ -63,6 +111,18 @@ impl Color {
         Color { v: self.v.scale(f) }
     }
 
+    /// Relative luminance (Rec. 709 weights, on linear values).
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.v.x + 0.7152 * self.v.y + 0.0722 * self.v.z
+    }
+
+    /// Linear blend, `a` at t = 0 and `b` at t = 1.
+    pub fn lerp(a: Color, b: Color, t: f64) -> Self {
+        Color {
+            v: a.v.lerp(&b.v, t),
+        }
+    }
+
     pub(crate) fn white() -> Self {
         Color::new(1.0, 1.0, 1.0)
     }
@@ -75,11 +135,9 @@ impl Color {
 impl Add for Color {
     type Output = Color;
     fn add(self, rhs: Self) -> Self::Output {
-        let mut c = Color {
+        Color {
             v: self.v.add(rhs.v),
-        };
-        c.clamp();
-        c
+        }
     }
 }
 
@@ -93,30 +151,34 @@ impl Mul for Color {
     type Output = Color;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        let mut c = Color {
-            v: Vector3::new(self.v.x * rhs.v.x, self.v.y * rhs.v.y, self.v.z * rhs.v.z),
-        };
-        c.clamp();
-        c
+        Color {
+            v: self.v.component_mul(&rhs.v),
+        }
     }
 }
 
 impl Into<sdl2::pixels::Color> for GammaColor {
     fn into(self) -> sdl2::pixels::Color {
-        // Apply gamma correction (as floats) at this point, before mapping into u8 space.
-        sdl2::pixels::Color::RGB(
-            (self.v.x * 256.0).floor() as u8,
-            (self.v.y * 256.0).floor() as u8,
-            (self.v.z * 256.0).floor() as u8,
-        )
+        let px: Rgb<u8> = self.into();
+        sdl2::pixels::Color::RGB(px[0], px[1], px[2])
     }
 }
 
 impl Into<Rgb<u8>> for GammaColor {
     fn into(self) -> Rgb<u8> {
-        let r = (self.v.x * 256.0).floor() as u8;
-        let g = (self.v.y * 256.0).floor() as u8;
-        let b = (self.v.z * 256.0).floor() as u8;
+        // Round rather than floor, so 8-bit sRGB values survive a trip through linear.
+        let r = (self.v.x * 255.0).round() as u8;
+        let g = (self.v.y * 255.0).round() as u8;
+        let b = (self.v.z * 255.0).round() as u8;
+        image::Rgb([r, g, b])
+    }
+}
+
+impl From<GammaColor> for Rgb<u16> {
+    fn from(c: GammaColor) -> Self {
+        let r = (c.v.x * 65535.0).round() as u16;
+        let g = (c.v.y * 65535.0).round() as u16;
+        let b = (c.v.z * 65535.0).round() as u16;
         image::Rgb([r, g, b])
     }
 }
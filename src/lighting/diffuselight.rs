@@ -5,7 +5,7 @@ use crate::math::ray::Ray;
 use super::{
     color::Color,
     material::Material,
-    texture::{Texture, solidcolor::SolidColor},
+    texture::{Texture, TextureContext, solidcolor::SolidColor},
 };
 
 pub struct DiffuseLight {
@@ -23,6 +23,10 @@ impl DiffuseLight {
 }
 
 impl Material for DiffuseLight {
+    fn name(&self) -> &'static str {
+        "diffuselight"
+    }
+
     fn scatter(
         &self,
         _: &Ray,
@@ -31,7 +35,7 @@ impl Material for DiffuseLight {
         None
     }
 
-    fn emit(&self, uv: &nalgebra::Vector2<f64>) -> Color {
-        self.tex.color_at(&uv)
+    fn emit(&self, ctx: &TextureContext) -> Color {
+        self.tex.color_at(ctx)
     }
 }
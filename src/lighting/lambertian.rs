@@ -24,6 +24,10 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
+    fn name(&self) -> &'static str {
+        "lambertian"
+    }
+
     fn scatter(
         &self,
         ray_in: &Ray,
@@ -34,8 +38,8 @@ impl Material for Lambertian {
         if is_small(bounce_dir) {
             bounce_dir = *normal;
         }
-        let bounce_ray = Ray::new(inter.point(), Unit::new_normalize(bounce_dir));
-        let albedo = self.tex.color_at(&inter.uv());
+        let bounce_ray = inter.spawn_ray(Unit::new_normalize(bounce_dir));
+        let albedo = self.tex.color_at(&inter.tex_context(ray_in));
         Some(Scatter::new(albedo, bounce_ray))
     }
 }
@@ -1,8 +1,6 @@
-use nalgebra::Vector2;
-
 use crate::{geom::intersection::Intersection, math::ray::Ray};
 
-use super::color::Color;
+use super::{color::Color, texture::TextureContext};
 
 pub struct Scatter {
     color: Color,
@@ -24,9 +22,12 @@ impl Scatter {
 }
 
 pub trait Material: Sync + Send {
+    /// Short name for the kind of material, for inspecting scenes.
+    fn name(&self) -> &'static str;
+
     fn scatter(&self, ray_in: &Ray, inter: &Intersection) -> Option<Scatter>;
 
-    fn emit(&self, _: &Vector2<f64>) -> Color {
+    fn emit(&self, _: &TextureContext) -> Color {
         Color::black()
     }
 }
@@ -1,3 +1,5 @@
+use nalgebra::Unit;
+
 use crate::{
     geom::intersection::Intersection,
     math::ray::Ray,
@@ -22,13 +24,20 @@ impl Metal {
 }
 
 impl Material for Metal {
+    fn name(&self) -> &'static str {
+        "metal"
+    }
+
     fn scatter(&self, ray_in: &Ray, inter: &Intersection) -> Option<Scatter> {
         let refl = reflect(&ray_in.dir(), &inter.normal());
         let scattered = refl + random_unit_vec3().scale(self.fuzz);
         if scattered.dot(&inter.normal()) > 0.0 {
             Some(Scatter::new(
                 self.albedo,
-                Ray::new_normalize(inter.point(), scattered),
+                // A mirror keeps the incoming cone spreading as it was
+                inter
+                    .spawn_ray(Unit::new_normalize(scattered))
+                    .with_cone(ray_in.width_at(inter.dist()), ray_in.spread()),
             ))
         } else {
             None
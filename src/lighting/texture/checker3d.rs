@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use crate::lighting::color::Color;
+
+use super::{Texture, TextureContext};
+
+/// Checkerboard of cubes in object space, so it wraps spheres without seams.
+pub struct Checker3d {
+    tex1: Arc<dyn Texture>,
+    tex2: Arc<dyn Texture>,
+    checker_size: f64,
+}
+
+impl Checker3d {
+    pub fn new(checker_size: f64, tex1: Arc<dyn Texture>, tex2: Arc<dyn Texture>) -> Self {
+        Checker3d {
+            tex1,
+            tex2,
+            checker_size,
+        }
+    }
+}
+
+impl Texture for Checker3d {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let p = ctx.local_point / self.checker_size;
+        let parity = p.x.floor() as i64 + p.y.floor() as i64 + p.z.floor() as i64;
+
+        if parity.rem_euclid(2) == 0 {
+            self.tex1.color_at(ctx)
+        } else {
+            self.tex2.color_at(ctx)
+        }
+    }
+}
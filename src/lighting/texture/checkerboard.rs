@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::lighting::color::Color;
 
-use super::Texture;
+use super::{Texture, TextureContext};
 
 pub struct Checkerboard {
     tex1: Arc<dyn Texture>,
@@ -21,14 +21,14 @@ impl Checkerboard {
 }
 
 impl Texture for Checkerboard {
-    fn color_at(&self, uv: &nalgebra::Vector2<f64>) -> Color {
-        let x = (uv.x / self.checker_size).floor() as i64;
-        let y = (uv.y / self.checker_size).floor() as i64;
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let x = (ctx.uv.x / self.checker_size).floor() as i64;
+        let y = (ctx.uv.y / self.checker_size).floor() as i64;
 
         if (x + y) % 2 == 0 {
-            self.tex1.color_at(uv)
+            self.tex1.color_at(ctx)
         } else {
-            self.tex2.color_at(uv)
+            self.tex2.color_at(ctx)
         }
     }
 }
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use crate::lighting::color::Color;
+
+use super::{Texture, TextureContext};
+
+#[derive(Debug, Clone, Copy)]
+pub enum CombineOp {
+    Add,
+    Multiply,
+}
+
+/// Two textures added or multiplied channel by channel, clamped to [0, 1] so the
+/// result is still a valid albedo.
+pub struct Combine {
+    op: CombineOp,
+    tex1: Arc<dyn Texture>,
+    tex2: Arc<dyn Texture>,
+}
+
+impl Combine {
+    pub fn new(op: CombineOp, tex1: Arc<dyn Texture>, tex2: Arc<dyn Texture>) -> Self {
+        Combine { op, tex1, tex2 }
+    }
+}
+
+impl Texture for Combine {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let a = self.tex1.color_at(ctx);
+        let b = self.tex2.color_at(ctx);
+        let mut c = match self.op {
+            CombineOp::Add => a + b,
+            CombineOp::Multiply => a * b,
+        };
+        c.clamp();
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector2, Vector3};
+
+    use crate::lighting::{
+        color::Color,
+        texture::{Texture, TextureContext, solidcolor::SolidColor},
+    };
+
+    use super::{Combine, CombineOp};
+
+    #[test]
+    fn results_stay_in_unit_range() {
+        let solid = |r, g, b| Arc::new(SolidColor::new(Color::new(r, g, b)));
+        let ctx = TextureContext::from_uv(Vector2::zeros());
+
+        let add = Combine::new(CombineOp::Add, solid(0.7, 0.2, 0.0), solid(0.6, 0.3, 0.0));
+        assert_eq!(add.color_at(&ctx).inner_vec(), Vector3::new(1.0, 0.5, 0.0));
+
+        // Bright inputs, like an HDR image, still multiply down to an albedo
+        let mul = Combine::new(
+            CombineOp::Multiply,
+            solid(4.0, 0.5, 1.0),
+            solid(0.5, 0.5, 2.0),
+        );
+        assert_eq!(mul.color_at(&ctx).inner_vec(), Vector3::new(1.0, 0.25, 1.0));
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{lighting::color::Color, math::perlin::Perlin};
+
+use super::{Texture, TextureContext};
+
+/// Speckled stone: high-frequency fBm thresholded into grains of three colors.
+pub struct Granite {
+    perlin: Perlin,
+    scale: f64,
+    base: Color,
+    grain: Color,
+    fleck: Color,
+}
+
+impl Granite {
+    pub fn new(seed: u64, scale: f64, base: Color, grain: Color, fleck: Color) -> Self {
+        Granite {
+            perlin: Perlin::new(seed),
+            scale,
+            base,
+            grain,
+            fleck,
+        }
+    }
+}
+
+impl Texture for Granite {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let p = ctx.local_point.scale(self.scale);
+        let n = self.perlin.fbm(&p, 6, 2.2, 0.55);
+        if n > 0.35 {
+            self.fleck
+        } else if n > 0.0 {
+            Color::lerp(self.base, self.grain, n / 0.35)
+        } else {
+            self.base
+        }
+    }
+}
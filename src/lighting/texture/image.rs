@@ -1,36 +1,370 @@
-use image::{ImageBuffer, ImageReader, Rgb};
-use nalgebra::Vector2;
+use image::{DynamicImage, ImageBuffer, ImageReader, ImageResult, Rgb};
+use nalgebra::{Vector2, Vector3};
 
-use crate::lighting::color::Color;
+use std::path::Path;
 
-use super::Texture;
+use crate::lighting::color::{Color, ColorSpace};
+
+use super::{Texture, TextureContext};
+
+/// What to do with UVs outside the unit square.
+#[derive(Debug, Clone, Copy)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+    Border(Color),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    // Bilinear on the two mip levels either side of the ray's footprint, blended
+    Trilinear,
+    // Catmull-Rom over the 4x4 texels around the sample
+    Bicubic,
+}
+
+type Buf = ImageBuffer<Rgb<f32>, Vec<f32>>;
 
 pub struct Image {
-    buf: ImageBuffer<Rgb<f32>, Vec<f32>>,
+    // Mip pyramid: the image itself, then each level half the size of the last, down to 1x1.
+    levels: Vec<Buf>,
     width: u32,
     height: u32,
+    wrap: Wrap,
+    filter: Filter,
+    flip_u: bool,
+    flip_v: bool,
 }
 
 impl Image {
+    /// Load an image, guessing from the extension whether it's sRGB or linear.
     pub fn from_fname(fname: &str) -> Image {
-        let i = ImageReader::open(fname).unwrap().decode().unwrap();
+        Image::load(fname, ColorSpace::from_path(Path::new(fname)))
+    }
 
-        let buf = i.into_rgb32f();
+    /// Load an image whose values are in `space`. They're converted to linear
+    /// here, before the mip levels are averaged.
+    pub fn load(fname: &str, space: ColorSpace) -> Image {
+        Image::open(Path::new(fname), space).unwrap()
+    }
+
+    /// Like `load`, but hands back the error if the file can't be read or decoded.
+    pub fn open(path: &Path, space: ColorSpace) -> ImageResult<Image> {
+        let i = ImageReader::open(path)?.decode()?;
+        Ok(Image::from_decoded(i, space))
+    }
+
+    /// Use an image that's already been decoded, e.g. one embedded in another file.
+    pub fn from_decoded(i: DynamicImage, space: ColorSpace) -> Image {
+        let mut buf = i.into_rgb32f();
+        if space != ColorSpace::Linear {
+            for px in buf.pixels_mut() {
+                for c in px.0.iter_mut() {
+                    *c = space.to_linear(*c as f64) as f32;
+                }
+            }
+        }
         let width = buf.width();
         let height = buf.height();
-        Image { buf, width, height }
+        Image {
+            levels: Image::pyramid(buf),
+            width,
+            height,
+            wrap: Wrap::Repeat,
+            filter: Filter::Trilinear,
+            flip_u: false,
+            flip_v: false,
+        }
+    }
+
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_flip(mut self, flip_u: bool, flip_v: bool) -> Self {
+        self.flip_u = flip_u;
+        self.flip_v = flip_v;
+        self
+    }
+
+    fn pyramid(buf: Buf) -> Vec<Buf> {
+        let mut levels = vec![buf];
+        loop {
+            let last = levels.last().unwrap();
+            let (w, h) = (last.width(), last.height());
+            if w == 1 && h == 1 {
+                break;
+            }
+            let (nw, nh) = ((w / 2).max(1), (h / 2).max(1));
+
+            // Box filter; on odd or unit sizes the last row/column gets counted twice.
+            let next = ImageBuffer::from_fn(nw, nh, |i, j| {
+                let mut sum = [0.0f32; 3];
+                for (di, dj) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let px = last.get_pixel((2 * i + di).min(w - 1), (2 * j + dj).min(h - 1));
+                    for c in 0..3 {
+                        sum[c] += px[c] / 4.0;
+                    }
+                }
+                Rgb(sum)
+            });
+            levels.push(next);
+        }
+        levels
+    }
+
+    /// Map a texel index that may be off the edge back onto the image, or None for the border.
+    fn wrap_index(&self, i: i64, n: u32) -> Option<u32> {
+        let n = n as i64;
+        let i = match self.wrap {
+            Wrap::Repeat => i.rem_euclid(n),
+            Wrap::Clamp => i.clamp(0, n - 1),
+            Wrap::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m < n { m } else { 2 * n - 1 - m }
+            }
+            Wrap::Border(_) => {
+                if i < 0 || i >= n {
+                    return None;
+                }
+                i
+            }
+        };
+        Some(i as u32)
+    }
+
+    fn texel(&self, level: usize, i: i64, j: i64) -> Vector3<f64> {
+        let buf = &self.levels[level];
+        match (
+            self.wrap_index(i, buf.width()),
+            self.wrap_index(j, buf.height()),
+        ) {
+            (Some(i), Some(j)) => {
+                let px = buf.get_pixel(i, j);
+                Vector3::new(px[0] as f64, px[1] as f64, px[2] as f64)
+            }
+            _ => match self.wrap {
+                Wrap::Border(c) => c.inner_vec(),
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    fn bilinear(&self, level: usize, u: f64, v: f64) -> Vector3<f64> {
+        let buf = &self.levels[level];
+        // Texel centers sit at half-integers
+        let x = u * buf.width() as f64 - 0.5;
+        let y = v * buf.height() as f64 - 0.5;
+        let (i, j) = (x.floor() as i64, y.floor() as i64);
+        let (fx, fy) = (x - x.floor(), y - y.floor());
+
+        let top = self
+            .texel(level, i, j)
+            .lerp(&self.texel(level, i + 1, j), fx);
+        let bottom = self
+            .texel(level, i, j + 1)
+            .lerp(&self.texel(level, i + 1, j + 1), fx);
+        top.lerp(&bottom, fy)
     }
+
+    fn bicubic(&self, u: f64, v: f64) -> Vector3<f64> {
+        let x = u * self.width as f64 - 0.5;
+        let y = v * self.height as f64 - 0.5;
+        let (i, j) = (x.floor() as i64, y.floor() as i64);
+        let wx = catmull_rom(x - x.floor());
+        let wy = catmull_rom(y - y.floor());
+
+        let mut sum = Vector3::zeros();
+        for (dj, wy) in wy.iter().enumerate() {
+            for (di, wx) in wx.iter().enumerate() {
+                sum += self.texel(0, i + di as i64 - 1, j + dj as i64 - 1) * (wx * wy);
+            }
+        }
+        // The negative lobes can undershoot below zero next to hard edges
+        sum.sup(&Vector3::zeros())
+    }
+
+    /// Blend the two mip levels whose texels are closest in size to the footprint `duv`.
+    fn trilinear(&self, u: f64, v: f64, duv: &Vector2<f64>) -> Vector3<f64> {
+        let texels = f64::max(duv.x * self.width as f64, duv.y * self.height as f64);
+        let max_level = (self.levels.len() - 1) as f64;
+        let lod = if texels > 1.0 {
+            texels.log2().min(max_level)
+        } else {
+            0.0
+        };
+
+        let lo = lod.floor() as usize;
+        let hi = lod.ceil() as usize;
+        let near = self.bilinear(lo, u, v);
+        if lo == hi {
+            near
+        } else {
+            near.lerp(&self.bilinear(hi, u, v), lod - lo as f64)
+        }
+    }
+}
+
+/// Weights of the four texels around a sample at fraction t past the second one.
+fn catmull_rom(t: f64) -> [f64; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
 }
 
 impl Texture for Image {
-    fn color_at(&self, uv: &Vector2<f64>) -> Color {
-        assert!(uv.x < 1.0);
-        assert!(uv.y < 1.0);
-        assert!(0.0 <= uv.x);
-        assert!(0.0 <= uv.y);
-        let i = (uv.x * (self.width as f64)) as u32;
-        let j = (uv.y * (self.height as f64)) as u32;
-        let px = self.buf.get_pixel(i, j);
-        Color::new(px[0] as f64, px[1] as f64, px[2] as f64)
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let u = if self.flip_u {
+            1.0 - ctx.uv.x
+        } else {
+            ctx.uv.x
+        };
+        let v = if self.flip_v {
+            1.0 - ctx.uv.y
+        } else {
+            ctx.uv.y
+        };
+
+        let c = match self.filter {
+            Filter::Nearest => {
+                let x = u * self.width as f64;
+                let y = v * self.height as f64;
+                self.texel(0, x.floor() as i64, y.floor() as i64)
+            }
+            Filter::Bilinear => self.bilinear(0, u, v),
+            Filter::Trilinear => self.trilinear(u, v, &ctx.duv),
+            Filter::Bicubic => self.bicubic(u, v),
+        };
+        Color::from_vec(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use image::{DynamicImage, ImageBuffer, Rgb, RgbImage};
+    use nalgebra::{Vector2, Vector3};
+
+    use crate::{
+        geom::quad::Quad,
+        lighting::{
+            color::{Color, ColorSpace},
+            diffuselight::DiffuseLight,
+            texture::{Texture, TextureContext},
+        },
+        math::{interval::Interval, ray::Ray},
+        rendering::{output, par_buffer::ParBuffer, scene::Scene},
+    };
+
+    use super::{Filter, Image, Wrap};
+
+    /// Linear values above 1 come back as they are, from every filter, and
+    /// bicubic's undershoot next to an edge stops at zero.
+    #[test]
+    fn hdr_values_arent_capped() {
+        // Bright left half, black right half
+        let buf = ImageBuffer::from_fn(4, 4, |i, _| {
+            if i < 2 {
+                Rgb([4.0f32; 3])
+            } else {
+                Rgb([0.0; 3])
+            }
+        });
+        let hdr = || {
+            Image::from_decoded(DynamicImage::ImageRgb32F(buf.clone()), ColorSpace::Linear)
+                .with_wrap(Wrap::Clamp)
+        };
+        let at = |u: f64| TextureContext::from_uv(Vector2::new(u, 0.5));
+
+        for filter in [
+            Filter::Nearest,
+            Filter::Bilinear,
+            Filter::Trilinear,
+            Filter::Bicubic,
+        ] {
+            let c = hdr().with_filter(filter).color_at(&at(0.125));
+            assert!(
+                c.inner_vec().iter().all(|&x| x >= 4.0 - 1e-6),
+                "{:?}",
+                filter
+            );
+        }
+
+        let bicubic = hdr().with_filter(Filter::Bicubic);
+        // Past the edge on the dark side, where Catmull-Rom dips negative
+        let dark = bicubic.color_at(&at(0.75)).inner_vec();
+        assert!(dark.iter().all(|&x| x == 0.0), "{:?}", dark);
+        // and on the bright side, where it's allowed to ring above the input
+        let bright = bicubic.color_at(&at(0.25)).inner_vec();
+        assert!(bright.iter().all(|&x| x > 4.0), "{:?}", bright);
+    }
+
+    /// An sRGB PNG shown on a white light, looked at texel by texel, comes back
+    /// out as the same 8-bit values.
+    #[test]
+    fn srgb_png_round_trips_unlit() {
+        let (w, h) = (16, 8);
+        let texture = RgbImage::from_fn(w, h, |i, j| {
+            Rgb([
+                ((i * 37 + j * 11) % 256) as u8,
+                ((i * 53 + 200) % 256) as u8,
+                if (i + j) % 5 == 0 {
+                    255
+                } else {
+                    (j * 29) as u8
+                },
+            ])
+        });
+        let dir = std::env::temp_dir().join(format!("srgb_round_trip_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tex_path = dir.join("texture.png");
+        texture.save(&tex_path).unwrap();
+
+        // One world unit per texel, with v running down from the top edge
+        let light = DiffuseLight::new(Arc::new(Image::from_fname(tex_path.to_str().unwrap())));
+        let quad = Quad::new(
+            Vector3::zeros(),
+            Vector3::new(w as f64, 0.0, 0.0),
+            Vector3::new(0.0, h as f64, 0.0),
+            Arc::new(light),
+        );
+        let scene = Scene::new(quad, Color::black());
+
+        let mut buf = ParBuffer::new(h as usize, w as usize);
+        for j in 0..h as usize {
+            for i in 0..w as usize {
+                let at = Vector3::new(i as f64 + 0.5, h as f64 - j as f64 - 0.5, 1.0);
+                let ray = Ray::new(at, -Vector3::z_axis());
+                let hit = scene.intersect(ray, Interval::new(0.0, f64::MAX)).unwrap();
+                buf.set(i, j, hit.material().emit(&hit.tex_context(&ray)));
+            }
+        }
+        let out_path = dir.join("out.png");
+        output::save(&buf, &out_path).unwrap();
+
+        let out = image::open(&out_path).unwrap().into_rgb16();
+        std::fs::remove_dir_all(&dir).unwrap();
+        for (i, j, px) in texture.enumerate_pixels() {
+            let back = out
+                .get_pixel(i, j)
+                .0
+                .map(|c| (c as f64 / 257.0).round() as u8);
+            assert_eq!(back, px.0, "texel ({}, {})", i, j);
+        }
     }
 }
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use nalgebra::Vector3;
+
+use crate::lighting::color::Color;
+
+use super::{Texture, TextureContext};
+
+/// One minus a texture, with the input clamped to [0, 1] first so bright values
+/// invert to black rather than going negative.
+pub struct Invert {
+    tex: Arc<dyn Texture>,
+}
+
+impl Invert {
+    pub fn new(tex: Arc<dyn Texture>) -> Self {
+        Invert { tex }
+    }
+}
+
+impl Texture for Invert {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let mut c = self.tex.color_at(ctx);
+        c.clamp();
+        Color::from_vec(Vector3::repeat(1.0) - c.inner_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector2, Vector3};
+
+    use crate::lighting::{
+        color::Color,
+        texture::{Texture, TextureContext, solidcolor::SolidColor},
+    };
+
+    use super::Invert;
+
+    #[test]
+    fn bright_inputs_invert_to_black() {
+        let ctx = TextureContext::from_uv(Vector2::zeros());
+        let inv = Invert::new(Arc::new(SolidColor::new(Color::new(2.5, 0.25, 1.0))));
+        assert_eq!(inv.color_at(&ctx).inner_vec(), Vector3::new(0.0, 0.75, 0.0));
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{lighting::color::Color, math::perlin::Perlin};
+
+use super::{Texture, TextureContext};
+
+/// Veined stone: sine bands along one axis, pushed around by turbulence.
+pub struct Marble {
+    perlin: Perlin,
+    scale: f64,
+    turbulence: f64,
+    base: Color,
+    vein: Color,
+}
+
+impl Marble {
+    pub fn new(seed: u64, scale: f64, turbulence: f64, base: Color, vein: Color) -> Self {
+        Marble {
+            perlin: Perlin::new(seed),
+            scale,
+            turbulence,
+            base,
+            vein,
+        }
+    }
+}
+
+impl Texture for Marble {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let p = ctx.local_point.scale(self.scale);
+        let t = 0.5 * (1.0 + f64::sin(p.x + self.turbulence * self.perlin.turb(&p, 7)));
+        Color::lerp(self.vein, self.base, t)
+    }
+}
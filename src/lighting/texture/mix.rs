@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use crate::lighting::color::Color;
+
+use super::{Texture, TextureContext};
+
+/// Blend of two textures, `tex1` where the mask is black and `tex2` where it's white.
+pub struct Mix {
+    tex1: Arc<dyn Texture>,
+    tex2: Arc<dyn Texture>,
+    mask: Arc<dyn Texture>,
+}
+
+impl Mix {
+    pub fn new(tex1: Arc<dyn Texture>, tex2: Arc<dyn Texture>, mask: Arc<dyn Texture>) -> Self {
+        Mix { tex1, tex2, mask }
+    }
+}
+
+impl Texture for Mix {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        // A mask brighter than white still only picks tex2
+        let t = self.mask.color_at(ctx).luminance().clamp(0.0, 1.0);
+        Color::lerp(self.tex1.color_at(ctx), self.tex2.color_at(ctx), t)
+    }
+}
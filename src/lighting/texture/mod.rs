@@ -1,12 +1,98 @@
-use nalgebra::Vector2;
+use nalgebra::{Vector2, Vector3};
 
 use super::color::Color;
 
+/// Everything a texture can look at when picking a color for a surface point.
+#[derive(Debug, Clone, Copy)]
+pub struct TextureContext {
+    pub uv: Vector2<f64>,
+    /// How much of the UV square the ray covers along u and v, for picking a mip level.
+    pub duv: Vector2<f64>,
+    /// Hit point before the object's transforms, so solid textures move with the object.
+    pub local_point: Vector3<f64>,
+    /// Color interpolated from the mesh's vertices, if it has any.
+    pub vertex_color: Option<Color>,
+}
+
+impl TextureContext {
+    /// A context with only texture coordinates, at the origin.
+    #[cfg(test)]
+    pub fn from_uv(uv: Vector2<f64>) -> Self {
+        TextureContext {
+            uv,
+            duv: Vector2::zeros(),
+            local_point: Vector3::zeros(),
+            vertex_color: None,
+        }
+    }
+}
+
 pub trait Texture: Sync + Send {
-    fn color_at(&self, uv: &Vector2<f64>) -> Color;
+    fn color_at(&self, ctx: &TextureContext) -> Color;
 }
 
+pub mod checker3d;
 pub mod checkerboard;
+pub mod combine;
+pub mod granite;
 pub mod image;
+pub mod invert;
+pub mod marble;
+pub mod mix;
+pub mod noise;
+pub mod ramp;
+pub mod remap;
 pub mod scaletex;
 pub mod solidcolor;
+pub mod uvtransform;
+pub mod vertexcolor;
+pub mod wood;
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Vector2, Vector3};
+
+    use crate::lighting::color::Color;
+
+    use super::{Texture, TextureContext, granite::Granite, marble::Marble, wood::Wood};
+
+    #[test]
+    fn procedural_textures_stay_in_unit_range() {
+        let (dark, light) = (Color::black(), Color::white());
+        let textures: [(&str, Box<dyn Texture>); 3] = [
+            ("marble", Box::new(Marble::new(1, 2.0, 5.0, light, dark))),
+            ("wood", Box::new(Wood::new(2, 1.5, 4.0, light, dark))),
+            (
+                "granite",
+                Box::new(Granite::new(3, 8.0, dark, Color::new(0.5, 0.5, 0.5), light)),
+            ),
+        ];
+        for (name, tex) in &textures {
+            for i in 0..3000 {
+                let i = i as f64;
+                // Every other point on the y axis, where wood's rings start
+                let p = if i % 2.0 == 0.0 {
+                    Vector3::new(0.0, i * 0.01, 0.0)
+                } else {
+                    Vector3::new(
+                        (i * 0.37) % 7.0 - 3.5,
+                        (i * 0.11) % 5.0 - 2.5,
+                        (i * 0.53) % 3.0,
+                    )
+                };
+                let ctx = TextureContext {
+                    local_point: p,
+                    ..TextureContext::from_uv(Vector2::zeros())
+                };
+                let c = tex.color_at(&ctx).inner_vec();
+                assert!(
+                    c.iter().all(|x| (0.0..=1.0).contains(x)),
+                    "{} is {:?} at {:?}",
+                    name,
+                    c,
+                    p
+                );
+            }
+        }
+    }
+}
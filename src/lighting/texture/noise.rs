@@ -0,0 +1,55 @@
+use nalgebra::Vector3;
+
+use crate::{lighting::color::Color, math::perlin::Perlin};
+
+use super::{Texture, TextureContext};
+
+#[derive(Debug, Clone, Copy)]
+pub enum NoiseKind {
+    Plain,
+    Turbulence {
+        depth: u32,
+    },
+    Fbm {
+        octaves: u32,
+        lacunarity: f64,
+        gain: f64,
+    },
+}
+
+/// Grayscale Perlin noise.
+pub struct Noise {
+    perlin: Perlin,
+    scale: f64,
+    kind: NoiseKind,
+}
+
+impl Noise {
+    pub fn new(seed: u64, scale: f64, kind: NoiseKind) -> Self {
+        Noise {
+            perlin: Perlin::new(seed),
+            scale,
+            kind,
+        }
+    }
+
+    fn value(&self, p: &Vector3<f64>) -> f64 {
+        let p = p.scale(self.scale);
+        match self.kind {
+            NoiseKind::Plain => 0.5 * (1.0 + self.perlin.noise(&p)),
+            NoiseKind::Turbulence { depth } => self.perlin.turb(&p, depth),
+            NoiseKind::Fbm {
+                octaves,
+                lacunarity,
+                gain,
+            } => 0.5 * (1.0 + self.perlin.fbm(&p, octaves, lacunarity, gain)),
+        }
+    }
+}
+
+impl Texture for Noise {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let v = self.value(&ctx.local_point).clamp(0.0, 1.0);
+        Color::new(v, v, v)
+    }
+}
@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use crate::lighting::color::Color;
+
+use super::{Texture, TextureContext};
+
+/// Color ramp: maps the brightness of `tex` through a piecewise linear gradient.
+pub struct Ramp {
+    tex: Arc<dyn Texture>,
+    // Sorted by position
+    stops: Vec<(f64, Color)>,
+}
+
+impl Ramp {
+    pub fn new(tex: Arc<dyn Texture>, mut stops: Vec<(f64, Color)>) -> Self {
+        assert!(!stops.is_empty(), "a ramp needs at least one stop");
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ramp { tex, stops }
+    }
+}
+
+impl Texture for Ramp {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let t = self.tex.color_at(ctx).luminance();
+
+        let first = self.stops[0];
+        let last = self.stops[self.stops.len() - 1];
+        if t <= first.0 {
+            return first.1;
+        }
+        if t >= last.0 {
+            return last.1;
+        }
+
+        let (lo, hi) = self
+            .stops
+            .windows(2)
+            .map(|w| (w[0], w[1]))
+            .find(|(_, hi)| t <= hi.0)
+            .unwrap();
+        let span = hi.0 - lo.0;
+        if span <= 0.0 {
+            return hi.1;
+        }
+        Color::lerp(lo.1, hi.1, (t - lo.0) / span)
+    }
+}
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use crate::lighting::color::Color;
+
+use super::{Texture, TextureContext};
+
+/// Linearly remaps each channel of `tex` from `[from_lo, from_hi]` to `[to_lo, to_hi]`.
+/// Handy for adjusting the contrast of a noise before using it as a mask.
+pub struct Remap {
+    tex: Arc<dyn Texture>,
+    from_lo: f64,
+    from_hi: f64,
+    to_lo: f64,
+    to_hi: f64,
+}
+
+impl Remap {
+    pub fn new(tex: Arc<dyn Texture>, from: (f64, f64), to: (f64, f64)) -> Self {
+        assert!(from.0 != from.1, "can't remap from an empty range");
+        Remap {
+            tex,
+            from_lo: from.0,
+            from_hi: from.1,
+            to_lo: to.0,
+            to_hi: to.1,
+        }
+    }
+}
+
+impl Texture for Remap {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let c = self.tex.color_at(ctx).inner_vec();
+        let remapped = c.map(|x| {
+            let t = (x - self.from_lo) / (self.from_hi - self.from_lo);
+            self.to_lo + t * (self.to_hi - self.to_lo)
+        });
+        let mut c = Color::from_vec(remapped);
+        c.clamp();
+        c
+    }
+}
@@ -1,6 +1,6 @@
 use nalgebra::Vector2;
 
-use super::Texture;
+use super::{Texture, TextureContext};
 
 use std::sync::Arc;
 
@@ -21,8 +21,13 @@ impl ScaleTex {
 }
 
 impl Texture for ScaleTex {
-    fn color_at(&self, uv: &nalgebra::Vector2<f64>) -> crate::lighting::color::Color {
-        let uv2 = Vector2::new(uv.x / self.scale_u, uv.y / self.scale_v);
-        self.tex.color_at(&uv2)
+    fn color_at(&self, ctx: &TextureContext) -> crate::lighting::color::Color {
+        let uv2 = Vector2::new(ctx.uv.x / self.scale_u, ctx.uv.y / self.scale_v);
+        let duv2 = Vector2::new(ctx.duv.x / self.scale_u, ctx.duv.y / self.scale_v);
+        self.tex.color_at(&TextureContext {
+            uv: uv2,
+            duv: duv2,
+            ..*ctx
+        })
     }
 }
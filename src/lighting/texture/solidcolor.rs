@@ -1,6 +1,6 @@
 use crate::lighting::color::Color;
 
-use super::Texture;
+use super::{Texture, TextureContext};
 
 pub struct SolidColor {
     albedo: Color,
@@ -13,7 +13,7 @@ impl SolidColor {
 }
 
 impl Texture for SolidColor {
-    fn color_at(&self, _: &nalgebra::Vector2<f64>) -> Color {
+    fn color_at(&self, _: &TextureContext) -> Color {
         self.albedo
     }
 }
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use nalgebra::{Rotation2, Vector2};
+
+use crate::lighting::color::Color;
+
+use super::{Texture, TextureContext};
+
+/// Moves `tex` around in UV space: tiled `tile` times, then rotated by `rotation`
+/// radians about the origin, then shifted by `offset`.
+pub struct UvTransform {
+    tile: Vector2<f64>,
+    rotation: Rotation2<f64>,
+    offset: Vector2<f64>,
+    tex: Arc<dyn Texture>,
+}
+
+impl UvTransform {
+    pub fn new(
+        tile: Vector2<f64>,
+        rotation: f64,
+        offset: Vector2<f64>,
+        tex: Arc<dyn Texture>,
+    ) -> Self {
+        UvTransform {
+            tile,
+            rotation: Rotation2::new(rotation),
+            offset,
+            tex,
+        }
+    }
+}
+
+impl Texture for UvTransform {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let uv = self.rotation * ctx.uv.component_mul(&self.tile) + self.offset;
+        // Rotation keeps lengths, so only tiling changes how much of the texture the ray covers.
+        let duv = ctx.duv.component_mul(&self.tile.abs());
+        self.tex.color_at(&TextureContext { uv, duv, ..*ctx })
+    }
+}
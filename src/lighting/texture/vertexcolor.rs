@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use crate::lighting::color::Color;
+
+use super::{Texture, TextureContext};
+
+/// The color painted on a mesh's vertices, e.g. from a scanned PLY. Surfaces
+/// without vertex colors get `fallback`.
+pub struct VertexColor {
+    fallback: Arc<dyn Texture>,
+}
+
+impl VertexColor {
+    pub fn new(fallback: Arc<dyn Texture>) -> Self {
+        VertexColor { fallback }
+    }
+}
+
+impl Texture for VertexColor {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        ctx.vertex_color
+            .unwrap_or_else(|| self.fallback.color_at(ctx))
+    }
+}
@@ -0,0 +1,36 @@
+use crate::{lighting::color::Color, math::perlin::Perlin};
+
+use super::{Texture, TextureContext};
+
+/// Growth rings around the y axis, wobbled by noise.
+pub struct Wood {
+    perlin: Perlin,
+    scale: f64,
+    rings: f64,
+    light: Color,
+    dark: Color,
+}
+
+impl Wood {
+    pub fn new(seed: u64, scale: f64, rings: f64, light: Color, dark: Color) -> Self {
+        Wood {
+            perlin: Perlin::new(seed),
+            scale,
+            rings,
+            light,
+            dark,
+        }
+    }
+}
+
+impl Texture for Wood {
+    fn color_at(&self, ctx: &TextureContext) -> Color {
+        let p = ctx.local_point.scale(self.scale);
+        let r = f64::sqrt(p.x * p.x + p.z * p.z) + 0.3 * self.perlin.fbm(&p, 4, 2.0, 0.5);
+        // Noise can push r below zero near the axis, where fract would go negative.
+        let ring = (r * self.rings).rem_euclid(1.0);
+        // Sharpen the rings so the dark latewood is thinner than the light earlywood.
+        let t = ring.powf(3.0);
+        Color::lerp(self.light, self.dark, t)
+    }
+}
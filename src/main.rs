@@ -11,6 +11,7 @@ use geom::translation::Translation;
 use geom::triangle::Triangle;
 use geom::trimesh::TriMesh;
 use geom::{Geom, Geomable};
+use image::RgbImage;
 use lighting::color::Color;
 use lighting::diffuselight::DiffuseLight;
 use lighting::lambertian::Lambertian;
@@ -20,11 +21,26 @@ use lighting::texture::Texture;
 use lighting::texture::image::Image;
 use lighting::texture::solidcolor::SolidColor;
 use nalgebra::{Unit, Vector3};
+use rendering::accumulator::Accumulator;
+use rendering::aov::{Aov, AovBuffers};
 use rendering::camera::Camera;
+use rendering::checkpoint::Checkpoint;
+use rendering::controls::CameraControls;
+use rendering::denoise::{self, Denoiser, Features};
+use rendering::gltf;
+use rendering::output;
+use rendering::par_buffer::ParBuffer;
+use rendering::picking::{self, Outline};
+use rendering::preview::Preview;
+use rendering::progress::CancelToken;
 use rendering::renderer::Renderer;
 use rendering::scene::Scene;
+use rendering::scenedesc::SceneDesc;
+use rendering::tiles::TileOrder;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -34,20 +50,94 @@ mod math;
 mod rendering;
 mod util;
 
-fn main() {
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-
-    let window_width = 960;
-    let window_height = 540;
+// How often to write a checkpoint when running with `--checkpoint`.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
 
-    let window = video_subsystem
-        .window("rust-sdl2 demo", window_width, window_height)
-        .position_centered()
-        .build()
-        .unwrap();
+fn main() {
+    // --checkpoint <file>: periodically save the render so far to <file>
+    // --resume: continue from the checkpoint file instead of starting over
+    let args: Vec<String> = std::env::args().collect();
+    let checkpoint_path = args
+        .iter()
+        .position(|a| a == "--checkpoint")
+        .map(|i| PathBuf::from(args.get(i + 1).expect("--checkpoint takes a file name")));
+    let resume = args.iter().any(|a| a == "--resume");
+    if resume && checkpoint_path.is_none() {
+        eprintln!("--resume needs --checkpoint <file> to resume from");
+        std::process::exit(2);
+    }
+    // --passes <n>: with --render and --checkpoint, how many one-sample passes to render
+    let passes: u64 = args
+        .iter()
+        .position(|a| a == "--passes")
+        .map(|i| {
+            args.get(i + 1)
+                .and_then(|n| n.parse().ok())
+                .expect("--passes takes a number")
+        })
+        .unwrap_or(64);
+    // --render <file>: render without a window and save to <file>
+    // --aovs <a,b,..>: also render these AOVs, as extra channels of an .exr or as separate files
+    let render_path = args
+        .iter()
+        .position(|a| a == "--render")
+        .map(|i| PathBuf::from(args.get(i + 1).expect("--render takes a file name")));
+    // --frames <n>: with --render, render n frames of the scene's animation to
+    // numbered files next to <file>
+    let frames: usize = args
+        .iter()
+        .position(|a| a == "--frames")
+        .map(|i| {
+            args.get(i + 1)
+                .and_then(|n| n.parse().ok())
+                .filter(|&n| n > 0)
+                .expect("--frames takes a positive number")
+        })
+        .unwrap_or(1);
+    let aovs: Vec<Aov> = args
+        .iter()
+        .position(|a| a == "--aovs")
+        .map(|i| {
+            let list = args
+                .get(i + 1)
+                .expect("--aovs takes a comma separated list");
+            list.split(',')
+                .map(|name| Aov::from_name(name).expect("unknown aov"))
+                .collect()
+        })
+        .unwrap_or_default();
+    // --scene <file>: load the scene (and camera, if it has one) from a JSON scene file,
+    // or a .gltf/.glb
+    let scene_path = args
+        .iter()
+        .position(|a| a == "--scene")
+        .map(|i| args.get(i + 1).expect("--scene takes a file name").clone());
+    // --denoise: run the denoiser over the --render output
+    let denoise = args.iter().any(|a| a == "--denoise");
+    // --tile-size <n>: render in tiles of at most n x n pixels
+    // --tile-order <scanline|spiral|hilbert>: the order tiles are started in
+    let tile_size: usize = args
+        .iter()
+        .position(|a| a == "--tile-size")
+        .map(|i| {
+            args.get(i + 1)
+                .and_then(|n| n.parse().ok())
+                .filter(|&n| n > 0)
+                .expect("--tile-size takes a positive number")
+        })
+        .unwrap_or(32);
+    let tile_order = args
+        .iter()
+        .position(|a| a == "--tile-order")
+        .map(|i| {
+            args.get(i + 1)
+                .and_then(|name| TileOrder::from_name(name))
+                .expect("--tile-order takes scanline, spiral or hilbert")
+        })
+        .unwrap_or(TileOrder::Spiral);
 
-    let mut canvas = window.into_canvas().build().unwrap();
+    let window_width: u32 = 960;
+    let window_height: u32 = 540;
 
     let camera_pos = Vector3::new(0.0, 0.0, 50.0);
     let camera_dir = Unit::new_normalize(Vector3::new(0.0, 0.0, -1.0));
@@ -74,19 +164,22 @@ fn main() {
     //     )),
     // ));
 
-    let bunny = (Translation::new(
-        Vector3::new(0.0, -10.0, 0.0),
-        (Scaling::new(
-            Vector3::new(10.0, 10.0, 10.0),
-            (Rotation::from_euler(
-                0.0,
-                0.0,
-                0.0,
-                (TriMesh::from_fname("scenes/teapot.obj", point8lambert.clone())),
+    let bunny = || {
+        (Translation::new(
+            Vector3::new(0.0, -10.0, 0.0),
+            (Scaling::new(
+                Vector3::new(10.0, 10.0, 10.0),
+                (Rotation::from_euler(
+                    0.0,
+                    0.0,
+                    0.0,
+                    (TriMesh::from_fname("scenes/teapot.obj", point8lambert.clone())
+                        .expect("couldn't load scenes/teapot.obj")),
+                )),
             )),
-        )),
-    ))
-    .into_geoms();
+        ))
+        .into_geoms()
+    };
 
     // let checkertex: Arc<dyn Texture> =
     // Arc::new(Checkerboard::new(
@@ -148,22 +241,49 @@ fn main() {
         birdlight.clone(),
     );
 
-    let objects = bunny;
-
-    let scene = Scene::new(objects, Color::white());
+    let (scene, scene_camera) = match scene_path {
+        Some(p) if p.ends_with(".gltf") || p.ends_with(".glb") => {
+            let g = gltf::load(&p).unwrap_or_else(|e| panic!("couldn't load {}: {}", p, e));
+            (
+                Scene::from_objects(g.objects, Color::white())
+                    .with_source_hash(g.source_hash)
+                    .with_materials(g.materials),
+                g.camera,
+            )
+        }
+        Some(p) => {
+            let sd =
+                SceneDesc::from_fname(&p).unwrap_or_else(|e| panic!("couldn't load {}: {}", p, e));
+            let scene =
+                Scene::try_from(&sd).unwrap_or_else(|e| panic!("couldn't load {}: {}", p, e));
+            (scene, sd.camera)
+        }
+        None => (Scene::new(bunny(), Color::white()), None),
+    };
 
     let recursion_depth = 50;
     let samples_per_batch = 10;
 
-    let camera = Camera::new(
-        window_width as usize,
-        window_height as usize,
-        camera_pos,
-        camera_dir,
-        camera_up_dir,
-        screen_dist,
-        80.0,
-    );
+    let camera = match scene_camera {
+        Some(cd) => Camera::new(
+            window_width as usize,
+            window_height as usize,
+            cd.pos,
+            Unit::new_normalize(cd.fwd),
+            Unit::new_normalize(cd.up),
+            cd.focal_length,
+            cd.vfov,
+        ),
+        None => Camera::new(
+            window_width as usize,
+            window_height as usize,
+            camera_pos,
+            camera_dir,
+            camera_up_dir,
+            screen_dist,
+            80.0,
+        ),
+    };
 
     let renderer = Renderer::new(
         recursion_depth,
@@ -171,20 +291,78 @@ fn main() {
         window_height as usize,
         samples_per_batch,
         0.00000001,
-    );
+    )
+    .with_tiling(tile_size, tile_order);
 
-    let start = Instant::now();
-    let mut buf = renderer.render(&camera, &scene);
-    let duration = start.elapsed();
-    dbg!(duration);
-    buf.blit_to(&mut canvas);
-    canvas.present();
+    if let Some(path) = render_path {
+        if frames > 1 {
+            if checkpoint_path.is_some() {
+                eprintln!("--checkpoint can't be used with --frames, ignoring it");
+            }
+            render_animation(&renderer, &camera, scene, &aovs, denoise, &path, frames);
+            return;
+        }
+        match &checkpoint_path {
+            Some(ck_path) if aovs.is_empty() && !denoise => {
+                let buf = render_checkpointed(&renderer, &camera, &scene, passes, ck_path, resume);
+                if let Err(e) = output::save(&buf, &path) {
+                    eprintln!("couldn't save {}: {}", path.display(), e);
+                }
+            }
+            _ => {
+                if checkpoint_path.is_some() {
+                    eprintln!("--checkpoint can't be used with --aovs or --denoise, ignoring it");
+                }
+                render_headless(&renderer, &camera, &scene, &aovs, denoise, &path);
+            }
+        }
+        return;
+    }
+
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+
+    let window_width = 960;
+    let window_height = 540;
+
+    let window = video_subsystem
+        .window("rust-sdl2 demo", window_width, window_height)
+        .position_centered()
+        .build()
+        .unwrap();
+
+    let mut canvas = window.into_canvas().build().unwrap();
 
-    // canvas.present();
+    let mut controls = CameraControls::new(&camera);
+    let mut camera = camera;
+    let scene = Arc::new(scene);
+    let renderer = Arc::new(renderer);
+    let denoiser = Denoiser::default();
+    // Denoiser guide images for the current camera, rendered when first needed.
+    let mut features: Option<Features> = None;
+    let mut view_mode = ViewMode::Noisy;
+    let resumed = match (&checkpoint_path, resume) {
+        (Some(path), true) => load_checkpoint(path, &scene, &renderer, &camera),
+        _ => None,
+    };
+    let mut preview = match resumed {
+        Some(ck) => Preview::resume(renderer.clone(), scene.clone(), camera.clone(), ck),
+        None => Preview::start(renderer.clone(), scene.clone(), camera.clone()),
+    };
+    let mut last_checkpoint = Instant::now();
+
+    // Click (press and release without dragging) to pick and outline an object.
+    let mut pressed_at = None;
+    let mut picked: Option<usize> = None;
+    let mut outline: Option<Outline> = None;
+
+    let highlight = Color::new(1.0, 0.6, 0.0);
+    let frame_time = Duration::new(0, 1_000_000_000u32 / 60);
     let mut event_pump = sdl_context.event_pump().unwrap();
     'running: loop {
-        // canvas.set_draw_color(Color::RGB(i, 64, 255 - i));
-        // canvas.clear();
+        // Coalesce all camera movement in a frame into a single restart of the preview.
+        let mut moved = None;
+        let mut redraw = false;
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. }
@@ -192,10 +370,301 @@ fn main() {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => break 'running,
-                _ => {}
+                Event::KeyDown {
+                    keycode: Some(Keycode::C),
+                    ..
+                } => {
+                    let json = serde_json::json!({ "camera": camera.to_json() });
+                    println!("{}", serde_json::to_string_pretty(&json).unwrap());
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::P),
+                    ..
+                } => {
+                    let fname = format!("frame_{}.png", preview.passes());
+                    let mut img = RgbImage::new(window_width, window_height);
+                    preview.snapshot().blit_to(&mut img);
+                    img.save(&fname).unwrap();
+                    println!("saved {}", fname);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::N),
+                    ..
+                } => {
+                    view_mode = view_mode.next();
+                    println!("view: {:?}", view_mode);
+                    redraw = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::X),
+                    ..
+                } => {
+                    let fname = format!("frame_{}.exr", preview.passes());
+                    output::save(&preview.snapshot(), Path::new(&fname)).unwrap();
+                    println!("saved {}", fname);
+                }
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } => pressed_at = Some((x, y)),
+                Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } => {
+                    if pressed_at == Some((x, y)) {
+                        match picking::pick(&camera, &scene, x as usize, y as usize) {
+                            Some(pick) => {
+                                println!("{}", pick);
+                                picked = Some(pick.object_id);
+                            }
+                            None => {
+                                println!("nothing at ({}, {})", x, y);
+                                picked = None;
+                            }
+                        }
+                        outline = picked.map(|id| {
+                            let (w, h) = (window_width as usize, window_height as usize);
+                            Outline::new(&camera, &scene, id, h, w)
+                        });
+                        redraw = true;
+                    }
+                    pressed_at = None;
+                }
+                e => {
+                    if let Some(c) = controls.handle(moved.as_ref().unwrap_or(&camera), &e) {
+                        moved = Some(c);
+                    }
+                }
+            }
+        }
+
+        let keys = event_pump.keyboard_state();
+        if let Some(c) = controls.fly(
+            moved.as_ref().unwrap_or(&camera),
+            &keys,
+            frame_time.as_secs_f64(),
+        ) {
+            moved = Some(c);
+        }
+
+        if let Some(c) = moved {
+            camera = c;
+            preview.restart(camera.clone());
+            outline = picked.map(|id| {
+                let (w, h) = (window_width as usize, window_height as usize);
+                Outline::new(&camera, &scene, id, h, w)
+            });
+            features = None;
+        }
+
+        let mut fresh = preview.poll();
+        if redraw && fresh.is_none() {
+            fresh = Some(preview.snapshot());
+        }
+        if let Some(buf) = fresh {
+            let mut buf = if view_mode == ViewMode::Noisy {
+                buf
+            } else {
+                let f = features.get_or_insert_with(|| renderer.render_features(&camera, &scene));
+                let denoised = denoiser.denoise(&buf, f);
+                match view_mode {
+                    ViewMode::Compare => denoise::side_by_side(&buf, &denoised),
+                    _ => denoised,
+                }
+            };
+            if let Some(outline) = &outline {
+                outline.draw_on(&mut buf, highlight);
+            }
+            buf.blit_to(&mut canvas);
+            canvas.present();
+            canvas.window_mut().set_title(&preview.title()).unwrap();
+        }
+
+        if let Some(path) = &checkpoint_path
+            && last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL
+        {
+            save_checkpoint(&preview.checkpoint(), path);
+            last_checkpoint = Instant::now();
+        }
+
+        ::std::thread::sleep(frame_time);
+    }
+
+    if let Some(path) = &checkpoint_path {
+        save_checkpoint(&preview.checkpoint(), path);
+    }
+}
+
+/// The checkpoint at `path`, if it's there and was made from this scene and
+/// these settings. Otherwise says why and returns None, to start over.
+fn load_checkpoint(
+    path: &Path,
+    scene: &Scene,
+    renderer: &Renderer,
+    camera: &Camera,
+) -> Option<Checkpoint> {
+    match Checkpoint::resume(path, scene, renderer, camera) {
+        Ok(ck) => {
+            println!("resuming from {} passes", ck.accum.passes());
+            Some(ck)
+        }
+        Err(e) => {
+            eprintln!("can't resume from {}: {}, starting over", path.display(), e);
+            None
+        }
+    }
+}
+
+/// A failed save is only reported: the render goes on, and the previous
+/// checkpoint is still there.
+fn save_checkpoint(ck: &Checkpoint, path: &Path) {
+    if let Err(e) = ck.save(path) {
+        eprintln!("couldn't save checkpoint to {}: {}", path.display(), e);
+    }
+}
+
+/// Render `passes` one-sample passes without a window, saving a checkpoint to
+/// `ck_path` as it goes so an interrupted render can be picked up with `--resume`.
+fn render_checkpointed(
+    renderer: &Renderer,
+    camera: &Camera,
+    scene: &Scene,
+    passes: u64,
+    ck_path: &Path,
+    resume: bool,
+) -> ParBuffer {
+    let (mut accum, seed) = match resume
+        .then(|| load_checkpoint(ck_path, scene, renderer, camera))
+        .flatten()
+    {
+        Some(ck) => (ck.accum, ck.seed),
+        None => (
+            Accumulator::new(renderer.window_height(), renderer.window_width()),
+            rand::random(),
+        ),
+    };
+
+    let cancel = CancelToken::new();
+    let mut last_checkpoint = Instant::now();
+    while accum.passes() < passes {
+        // Pass n always gets seed + n, as in the viewer
+        let pass = renderer.render_pass(camera, scene, seed.wrapping_add(accum.passes()), &cancel);
+        accum.add_pass(&pass);
+        eprint!("\rpass {}/{}  ", accum.passes(), passes);
+        if last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+            save_checkpoint(
+                &Checkpoint::new(scene, renderer, camera, seed, &accum),
+                ck_path,
+            );
+            last_checkpoint = Instant::now();
+        }
+    }
+    eprintln!();
+    save_checkpoint(
+        &Checkpoint::new(scene, renderer, camera, seed, &accum),
+        ck_path,
+    );
+    accum.resolve()
+}
+
+/// What the viewer shows, cycled with N.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ViewMode {
+    Noisy,
+    Denoised,
+    /// Noisy on the left half, denoised on the right.
+    Compare,
+}
+
+impl ViewMode {
+    fn next(self) -> Self {
+        match self {
+            ViewMode::Noisy => ViewMode::Denoised,
+            ViewMode::Denoised => ViewMode::Compare,
+            ViewMode::Compare => ViewMode::Noisy,
+        }
+    }
+}
+
+fn render_headless(
+    renderer: &Renderer,
+    camera: &Camera,
+    scene: &Scene,
+    aovs: &[Aov],
+    denoise: bool,
+    path: &Path,
+) {
+    let start = Instant::now();
+    if denoise {
+        let mut with_features = aovs.to_vec();
+        for a in Features::AOVS {
+            if !with_features.contains(&a) {
+                with_features.push(a);
             }
         }
+        let (buf, aov_bufs) = renderer.render_aovs(camera, scene, &with_features);
+        let features = Features::from_aovs(&aov_bufs, buf.rows(), buf.cols());
+        let buf = Denoiser::default().denoise(&buf, &features);
+        if aovs.is_empty() {
+            output::save(&buf, path).unwrap();
+        } else {
+            save_with_aovs(&buf, &aov_bufs, path);
+        }
+    } else if aovs.is_empty() {
+        let buf = renderer.render_tiled(
+            camera,
+            scene,
+            &|p| {
+                let eta = p.eta().map(|d| d.as_secs()).unwrap_or(0);
+                eprint!("\r{:5.1}% eta {}s  ", p.fraction() * 100.0, eta);
+            },
+            &CancelToken::new(),
+        );
+        eprintln!();
+        output::save(&buf, path).unwrap();
+    } else {
+        let (buf, aov_bufs) = renderer.render_aovs(camera, scene, aovs);
+        save_with_aovs(&buf, &aov_bufs, path);
+    }
+    println!("rendered {} in {:?}", path.display(), start.elapsed());
+}
+
+/// Render `frames` frames to `<stem>_0000.<ext>` and on, moving the scene on a
+/// frame in between.
+fn render_animation(
+    renderer: &Renderer,
+    camera: &Camera,
+    mut scene: Scene,
+    aovs: &[Aov],
+    denoise: bool,
+    path: &Path,
+    frames: usize,
+) {
+    let stem = path.file_stem().unwrap().to_str().unwrap();
+    let ext = path.extension().unwrap().to_str().unwrap();
+    for frame in 0..frames {
+        if frame > 0 {
+            scene = scene.next_frame();
+        }
+        let frame_path = path.with_file_name(format!("{}_{:04}.{}", stem, frame, ext));
+        render_headless(renderer, camera, &scene, aovs, denoise, &frame_path);
+    }
+}
 
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+/// AOVs go in the same file for EXR, and in files next to it otherwise.
+fn save_with_aovs(buf: &ParBuffer, aov_bufs: &AovBuffers, path: &Path) {
+    let is_exr = path.extension().is_some_and(|e| e == "exr");
+    if is_exr {
+        aov_bufs.save_exr(buf, path).unwrap();
+    } else {
+        output::save(buf, path).unwrap();
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let stem = path.file_stem().unwrap().to_str().unwrap();
+        let ext = path.extension().unwrap().to_str().unwrap();
+        aov_bufs.save_separate(dir, stem, ext).unwrap();
     }
 }
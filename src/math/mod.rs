@@ -1,6 +1,7 @@
 pub mod axis;
 pub mod interval;
 pub mod onlinemean;
+pub mod perlin;
 pub mod ray;
 pub mod raypacket;
 pub mod screenspace;
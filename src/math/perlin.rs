@@ -0,0 +1,149 @@
+use nalgebra::{Unit, Vector3};
+use rand::{Rng, SeedableRng, rngs::SmallRng, seq::SliceRandom};
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise, following Ray Tracing: The Next Week: random unit gradients
+/// at the lattice points, hashed by permuting each coordinate, and blended with
+/// a Hermite smoothstep. Values are roughly in [-1, 1].
+pub struct Perlin {
+    gradients: Vec<Unit<Vector3<f64>>>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    /// Seeded so that a scene file always produces the same pattern.
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| {
+                loop {
+                    let v = Vector3::new(
+                        rng.random_range(-1.0..1.0),
+                        rng.random_range(-1.0..1.0),
+                        rng.random_range(-1.0..1.0),
+                    );
+                    let n = v.norm_squared();
+                    if 1e-8 < n && n <= 1.0 {
+                        break Unit::new_normalize(v);
+                    }
+                }
+            })
+            .collect();
+
+        let mut perm = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        let perm_x = perm();
+        let perm_y = perm();
+        let perm_z = perm();
+
+        Perlin {
+            gradients,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    pub fn noise(&self, p: &Vector3<f64>) -> f64 {
+        let u = p.x - p.x.floor();
+        let v = p.y - p.y.floor();
+        let w = p.z - p.z.floor();
+
+        let i = p.x.floor() as i64;
+        let j = p.y.floor() as i64;
+        let k = p.z.floor() as i64;
+
+        // Hermite smoothing to hide the lattice.
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+
+        let mask = (POINT_COUNT - 1) as i64;
+        let mut acc = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let idx = self.perm_x[((i + di) & mask) as usize]
+                        ^ self.perm_y[((j + dj) & mask) as usize]
+                        ^ self.perm_z[((k + dk) & mask) as usize];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vector3::new(u - fi, v - fj, w - fk);
+                    acc += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * self.gradients[idx].dot(&weight);
+                }
+            }
+        }
+        acc
+    }
+
+    /// Sum of `|noise|` over `depth` octaves, each at double the frequency and half the weight.
+    pub fn turb(&self, p: &Vector3<f64>, depth: u32) -> f64 {
+        let mut acc = 0.0;
+        let mut p = *p;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            acc += weight * self.noise(&p).abs();
+            weight *= 0.5;
+            p *= 2.0;
+        }
+        acc
+    }
+
+    /// Fractional Brownian motion: signed noise summed over octaves, scaling the
+    /// frequency by `lacunarity` and the amplitude by `gain` each time.
+    pub fn fbm(&self, p: &Vector3<f64>, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+        let mut acc = 0.0;
+        let mut p = *p;
+        let mut amp = 1.0;
+        for _ in 0..octaves {
+            acc += amp * self.noise(&p);
+            amp *= gain;
+            p *= lacunarity;
+        }
+        acc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::Perlin;
+
+    fn points() -> impl Iterator<Item = Vector3<f64>> {
+        (0..4000).map(|i| {
+            let i = i as f64;
+            Vector3::new(
+                (i * 0.731) % 37.0 - 18.5,
+                (i * 1.379) % 23.0 - 11.5,
+                (i * 0.157) % 41.0 - 20.5,
+            )
+        })
+    }
+
+    #[test]
+    fn same_seed_same_noise() {
+        let (a, b, other) = (Perlin::new(7), Perlin::new(7), Perlin::new(8));
+        assert!(points().all(|p| a.noise(&p) == b.noise(&p)));
+        assert!(points().any(|p| a.noise(&p) != other.noise(&p)));
+    }
+
+    #[test]
+    fn noise_is_within_unit_range() {
+        for seed in 0..4 {
+            let perlin = Perlin::new(seed);
+            for p in points() {
+                let n = perlin.noise(&p);
+                assert!((-1.0..=1.0).contains(&n), "{} at {:?}", n, p);
+            }
+        }
+    }
+}
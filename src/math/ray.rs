@@ -1,10 +1,27 @@
 use nalgebra::{Unit, UnitVector3, Vector3};
 
+/// Rays through the neighbouring pixels, one step over in x and in y. Where they
+/// land next to the main ray's hit tells how big the pixel is on the surface.
+#[derive(Debug, Copy, Clone)]
+pub struct Differentials {
+    pub rx_origin: Vector3<f64>,
+    pub rx_dir: Vector3<f64>,
+    pub ry_origin: Vector3<f64>,
+    pub ry_dir: Vector3<f64>,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Ray {
     origin: Vector3<f64>,
     // Always normalized
     dir: Unit<Vector3<f64>>,
+
+    // The ray as a cone: its width at the origin, and how fast that grows per unit distance.
+    // Zero for rays that aren't tracking their footprint.
+    width: f64,
+    spread: f64,
+    // Only camera rays have them; bounced rays fall back on the cone.
+    diff: Option<Differentials>,
 }
 
 impl Ray {
@@ -12,6 +29,9 @@ impl Ray {
         Ray {
             origin: origin,
             dir: dir,
+            width: 0.0,
+            spread: 0.0,
+            diff: None,
         }
     }
 
@@ -23,6 +43,21 @@ impl Ray {
         Ray::new(from, Unit::new_normalize(to - from))
     }
 
+    pub fn with_cone(mut self, width: f64, spread: f64) -> Self {
+        self.width = width;
+        self.spread = spread;
+        self
+    }
+
+    pub fn with_differentials(mut self, diff: Differentials) -> Self {
+        self.diff = Some(diff);
+        self
+    }
+
+    pub fn differentials(&self) -> Option<&Differentials> {
+        self.diff.as_ref()
+    }
+
     pub fn origin(&self) -> Vector3<f64> {
         self.origin
     }
@@ -34,4 +69,13 @@ impl Ray {
     pub fn at(&self, t: f64) -> Vector3<f64> {
         self.origin + self.dir.scale(t)
     }
+
+    pub fn spread(&self) -> f64 {
+        self.spread
+    }
+
+    /// Width of the ray's cone at distance t.
+    pub fn width_at(&self, t: f64) -> f64 {
+        self.width + self.spread * t
+    }
 }
@@ -0,0 +1,101 @@
+use nalgebra::Vector3;
+
+use crate::lighting::color::Color;
+
+use super::par_buffer::ParBuffer;
+
+/// Running per-pixel radiance sums over progressive passes. Sums are kept
+/// unclamped so that bright samples still average out correctly.
+pub struct Accumulator {
+    rows: usize,
+    cols: usize,
+    sum: Box<[Vector3<f64>]>, //stored row-major
+    passes: u64,
+}
+
+impl Accumulator {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        let sum = vec![Vector3::zeros(); rows * cols].into_boxed_slice();
+        Accumulator {
+            rows,
+            cols,
+            sum,
+            passes: 0,
+        }
+    }
+
+    pub fn from_parts(rows: usize, cols: usize, sum: Box<[Vector3<f64>]>, passes: u64) -> Self {
+        assert!(sum.len() == rows * cols);
+        Accumulator {
+            rows,
+            cols,
+            sum,
+            passes,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn sums(&self) -> &[Vector3<f64>] {
+        &self.sum
+    }
+
+    pub fn passes(&self) -> u64 {
+        self.passes
+    }
+
+    pub fn reset(&mut self) {
+        self.sum.fill(Vector3::zeros());
+        self.passes = 0;
+    }
+
+    pub fn add_pass(&mut self, pass: &ParBuffer) {
+        assert!(pass.rows() == self.rows && pass.cols() == self.cols);
+        for y in 0..self.rows {
+            for x in 0..self.cols {
+                self.sum[y * self.cols + x] += pass.get(x, y).inner_vec();
+            }
+        }
+        self.passes += 1;
+    }
+
+    /// The mean of all passes so far.
+    pub fn resolve(&self) -> ParBuffer {
+        let mut buf = ParBuffer::new(self.rows, self.cols);
+        if self.passes == 0 {
+            return buf;
+        }
+        let n = self.passes as f64;
+        for y in 0..self.rows {
+            for x in 0..self.cols {
+                buf.set(x, y, Color::from_vec(self.sum[y * self.cols + x] / n));
+            }
+        }
+        buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lighting::color::Color, rendering::par_buffer::ParBuffer};
+
+    use super::Accumulator;
+
+    #[test]
+    fn keeps_values_above_one() {
+        let mut accum = Accumulator::new(1, 1);
+        for v in [3.0, 5.0] {
+            let mut pass = ParBuffer::new(1, 1);
+            pass.set(0, 0, Color::new(v, v, v) + Color::new(1.0, 0.0, 0.0));
+            accum.add_pass(&pass);
+        }
+        let v = accum.resolve().get(0, 0).inner_vec();
+        assert_eq!((v.x, v.y, v.z), (5.0, 4.0, 4.0));
+    }
+}
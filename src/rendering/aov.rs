@@ -0,0 +1,240 @@
+use std::{io, path::Path};
+
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, SmallVec,
+    WritableImage,
+};
+use nalgebra::Vector3;
+
+use crate::lighting::color::Color;
+
+use super::{output, par_buffer::ParBuffer};
+
+/// Arbitrary output variables: extra per-pixel data rendered alongside the beauty
+/// image, for compositing. All but the lighting split describe the first hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    Depth,
+    Position,
+    Normal,
+    Uv,
+    Albedo,
+    MaterialId,
+    ObjectId,
+    /// Light reaching the first hit straight from an emitter or the background.
+    Direct,
+    /// Light reaching the first hit after bouncing at least once more.
+    Indirect,
+    /// Light emitted by the first hit itself (or the background, on a miss).
+    Emission,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 10] = [
+        Aov::Depth,
+        Aov::Position,
+        Aov::Normal,
+        Aov::Uv,
+        Aov::Albedo,
+        Aov::MaterialId,
+        Aov::ObjectId,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::Emission,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::Normal => "normal",
+            Aov::Uv => "uv",
+            Aov::Albedo => "albedo",
+            Aov::MaterialId => "material_id",
+            Aov::ObjectId => "object_id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Emission => "emission",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Aov> {
+        Aov::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    /// Channel names when written to EXR.
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Position | Aov::Normal => &["X", "Y", "Z"],
+            Aov::Uv => &["U", "V"],
+            Aov::MaterialId | Aov::ObjectId => &["id"],
+            Aov::Albedo | Aov::Direct | Aov::Indirect | Aov::Emission => &["R", "G", "B"],
+        }
+    }
+
+    /// Ids can't be meaningfully averaged over samples, so they keep the first one.
+    fn is_id(&self) -> bool {
+        matches!(self, Aov::MaterialId | Aov::ObjectId)
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// The value of every AOV for a single camera sample.
+#[derive(Debug, Clone, Copy)]
+pub struct AovSample {
+    values: [Vector3<f64>; Aov::ALL.len()],
+}
+
+impl Default for AovSample {
+    fn default() -> Self {
+        AovSample {
+            values: [Vector3::zeros(); Aov::ALL.len()],
+        }
+    }
+}
+
+impl AovSample {
+    pub fn set(&mut self, aov: Aov, v: Vector3<f64>) {
+        self.values[aov.index()] = v;
+    }
+
+    pub fn set_scalar(&mut self, aov: Aov, v: f64) {
+        self.set(aov, Vector3::new(v, 0.0, 0.0));
+    }
+
+    pub fn set_color(&mut self, aov: Aov, c: Color) {
+        self.set(aov, c.inner_vec());
+    }
+
+    pub fn get(&self, aov: Aov) -> Vector3<f64> {
+        self.values[aov.index()]
+    }
+}
+
+/// Running mean of the AOVs over the samples taken for one pixel.
+pub struct AovPixel {
+    sum: AovSample,
+    count: u64,
+}
+
+impl AovPixel {
+    pub fn new() -> Self {
+        AovPixel {
+            sum: AovSample::default(),
+            count: 0,
+        }
+    }
+
+    pub fn add_sample(&mut self, s: &AovSample) {
+        for aov in Aov::ALL {
+            if !aov.is_id() {
+                self.sum.values[aov.index()] += s.get(aov);
+            } else if self.count == 0 {
+                self.sum.values[aov.index()] = s.get(aov);
+            }
+        }
+        self.count += 1;
+    }
+
+    pub fn mean(&self, aov: Aov) -> Vector3<f64> {
+        if aov.is_id() || self.count == 0 {
+            self.sum.get(aov)
+        } else {
+            self.sum.get(aov) / self.count as f64
+        }
+    }
+}
+
+/// One image per enabled AOV.
+pub struct AovBuffers {
+    rows: usize,
+    cols: usize,
+    aovs: Vec<Aov>,
+    data: Vec<Box<[Vector3<f64>]>>, //one per aov, stored row-major
+}
+
+impl AovBuffers {
+    pub fn new(rows: usize, cols: usize, aovs: &[Aov]) -> Self {
+        let data = aovs
+            .iter()
+            .map(|_| vec![Vector3::zeros(); rows * cols].into_boxed_slice())
+            .collect();
+        AovBuffers {
+            rows,
+            cols,
+            aovs: aovs.to_vec(),
+            data,
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, px: &AovPixel) {
+        for (i, aov) in self.aovs.iter().enumerate() {
+            self.data[i][y * self.cols + x] = px.mean(*aov);
+        }
+    }
+
+    pub fn get(&self, aov: Aov, x: usize, y: usize) -> Option<Vector3<f64>> {
+        let i = self.aovs.iter().position(|a| *a == aov)?;
+        Some(self.data[i][y * self.cols + x])
+    }
+
+    fn channel(&self, i: usize, component: usize) -> Vec<f32> {
+        self.data[i].iter().map(|v| v[component] as f32).collect()
+    }
+
+    /// Write the beauty image and every AOV into one EXR, with the AOVs as
+    /// `<aov>.<channel>` channel groups (e.g. `normal.X`) next to the beauty's R, G and B.
+    pub fn save_exr(&self, beauty: &ParBuffer, path: &Path) -> io::Result<()> {
+        assert!(beauty.rows() == self.rows && beauty.cols() == self.cols);
+
+        let mut channels = Vec::new();
+        for (c, name) in ["R", "G", "B"].into_iter().enumerate() {
+            let mut v = Vec::with_capacity(self.rows * self.cols);
+            for y in 0..self.rows {
+                for x in 0..self.cols {
+                    v.push(beauty.get(x, y).inner_vec()[c] as f32);
+                }
+            }
+            channels.push(AnyChannel::new(name, FlatSamples::F32(v)));
+        }
+        for (i, aov) in self.aovs.iter().enumerate() {
+            for (c, chan) in aov.channels().iter().enumerate() {
+                let name = format!("{}.{}", aov.name(), chan);
+                channels.push(AnyChannel::new(
+                    name.as_str(),
+                    FlatSamples::F32(self.channel(i, c)),
+                ));
+            }
+        }
+
+        let layer = Layer::new(
+            (self.cols, self.rows),
+            LayerAttributes::named("render"),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(SmallVec::from_vec(channels)),
+        );
+        Image::from_layer(layer)
+            .write()
+            .to_file(path)
+            .map_err(io::Error::other)
+    }
+
+    /// Write each AOV to its own file, `<stem>.<aov>.<ext>`, in the format implied by `ext`.
+    pub fn save_separate(&self, dir: &Path, stem: &str, ext: &str) -> io::Result<()> {
+        for (i, aov) in self.aovs.iter().enumerate() {
+            let mut buf = ParBuffer::new(self.rows, self.cols);
+            for y in 0..self.rows {
+                for x in 0..self.cols {
+                    buf.set(x, y, Color::from_vec(self.data[i][y * self.cols + x]));
+                }
+            }
+            let path = dir.join(format!("{}.{}.{}", stem, aov.name(), ext));
+            output::save(&buf, &path)?;
+        }
+        Ok(())
+    }
+}
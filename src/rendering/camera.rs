@@ -1,10 +1,22 @@
+use std::hash::{Hash, Hasher};
+
 use nalgebra::{Unit, Vector3};
+use serde_json::json;
 
-use crate::math::ray::Ray;
+use crate::math::ray::{Differentials, Ray};
 
+#[derive(Clone)]
 pub struct Camera {
     pos: Vector3<f64>,
 
+    //Parameters the camera was built from, kept around so it can be moved and rebuilt
+    window_width: usize,
+    window_height: usize,
+    fwd: Unit<Vector3<f64>>,
+    up: Unit<Vector3<f64>>,
+    focal_length: f64,
+    vfov: f64,
+
     //World-position screen data
     pixel_00_center: Vector3<f64>, //world space location of the center of (0,0) in pixel space. (i.e) the screen
     pixel_delta_u: Vector3<f64>,   //how much to the right in world space +1px in screen space x is
@@ -57,14 +69,86 @@ impl Camera {
 
         Camera {
             pos: pos,
+            window_width,
+            window_height,
+            fwd,
+            up,
+            focal_length,
+            vfov,
             pixel_00_center: screen00,
             pixel_delta_u,
             pixel_delta_v,
         }
     }
 
+    /// The same camera (resolution, lens) placed somewhere else.
+    pub fn moved(
+        &self,
+        pos: Vector3<f64>,
+        fwd: Unit<Vector3<f64>>,
+        up: Unit<Vector3<f64>>,
+    ) -> Self {
+        Camera::new(
+            self.window_width,
+            self.window_height,
+            pos,
+            fwd,
+            up,
+            self.focal_length,
+            self.vfov,
+        )
+    }
+
+    pub fn pos(&self) -> Vector3<f64> {
+        self.pos
+    }
+
+    pub fn fwd(&self) -> Unit<Vector3<f64>> {
+        self.fwd
+    }
+
+    pub fn up(&self) -> Unit<Vector3<f64>> {
+        self.up
+    }
+
+    pub fn focal_length(&self) -> f64 {
+        self.focal_length
+    }
+
+    /// The camera in the scene file's `"camera"` format.
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "pos": [self.pos.x, self.pos.y, self.pos.z],
+            "fwd": [self.fwd.x, self.fwd.y, self.fwd.z],
+            "up": [self.up.x, self.up.y, self.up.z],
+            "focal_length": self.focal_length,
+            "vfov": self.vfov,
+        })
+    }
+
     pub fn ray_through(&self, u: f64, v: f64) -> Ray {
         let pt = self.pixel_00_center + self.pixel_delta_u.scale(u) + self.pixel_delta_v.scale(v);
+        // A pixel's worth of angle, so the ray's footprint grows to cover one pixel.
+        let spread = self.pixel_delta_v.norm() / self.focal_length;
         Ray::through_points(self.pos, pt)
+            .with_cone(0.0, spread)
+            .with_differentials(Differentials {
+                rx_origin: self.pos,
+                rx_dir: (pt + self.pixel_delta_u - self.pos).normalize(),
+                ry_origin: self.pos,
+                ry_dir: (pt + self.pixel_delta_v - self.pos).normalize(),
+            })
+    }
+}
+
+impl Hash for Camera {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.window_width.hash(state);
+        self.window_height.hash(state);
+        for v in [self.pos, *self.fwd, *self.up] {
+            v.map(f64::to_bits).hash(state);
+        }
+        self.focal_length.to_bits().hash(state);
+        self.vfov.to_bits().hash(state);
     }
 }
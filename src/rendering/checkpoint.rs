@@ -0,0 +1,329 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use nalgebra::Vector3;
+
+use super::{accumulator::Accumulator, camera::Camera, renderer::Renderer, scene::Scene};
+
+const MAGIC: &[u8; 4] = b"RTCK";
+const VERSION: u32 = 1;
+// Magic, version, then six u64s: hashes, seed, passes, rows and cols
+const HEADER_LEN: u64 = 4 + 4 + 6 * 8;
+// Three f64s per pixel
+const PIXEL_LEN: u64 = 3 * 8;
+
+/// 64-bit FNV-1a. Used instead of `DefaultHasher` because checkpoint hashes
+/// have to stay the same across builds and compiler versions.
+pub struct Fnv(u64);
+
+impl Fnv {
+    pub fn new() -> Self {
+        Fnv(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut h = Fnv::new();
+    h.write(bytes);
+    h.finish()
+}
+
+/// Hash of everything that affects what a pixel converges to, other than the
+/// scene: the recursion depth, the resolution and the camera.
+pub fn settings_hash(renderer: &Renderer, camera: &Camera) -> u64 {
+    let mut h = Fnv::new();
+    renderer.hash(&mut h);
+    camera.hash(&mut h);
+    h.finish()
+}
+
+/// Everything needed to pick a progressive render back up where it left off.
+///
+/// The seed and pass count determine the pixel jitter of the next pass, so a
+/// resumed render continues the same sample sequence instead of repeating it.
+/// Scattering still draws from the thread-local RNG, so a resumed render is
+/// statistically, but not bit-for-bit, the same as an uninterrupted one.
+pub struct Checkpoint {
+    pub scene_hash: u64,
+    pub settings_hash: u64,
+    pub seed: u64,
+    pub accum: Accumulator,
+}
+
+#[derive(Debug)]
+pub enum ResumeError {
+    Io(io::Error),
+    SceneChanged,
+    SettingsChanged,
+}
+
+impl fmt::Display for ResumeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResumeError::Io(e) => write!(f, "{}", e),
+            ResumeError::SceneChanged => write!(f, "the scene has changed"),
+            ResumeError::SettingsChanged => write!(f, "the render settings have changed"),
+        }
+    }
+}
+
+impl From<io::Error> for ResumeError {
+    fn from(e: io::Error) -> Self {
+        ResumeError::Io(e)
+    }
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn read_f64(r: &mut impl Read) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(r)?))
+}
+
+impl Checkpoint {
+    /// Snapshot of a progressive render of `scene` with these settings.
+    pub fn new(
+        scene: &Scene,
+        renderer: &Renderer,
+        camera: &Camera,
+        seed: u64,
+        accum: &Accumulator,
+    ) -> Self {
+        Checkpoint {
+            scene_hash: scene.fingerprint(),
+            settings_hash: settings_hash(renderer, camera),
+            seed,
+            accum: Accumulator::from_parts(
+                accum.rows(),
+                accum.cols(),
+                accum.sums().into(),
+                accum.passes(),
+            ),
+        }
+    }
+
+    /// Write the checkpoint next to `path` and move it into place, so a crash
+    /// mid-write never clobbers the previous checkpoint.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        {
+            let mut w = BufWriter::new(File::create(&tmp)?);
+            w.write_all(MAGIC)?;
+            w.write_all(&VERSION.to_le_bytes())?;
+            for v in [
+                self.scene_hash,
+                self.settings_hash,
+                self.seed,
+                self.accum.passes(),
+                self.accum.rows() as u64,
+                self.accum.cols() as u64,
+            ] {
+                w.write_all(&v.to_le_bytes())?;
+            }
+            for s in self.accum.sums() {
+                for c in [s.x, s.y, s.z] {
+                    w.write_all(&c.to_le_bytes())?;
+                }
+            }
+            w.flush()?;
+        }
+        fs::rename(tmp, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut r = BufReader::new(file);
+
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        let mut version = [0; 4];
+        r.read_exact(&mut version)?;
+        if &magic != MAGIC || u32::from_le_bytes(version) != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a checkpoint file, or from an incompatible version",
+            ));
+        }
+
+        let scene_hash = read_u64(&mut r)?;
+        let settings_hash = read_u64(&mut r)?;
+        let seed = read_u64(&mut r)?;
+        let passes = read_u64(&mut r)?;
+        let rows = read_u64(&mut r)?;
+        let cols = read_u64(&mut r)?;
+
+        // Check the size against the file before allocating, so a corrupt
+        // header can't ask for more memory than the file could fill
+        let pixels = rows
+            .checked_mul(cols)
+            .filter(|&n| n.checked_mul(PIXEL_LEN) == Some(file_len - HEADER_LEN))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("checkpoint size doesn't match its {}x{} header", cols, rows),
+                )
+            })?;
+        let (rows, cols) = (rows as usize, cols as usize);
+
+        let mut sum = Vec::with_capacity(pixels as usize);
+        for _ in 0..pixels {
+            let x = read_f64(&mut r)?;
+            let y = read_f64(&mut r)?;
+            let z = read_f64(&mut r)?;
+            sum.push(Vector3::new(x, y, z));
+        }
+
+        Ok(Checkpoint {
+            scene_hash,
+            settings_hash,
+            seed,
+            accum: Accumulator::from_parts(rows, cols, sum.into_boxed_slice(), passes),
+        })
+    }
+
+    /// Load a checkpoint, refusing it if it was made with a different scene or settings.
+    pub fn resume(
+        path: &Path,
+        scene: &Scene,
+        renderer: &Renderer,
+        camera: &Camera,
+    ) -> Result<Self, ResumeError> {
+        let ck = Checkpoint::load(path)?;
+        if ck.scene_hash != scene.fingerprint() {
+            return Err(ResumeError::SceneChanged);
+        }
+        if ck.settings_hash != settings_hash(renderer, camera) {
+            return Err(ResumeError::SettingsChanged);
+        }
+        Ok(ck)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::Vector3;
+
+    use crate::{
+        geom::sphere::Sphere,
+        lighting::{color::Color, lambertian::Lambertian, texture::solidcolor::SolidColor},
+        rendering::{
+            accumulator::Accumulator, camera::Camera, renderer::Renderer, scene::Scene,
+            tiles::TileOrder,
+        },
+    };
+
+    use std::io;
+
+    use super::{Checkpoint, ResumeError};
+
+    fn scene(r: f64) -> Scene {
+        let mat = Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::white()))));
+        Scene::new(Sphere::new(Vector3::zeros(), r, mat), Color::black())
+    }
+
+    #[test]
+    fn save_load_resume_round_trip() {
+        let (rows, cols) = (3, 4);
+        let camera = Camera::new(
+            cols,
+            rows,
+            Vector3::new(0.0, 0.0, 5.0),
+            -Vector3::z_axis(),
+            Vector3::y_axis(),
+            1.0,
+            60.0,
+        );
+        let renderer = Renderer::new(8, cols, rows, 10, 0.01);
+        let sums: Vec<_> = (0..rows * cols)
+            .map(|i| Vector3::new(i as f64, 0.5, 7.25))
+            .collect();
+        let accum = Accumulator::from_parts(rows, cols, sums.clone().into_boxed_slice(), 5);
+
+        let path =
+            std::env::temp_dir().join(format!("checkpoint_round_trip_{}.ck", std::process::id()));
+        Checkpoint::new(&scene(1.0), &renderer, &camera, 42, &accum)
+            .save(&path)
+            .unwrap();
+
+        // Tiling and batching don't change what the render converges to
+        let retiled = Renderer::new(8, cols, rows, 3, 0.5).with_tiling(7, TileOrder::Hilbert);
+        let ck = Checkpoint::resume(&path, &scene(1.0), &retiled, &camera).unwrap();
+        assert_eq!(ck.seed, 42);
+        assert_eq!(ck.accum.passes(), 5);
+        assert_eq!((ck.accum.rows(), ck.accum.cols()), (rows, cols));
+        assert_eq!(ck.accum.sums(), &sums[..]);
+
+        let deeper = Renderer::new(9, cols, rows, 10, 0.01);
+        let refused = [
+            Checkpoint::resume(&path, &scene(1.0), &deeper, &camera),
+            Checkpoint::resume(&path, &scene(2.0), &renderer, &camera),
+        ];
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(refused[0], Err(ResumeError::SettingsChanged)));
+        assert!(matches!(refused[1], Err(ResumeError::SceneChanged)));
+    }
+
+    #[test]
+    fn corrupt_sizes_are_invalid_data() {
+        let path =
+            std::env::temp_dir().join(format!("checkpoint_corrupt_{}.ck", std::process::id()));
+        let accum = Accumulator::from_parts(2, 2, vec![Vector3::zeros(); 4].into_boxed_slice(), 1);
+        Checkpoint {
+            scene_hash: 1,
+            settings_hash: 2,
+            seed: 3,
+            accum,
+        }
+        .save(&path)
+        .unwrap();
+        let good = std::fs::read(&path).unwrap();
+
+        let load_with = |bytes: &[u8]| {
+            std::fs::write(&path, bytes).unwrap();
+            Checkpoint::load(&path).map(|_| ()).map_err(|e| e.kind())
+        };
+        // rows and cols are the last two header fields
+        let with_size = |rows: u64, cols: u64| {
+            let mut b = good.clone();
+            b[40..48].copy_from_slice(&rows.to_le_bytes());
+            b[48..56].copy_from_slice(&cols.to_le_bytes());
+            b
+        };
+        let results = [
+            load_with(&good),
+            load_with(&good[..good.len() - 8]),
+            load_with(&with_size(u64::MAX, 3)),
+            load_with(&with_size(1 << 20, 1 << 20)),
+        ];
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(results[0], Ok(()));
+        for r in &results[1..] {
+            assert_eq!(*r, Err(io::ErrorKind::InvalidData));
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use nalgebra::{Rotation3, Unit, Vector3};
+use sdl2::{event::Event, keyboard::KeyboardState, keyboard::Scancode};
+
+use super::camera::Camera;
+
+const ORBIT_SENSITIVITY: f64 = 0.005; // radians per pixel of mouse travel
+const LOOK_SENSITIVITY: f64 = 0.003;
+const ZOOM_STEP: f64 = 0.9; // distance to target is multiplied by this per wheel notch
+const FLY_SPEED: f64 = 0.5; // fraction of the distance to the target per second
+
+/// Mouse and keyboard camera navigation for the viewer.
+///
+/// - left drag: orbit around the target
+/// - middle drag: pan
+/// - wheel: zoom towards/away from the target
+/// - right drag: look around in place (fly mode), with WASD to move and Q/E for down/up
+pub struct CameraControls {
+    target: Vector3<f64>,
+}
+
+impl CameraControls {
+    pub fn new(camera: &Camera) -> Self {
+        CameraControls {
+            target: camera.pos() + camera.fwd().scale(camera.focal_length()),
+        }
+    }
+
+    fn right(camera: &Camera) -> Unit<Vector3<f64>> {
+        Unit::new_normalize(camera.fwd().cross(&camera.up()))
+    }
+
+    fn look_at(camera: &Camera, pos: Vector3<f64>, target: Vector3<f64>) -> Option<Camera> {
+        let fwd = target - pos;
+        // Refuse to look straight along the up vector, the basis would degenerate.
+        if fwd.norm() < 1e-8 || fwd.normalize().cross(&camera.up()).norm() < 1e-3 {
+            return None;
+        }
+        Some(camera.moved(pos, Unit::new_normalize(fwd), camera.up()))
+    }
+
+    /// Handle a mouse event, returning the moved camera if it changed.
+    pub fn handle(&mut self, camera: &Camera, event: &Event) -> Option<Camera> {
+        match event {
+            Event::MouseMotion {
+                mousestate,
+                xrel,
+                yrel,
+                ..
+            } => {
+                let (dx, dy) = (*xrel as f64, *yrel as f64);
+                if mousestate.left() {
+                    self.orbit(camera, dx, dy)
+                } else if mousestate.middle() {
+                    self.pan(camera, dx, dy)
+                } else if mousestate.right() {
+                    self.look(camera, dx, dy)
+                } else {
+                    None
+                }
+            }
+            Event::MouseWheel { y, .. } => self.zoom(camera, *y),
+            _ => None,
+        }
+    }
+
+    fn orbit(&mut self, camera: &Camera, dx: f64, dy: f64) -> Option<Camera> {
+        let yaw = Rotation3::from_axis_angle(&camera.up(), -dx * ORBIT_SENSITIVITY);
+        let pitch = Rotation3::from_axis_angle(&Self::right(camera), -dy * ORBIT_SENSITIVITY);
+        let offset = yaw * pitch * (camera.pos() - self.target);
+        Self::look_at(camera, self.target + offset, self.target)
+    }
+
+    fn pan(&mut self, camera: &Camera, dx: f64, dy: f64) -> Option<Camera> {
+        let dist = (self.target - camera.pos()).norm();
+        let up = Self::right(camera).cross(&camera.fwd());
+        let by =
+            (Self::right(camera).scale(-dx) + up.scale(dy)).scale(dist * ORBIT_SENSITIVITY * 0.2);
+        self.target += by;
+        Some(camera.moved(camera.pos() + by, camera.fwd(), camera.up()))
+    }
+
+    fn zoom(&mut self, camera: &Camera, notches: i32) -> Option<Camera> {
+        let offset = (camera.pos() - self.target).scale(ZOOM_STEP.powi(notches));
+        Self::look_at(camera, self.target + offset, self.target)
+    }
+
+    fn look(&mut self, camera: &Camera, dx: f64, dy: f64) -> Option<Camera> {
+        let yaw = Rotation3::from_axis_angle(&camera.up(), -dx * LOOK_SENSITIVITY);
+        let pitch = Rotation3::from_axis_angle(&Self::right(camera), -dy * LOOK_SENSITIVITY);
+        let to_target = yaw * pitch * (self.target - camera.pos());
+        let camera = Self::look_at(camera, camera.pos(), camera.pos() + to_target)?;
+        self.target = camera.pos() + to_target;
+        Some(camera)
+    }
+
+    /// Apply held movement keys over `dt` seconds.
+    pub fn fly(&mut self, camera: &Camera, keys: &KeyboardState, dt: f64) -> Option<Camera> {
+        let right = Self::right(camera);
+        let mut dir = Vector3::zeros();
+        for (code, v) in [
+            (Scancode::W, *camera.fwd()),
+            (Scancode::S, -*camera.fwd()),
+            (Scancode::D, *right),
+            (Scancode::A, -*right),
+            (Scancode::E, *camera.up()),
+            (Scancode::Q, -*camera.up()),
+        ] {
+            if keys.is_scancode_pressed(code) {
+                dir += v;
+            }
+        }
+        if dir.norm() < 1e-8 {
+            return None;
+        }
+
+        let dist = (self.target - camera.pos()).norm();
+        let by = dir.normalize().scale(dist * FLY_SPEED * dt);
+        self.target += by;
+        Some(camera.moved(camera.pos() + by, camera.fwd(), camera.up()))
+    }
+}
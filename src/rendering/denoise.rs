@@ -0,0 +1,206 @@
+use nalgebra::Vector3;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::lighting::color::Color;
+
+use super::{
+    aov::{Aov, AovBuffers},
+    par_buffer::ParBuffer,
+};
+
+/// Per-pixel guide images for the denoiser. Edges in these are kept sharp,
+/// since unlike the noisy color they are (nearly) free of noise.
+pub struct Features {
+    rows: usize,
+    cols: usize,
+    albedo: Vec<Vector3<f64>>,
+    normal: Vec<Vector3<f64>>,
+    depth: Vec<f64>,
+}
+
+impl Features {
+    pub const AOVS: [Aov; 3] = [Aov::Albedo, Aov::Normal, Aov::Depth];
+
+    /// Pull the guide images out of rendered AOVs, which must include `Features::AOVS`.
+    pub fn from_aovs(aovs: &AovBuffers, rows: usize, cols: usize) -> Self {
+        let mut albedo = Vec::with_capacity(rows * cols);
+        let mut normal = Vec::with_capacity(rows * cols);
+        let mut depth = Vec::with_capacity(rows * cols);
+        for y in 0..rows {
+            for x in 0..cols {
+                let get = |a| aovs.get(a, x, y).expect("feature aovs were rendered");
+                albedo.push(get(Aov::Albedo));
+                normal.push(get(Aov::Normal));
+                depth.push(get(Aov::Depth).x);
+            }
+        }
+        Features {
+            rows,
+            cols,
+            albedo,
+            normal,
+            depth,
+        }
+    }
+}
+
+/// Edge-avoiding à-trous wavelet filter (Dammertz et al. 2010): repeated
+/// 5x5 B-spline blurs with doubling tap spacing, where each tap is weighted
+/// down by how different its color, albedo, normal and depth are from the center.
+pub struct Denoiser {
+    iterations: u32,
+    sigma_color: f64,
+    sigma_albedo: f64,
+    sigma_normal: f64,
+    sigma_depth: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            iterations: 5,
+            sigma_color: 0.5,
+            sigma_albedo: 0.1,
+            sigma_normal: 0.3,
+            sigma_depth: 0.05,
+        }
+    }
+}
+
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+impl Denoiser {
+    pub fn denoise(&self, buf: &ParBuffer, f: &Features) -> ParBuffer {
+        assert!(buf.rows() == f.rows && buf.cols() == f.cols);
+        let (rows, cols) = (f.rows, f.cols);
+
+        let mut color: Vec<Vector3<f64>> = (0..rows * cols)
+            .map(|i| buf.get(i % cols, i / cols).inner_vec())
+            .collect();
+
+        let mut sigma_color = self.sigma_color;
+        for it in 0..self.iterations {
+            let step = 1i64 << it;
+            color = (0..rows * cols)
+                .into_par_iter()
+                .map(|i| self.filter_px(&color, f, i, step, sigma_color))
+                .collect();
+            // Later passes see an already smoothed image, so tolerate less color difference.
+            sigma_color /= 2.0;
+        }
+
+        let mut out = ParBuffer::new(rows, cols);
+        for (i, c) in color.into_iter().enumerate() {
+            out.set(i % cols, i / cols, Color::from_vec(c));
+        }
+        out
+    }
+
+    fn filter_px(
+        &self,
+        color: &[Vector3<f64>],
+        f: &Features,
+        i: usize,
+        step: i64,
+        sigma_color: f64,
+    ) -> Vector3<f64> {
+        let (x, y) = ((i % f.cols) as i64, (i / f.cols) as i64);
+        let mut sum = Vector3::zeros();
+        let mut weight_sum = 0.0;
+
+        for (ky, hy) in KERNEL.iter().enumerate() {
+            for (kx, hx) in KERNEL.iter().enumerate() {
+                let qx = x + (kx as i64 - 2) * step;
+                let qy = y + (ky as i64 - 2) * step;
+                if qx < 0 || qy < 0 || qx >= f.cols as i64 || qy >= f.rows as i64 {
+                    continue;
+                }
+                let j = qy as usize * f.cols + qx as usize;
+
+                let dc = (color[i] - color[j]).norm_squared() / (sigma_color * sigma_color);
+                let da = (f.albedo[i] - f.albedo[j]).norm_squared()
+                    / (self.sigma_albedo * self.sigma_albedo);
+                let dn = (f.normal[i] - f.normal[j]).norm_squared()
+                    / (self.sigma_normal * self.sigma_normal);
+                let dz = Self::depth_distance(f.depth[i], f.depth[j]) / self.sigma_depth;
+
+                let w = hx * hy * f64::exp(-(dc + da + dn + dz));
+                sum += color[j] * w;
+                weight_sum += w;
+            }
+        }
+
+        // The center tap always has weight hx * hy > 0, so this never divides by zero.
+        sum / weight_sum
+    }
+
+    /// Relative depth difference, with misses (infinite depth) only matching other misses.
+    fn depth_distance(a: f64, b: f64) -> f64 {
+        match (a.is_finite(), b.is_finite()) {
+            (true, true) => (a - b).abs() / f64::max(a.abs(), 1e-8),
+            (false, false) => 0.0,
+            _ => f64::INFINITY,
+        }
+    }
+}
+
+/// Noisy image on the left, denoised on the right, split by a line down the middle.
+pub fn side_by_side(noisy: &ParBuffer, denoised: &ParBuffer) -> ParBuffer {
+    let (rows, cols) = (noisy.rows(), noisy.cols());
+    let mut out = ParBuffer::new(rows, cols);
+    for y in 0..rows {
+        for x in 0..cols {
+            let c = if x == cols / 2 {
+                Color::white()
+            } else if x < cols / 2 {
+                noisy.get(x, y)
+            } else {
+                denoised.get(x, y)
+            };
+            out.set(x, y, c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use crate::{lighting::color::Color, rendering::par_buffer::ParBuffer};
+
+    use super::{Denoiser, Features};
+
+    #[test]
+    fn edges_in_the_guides_survive_flat_regions_smooth() {
+        // Dark on the left, bright on the right, both with +-0.1 checkerboard noise
+        let (rows, cols) = (16, 16);
+        let base = |x: usize| if x < cols / 2 { 0.2 } else { 0.8 };
+        let mut buf = ParBuffer::new(rows, cols);
+        let mut albedo = Vec::new();
+        for y in 0..rows {
+            for x in 0..cols {
+                let noise = if (x + y) % 2 == 0 { 0.1 } else { -0.1 };
+                let c = base(x) + noise;
+                buf.set(x, y, Color::new(c, c, c));
+                albedo.push(Vector3::repeat(base(x)));
+            }
+        }
+        let features = Features {
+            rows,
+            cols,
+            albedo,
+            normal: vec![Vector3::z(); rows * cols],
+            depth: vec![1.0; rows * cols],
+        };
+
+        let out = Denoiser::default().denoise(&buf, &features);
+        for y in 0..rows {
+            for x in 0..cols {
+                let c = out.get(x, y).inner_vec().x;
+                // Including the columns either side of the edge
+                assert!((c - base(x)).abs() < 0.03, "({}, {}) is {}", x, y, c);
+            }
+        }
+    }
+}
@@ -0,0 +1,734 @@
+use std::{fmt, fs, io, path::Path, sync::Arc};
+
+use nalgebra::{Matrix4, Point3, Quaternion, Unit, UnitQuaternion, Vector2, Vector3};
+use serde_json::Value;
+
+use crate::{
+    geom::{
+        Geom, Geomable,
+        trimesh::{MeshData, MeshError, TriMesh},
+    },
+    lighting::{
+        color::{Color, ColorSpace},
+        diffuselight::DiffuseLight,
+        lambertian::Lambertian,
+        material::Material,
+        metal::Metal,
+        texture::{
+            Texture,
+            combine::{Combine, CombineOp},
+            image::{Image, Wrap},
+            solidcolor::SolidColor,
+        },
+    },
+};
+
+use super::{checkpoint, scenedesc::CameraDesc};
+
+#[derive(Debug)]
+pub enum GltfError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Image(image::ImageError),
+    Mesh(MeshError),
+    /// The file is valid JSON but not valid glTF, or uses something we can't load.
+    Invalid(String),
+}
+
+impl From<io::Error> for GltfError {
+    fn from(e: io::Error) -> Self {
+        GltfError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for GltfError {
+    fn from(e: serde_json::Error) -> Self {
+        GltfError::Json(e)
+    }
+}
+
+impl From<image::ImageError> for GltfError {
+    fn from(e: image::ImageError) -> Self {
+        GltfError::Image(e)
+    }
+}
+
+impl From<MeshError> for GltfError {
+    fn from(e: MeshError) -> Self {
+        GltfError::Mesh(e)
+    }
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GltfError::Io(e) => write!(f, "{}", e),
+            GltfError::Json(e) => write!(f, "bad json: {}", e),
+            GltfError::Image(e) => write!(f, "couldn't decode texture: {}", e),
+            GltfError::Mesh(e) => write!(f, "{}", e),
+            GltfError::Invalid(e) => write!(f, "invalid gltf: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+fn invalid(msg: impl Into<String>) -> GltfError {
+    GltfError::Invalid(msg.into())
+}
+
+/// Everything we take from a glTF file.
+pub struct GltfScene {
+    /// One entry per placed mesh, holding all of its primitives.
+    pub objects: Vec<Vec<Geom>>,
+    /// The file's materials that are used, in file order.
+    pub materials: Vec<Arc<dyn Material>>,
+    /// The first perspective camera in the scene, if there is one.
+    pub camera: Option<CameraDesc>,
+    /// Hash of the file, which holds the materials as well as the geometry.
+    pub source_hash: u64,
+}
+
+struct Doc {
+    json: Value,
+    buffers: Vec<Vec<u8>>,
+    dir: std::path::PathBuf,
+    materials: Vec<Option<Arc<dyn Material>>>,
+}
+
+/// Load a `.gltf` (with external or embedded buffers) or `.glb` file.
+///
+/// Node transforms are baked into the triangles. Materials are approximated:
+/// emissive ones become lights, mostly metallic ones become `Metal` with fuzz
+/// from the roughness, and the rest are `Lambertian` with the base color
+/// texture. Tangents are checked like the other attributes but not kept:
+/// without normal maps there's nothing to use them.
+pub fn load(fname: &str) -> Result<GltfScene, GltfError> {
+    let bytes = fs::read(fname)?;
+    let (json, bin) = if bytes.starts_with(b"glTF") {
+        split_glb(&bytes)?
+    } else {
+        (serde_json::from_slice(&bytes)?, None)
+    };
+    let dir = Path::new(fname)
+        .parent()
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+
+    let mut buffers = Vec::new();
+    for (i, b) in array(&json, "buffers").iter().enumerate() {
+        let data = match b.get("uri").and_then(Value::as_str) {
+            Some(uri) => read_uri(uri, &dir)?,
+            None if i == 0 => bin.clone().ok_or_else(|| invalid("buffer without data"))?,
+            None => return Err(invalid("buffer without data")),
+        };
+        buffers.push(data);
+    }
+
+    let n_materials = array(&json, "materials").len();
+    let mut doc = Doc {
+        json,
+        buffers,
+        dir,
+        materials: vec![None; n_materials],
+    };
+
+    let roots: Vec<usize> = match doc.json.get("scenes").and_then(Value::as_array) {
+        Some(scenes) if !scenes.is_empty() => {
+            let s = doc.json.get("scene").and_then(Value::as_u64).unwrap_or(0) as usize;
+            indices(
+                scenes.get(s).ok_or_else(|| invalid("no such scene"))?,
+                "nodes",
+            )
+        }
+        // No scenes: every node that isn't someone's child is a root
+        _ => {
+            let nodes = array(&doc.json, "nodes");
+            let children: Vec<usize> = nodes.iter().flat_map(|n| indices(n, "children")).collect();
+            (0..nodes.len()).filter(|i| !children.contains(i)).collect()
+        }
+    };
+
+    let mut scene = GltfScene {
+        objects: Vec::new(),
+        materials: Vec::new(),
+        camera: None,
+        source_hash: checkpoint::hash_bytes(&bytes),
+    };
+    for root in roots {
+        visit(&mut doc, root, &Matrix4::identity(), &mut scene, 0)?;
+    }
+    scene.materials = doc.materials.into_iter().flatten().collect();
+    Ok(scene)
+}
+
+fn array<'a>(v: &'a Value, key: &str) -> &'a [Value] {
+    v.get(key)
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+}
+
+fn indices(v: &Value, key: &str) -> Vec<usize> {
+    array(v, key)
+        .iter()
+        .filter_map(|i| i.as_u64().map(|i| i as usize))
+        .collect()
+}
+
+fn floats(v: &Value, key: &str) -> Option<Vec<f64>> {
+    v.get(key)?.as_array()?.iter().map(Value::as_f64).collect()
+}
+
+/// The `n` numbers under `key`, or `default` if it isn't there.
+fn floats_n(v: &Value, key: &str, n: usize, default: &[f64]) -> Result<Vec<f64>, GltfError> {
+    match v.get(key) {
+        None => Ok(default.to_vec()),
+        Some(_) => floats(v, key)
+            .filter(|f| f.len() == n)
+            .ok_or_else(|| invalid(format!("{} should be {} numbers", key, n))),
+    }
+}
+
+fn split_glb(bytes: &[u8]) -> Result<(Value, Option<Vec<u8>>), GltfError> {
+    let u32_at = |at: usize| -> Result<u32, GltfError> {
+        let b = bytes
+            .get(at..at + 4)
+            .ok_or_else(|| invalid("glb ends early"))?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    let mut json = None;
+    let mut bin = None;
+    // 12 byte header, then chunks of (length, type, data)
+    let mut at = 12;
+    while at < bytes.len() {
+        let len = u32_at(at)? as usize;
+        let ty = u32_at(at + 4)?;
+        let data = bytes
+            .get(at + 8..at + 8 + len)
+            .ok_or_else(|| invalid("glb chunk runs off the end"))?;
+        match &ty.to_le_bytes() {
+            b"JSON" => json = Some(serde_json::from_slice(data)?),
+            b"BIN\0" => bin = Some(data.to_vec()),
+            _ => {}
+        }
+        at += 8 + len;
+    }
+    Ok((json.ok_or_else(|| invalid("glb without json"))?, bin))
+}
+
+fn read_uri(uri: &str, dir: &Path) -> Result<Vec<u8>, GltfError> {
+    if let Some(rest) = uri.strip_prefix("data:") {
+        let (_, data) = rest
+            .split_once(";base64,")
+            .ok_or_else(|| invalid("data uri isn't base64"))?;
+        base64_decode(data)
+    } else {
+        Ok(fs::read(dir.join(uri))?)
+    }
+}
+
+fn base64_decode(s: &str) -> Result<Vec<u8>, GltfError> {
+    let val = |c: u8| -> Result<u32, GltfError> {
+        Ok(match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(invalid("bad base64")),
+        } as u32)
+    };
+
+    let chars: Vec<u8> = s
+        .bytes()
+        .filter(|&c| c != b'=' && !c.is_ascii_whitespace())
+        .collect();
+    let mut out = Vec::with_capacity(chars.len() * 3 / 4);
+    for chunk in chars.chunks(4) {
+        let mut n = 0;
+        for (i, &c) in chunk.iter().enumerate() {
+            n |= val(c)? << (18 - 6 * i);
+        }
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        out.extend_from_slice(&bytes[..chunk.len() - 1]);
+    }
+    Ok(out)
+}
+
+impl Doc {
+    fn get(&self, key: &str, i: usize) -> Result<&Value, GltfError> {
+        array(&self.json, key)
+            .get(i)
+            .ok_or_else(|| invalid(format!("{} {} doesn't exist", key, i)))
+    }
+
+    fn buffer_view(&self, i: usize) -> Result<(&[u8], Option<usize>), GltfError> {
+        let view = self.get("bufferViews", i)?;
+        let buffer = view.get("buffer").and_then(Value::as_u64).unwrap_or(0) as usize;
+        let offset = view.get("byteOffset").and_then(Value::as_u64).unwrap_or(0) as usize;
+        let len = view
+            .get("byteLength")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| invalid("bufferView without byteLength"))? as usize;
+        let stride = view
+            .get("byteStride")
+            .and_then(Value::as_u64)
+            .map(|s| s as usize);
+        let data = self
+            .buffers
+            .get(buffer)
+            .and_then(|b| b.get(offset..offset + len))
+            .ok_or_else(|| invalid("bufferView runs off its buffer"))?;
+        Ok((data, stride))
+    }
+
+    /// Every element of an accessor, each as a list of components.
+    fn accessor(&self, i: usize) -> Result<Vec<Vec<f64>>, GltfError> {
+        let acc = self.get("accessors", i)?;
+        if acc.get("sparse").is_some() {
+            return Err(invalid("sparse accessors aren't supported"));
+        }
+        let count = acc.get("count").and_then(Value::as_u64).unwrap_or(0) as usize;
+        let n = match acc.get("type").and_then(Value::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            Some("MAT4") => 16,
+            t => return Err(invalid(format!("unsupported accessor type {:?}", t))),
+        };
+        let component = acc
+            .get("componentType")
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+        let size = match component {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            c => return Err(invalid(format!("unknown component type {}", c))),
+        };
+        let normalized = acc
+            .get("normalized")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+
+        let Some(view) = acc.get("bufferView").and_then(Value::as_u64) else {
+            // No data means all zeros
+            return Ok(vec![vec![0.0; n]; count]);
+        };
+        let (data, stride) = self.buffer_view(view as usize)?;
+        let offset = acc.get("byteOffset").and_then(Value::as_u64).unwrap_or(0) as usize;
+        let stride = stride.unwrap_or(n * size);
+
+        (0..count)
+            .map(|e| {
+                (0..n)
+                    .map(|c| {
+                        let at = offset + e * stride + c * size;
+                        let b = data
+                            .get(at..at + size)
+                            .ok_or_else(|| invalid("accessor runs off its bufferView"))?;
+                        let (v, max) = match component {
+                            5120 => (b[0] as i8 as f64, 127.0),
+                            5121 => (b[0] as f64, 255.0),
+                            5122 => (i16::from_le_bytes([b[0], b[1]]) as f64, 32767.0),
+                            5123 => (u16::from_le_bytes([b[0], b[1]]) as f64, 65535.0),
+                            5125 => (u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64, 1.0),
+                            _ => (f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64, 1.0),
+                        };
+                        Ok(if normalized { (v / max).max(-1.0) } else { v })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// An accessor holding one element per vertex, each at least `width` components.
+    fn vertex_accessor(
+        &self,
+        i: usize,
+        width: usize,
+        n_verts: usize,
+        what: &str,
+    ) -> Result<Vec<Vec<f64>>, GltfError> {
+        let rows = self.accessor(i)?;
+        if rows.len() != n_verts {
+            return Err(invalid(format!(
+                "{} has {} elements for {} vertices",
+                what,
+                rows.len(),
+                n_verts
+            )));
+        }
+        if rows.first().is_some_and(|r| r.len() < width) {
+            return Err(invalid(format!("{} needs {} components", what, width)));
+        }
+        Ok(rows)
+    }
+
+    fn material(&mut self, i: Option<usize>) -> Result<Arc<dyn Material>, GltfError> {
+        let Some(i) = i else {
+            return Ok(Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+                Color::new(0.8, 0.8, 0.8),
+            )))));
+        };
+        if let Some(Some(m)) = self.materials.get(i) {
+            return Ok(m.clone());
+        }
+
+        let m = self.get("materials", i)?.clone();
+        let pbr = m
+            .get("pbrMetallicRoughness")
+            .cloned()
+            .unwrap_or(Value::Null);
+
+        let emissive = floats_n(&m, "emissiveFactor", 3, &[0.0; 3])?;
+        // Lights can be brighter than 1 with this extension
+        let strength = m
+            .get("extensions")
+            .and_then(|e| e.get("KHR_materials_emissive_strength"))
+            .and_then(|e| e.get("emissiveStrength"))
+            .and_then(Value::as_f64)
+            .unwrap_or(1.0);
+        let base = floats_n(&pbr, "baseColorFactor", 4, &[1.0; 4])?;
+        let metallic = pbr
+            .get("metallicFactor")
+            .and_then(Value::as_f64)
+            .unwrap_or(1.0);
+        let roughness = pbr
+            .get("roughnessFactor")
+            .and_then(Value::as_f64)
+            .unwrap_or(1.0);
+        let base = albedo(&base);
+
+        let mat: Arc<dyn Material> = if emissive.iter().any(|&e| e > 0.0) && strength > 0.0 {
+            let radiance = Vector3::new(emissive[0], emissive[1], emissive[2]).map(|e| e.max(0.0));
+            Arc::new(DiffuseLight::solid(Color::from_vec(radiance * strength)))
+        } else if metallic >= 0.5 {
+            Arc::new(Metal::new(base, roughness.clamp(0.0, 1.0)))
+        } else {
+            let solid: Arc<dyn Texture> = Arc::new(SolidColor::new(base));
+            let tex = match pbr.get("baseColorTexture") {
+                Some(t) => {
+                    let idx = t.get("index").and_then(Value::as_u64).unwrap_or(0) as usize;
+                    // The texture is scaled by the factor
+                    Arc::new(Combine::new(CombineOp::Multiply, self.texture(idx)?, solid))
+                }
+                None => solid,
+            };
+            Arc::new(Lambertian::new(tex))
+        };
+
+        self.materials[i] = Some(mat.clone());
+        Ok(mat)
+    }
+
+    fn texture(&self, i: usize) -> Result<Arc<dyn Texture>, GltfError> {
+        let tex = self.get("textures", i)?;
+        let source = tex
+            .get("source")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| invalid("texture without a source"))?;
+        let img = self.get("images", source as usize)?;
+
+        let bytes = match (
+            img.get("uri").and_then(Value::as_str),
+            img.get("bufferView"),
+        ) {
+            (Some(uri), _) => read_uri(uri, &self.dir)?,
+            (None, Some(view)) => {
+                let view = view
+                    .as_u64()
+                    .ok_or_else(|| invalid("bad image bufferView"))?;
+                self.buffer_view(view as usize)?.0.to_vec()
+            }
+            _ => return Err(invalid("image without data")),
+        };
+        let decoded = image::load_from_memory(&bytes)?;
+
+        // Samplers set the wrap per axis; we only have one, so take s.
+        let wrap = match tex
+            .get("sampler")
+            .and_then(Value::as_u64)
+            .and_then(|s| array(&self.json, "samplers").get(s as usize))
+            .and_then(|s| s.get("wrapS"))
+            .and_then(Value::as_u64)
+        {
+            Some(33071) => Wrap::Clamp,
+            Some(33648) => Wrap::Mirror,
+            _ => Wrap::Repeat,
+        };
+
+        // Base color textures are sRGB
+        Ok(Arc::new(
+            Image::from_decoded(decoded, ColorSpace::Srgb).with_wrap(wrap),
+        ))
+    }
+}
+
+/// A reflectance from the first three of `v`, which the caller has checked are there.
+fn albedo(v: &[f64]) -> Color {
+    let mut c = Color::from_vec(Vector3::new(v[0], v[1], v[2]));
+    c.clamp();
+    c
+}
+
+fn local_transform(node: &Value) -> Result<Matrix4<f64>, GltfError> {
+    if node.get("matrix").is_some() {
+        let m = floats_n(node, "matrix", 16, &[])?;
+        return Ok(Matrix4::from_column_slice(&m));
+    }
+    let t = floats_n(node, "translation", 3, &[0.0; 3])?;
+    let r = floats_n(node, "rotation", 4, &[0.0, 0.0, 0.0, 1.0])?;
+    let s = floats_n(node, "scale", 3, &[1.0; 3])?;
+
+    let rotation = UnitQuaternion::from_quaternion(Quaternion::new(r[3], r[0], r[1], r[2]));
+    Ok(Matrix4::new_translation(&Vector3::new(t[0], t[1], t[2]))
+        * rotation.to_homogeneous()
+        * Matrix4::new_nonuniform_scaling(&Vector3::new(s[0], s[1], s[2])))
+}
+
+fn visit(
+    doc: &mut Doc,
+    node: usize,
+    parent: &Matrix4<f64>,
+    scene: &mut GltfScene,
+    depth: usize,
+) -> Result<(), GltfError> {
+    if depth > 256 {
+        return Err(invalid("node hierarchy has a cycle"));
+    }
+    let n = doc.get("nodes", node)?.clone();
+    let world = parent * local_transform(&n)?;
+
+    if let Some(mesh) = n.get("mesh").and_then(Value::as_u64) {
+        load_mesh(doc, mesh as usize, &world, scene)?;
+    }
+    if let Some(cam) = n.get("camera").and_then(Value::as_u64)
+        && scene.camera.is_none()
+    {
+        scene.camera = camera(doc, cam as usize, &world)?;
+    }
+    for child in indices(&n, "children") {
+        visit(doc, child, &world, scene, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn camera(doc: &Doc, i: usize, world: &Matrix4<f64>) -> Result<Option<CameraDesc>, GltfError> {
+    let cam = doc.get("cameras", i)?;
+    let Some(yfov) = cam
+        .get("perspective")
+        .and_then(|p| p.get("yfov"))
+        .and_then(Value::as_f64)
+    else {
+        // Orthographic cameras can't be expressed with ours
+        return Ok(None);
+    };
+
+    // glTF cameras look down -z with +y up
+    let pos = world.transform_point(&Point3::origin()).coords;
+    let fwd = world.transform_vector(&-Vector3::z());
+    let up = world.transform_vector(&Vector3::y());
+    Ok(Some(CameraDesc {
+        pos,
+        fwd,
+        up,
+        focal_length: 1.0,
+        vfov: yfov.to_degrees(),
+    }))
+}
+
+fn load_mesh(
+    doc: &mut Doc,
+    i: usize,
+    world: &Matrix4<f64>,
+    scene: &mut GltfScene,
+) -> Result<(), GltfError> {
+    let mesh = doc.get("meshes", i)?.clone();
+    // Normals go through the inverse transpose, so they stay perpendicular under non-uniform scales
+    let normal_matrix = world
+        .fixed_view::<3, 3>(0, 0)
+        .try_inverse()
+        .map(|m| m.transpose())
+        .unwrap_or_else(|| world.fixed_view::<3, 3>(0, 0).into_owned());
+
+    let mut geoms = Vec::new();
+    for prim in array(&mesh, "primitives") {
+        let attrs = prim
+            .get("attributes")
+            .ok_or_else(|| invalid("primitive without attributes"))?;
+        let attr = |name: &str| attrs.get(name).and_then(Value::as_u64).map(|a| a as usize);
+
+        let position = attr("POSITION").ok_or_else(|| invalid("primitive without positions"))?;
+        let rows = doc.accessor(position)?;
+        let n = rows.len();
+        if rows.first().is_some_and(|p| p.len() < 3) {
+            return Err(invalid("POSITION needs 3 components"));
+        }
+        let positions: Vec<Vector3<f64>> = rows
+            .iter()
+            .map(|p| world.transform_point(&Point3::new(p[0], p[1], p[2])).coords)
+            .collect();
+        let normals = attr("NORMAL")
+            .map(|a| doc.vertex_accessor(a, 3, n, "NORMAL"))
+            .transpose()?
+            .map(|ns| {
+                ns.iter()
+                    .map(|n| {
+                        Unit::try_new(normal_matrix * Vector3::new(n[0], n[1], n[2]), 1e-20)
+                            .unwrap_or(Vector3::y_axis())
+                    })
+                    .collect()
+            });
+        // glTF has v = 0 at the top of the image, like us
+        let uvs = attr("TEXCOORD_0")
+            .map(|a| doc.vertex_accessor(a, 2, n, "TEXCOORD_0"))
+            .transpose()?
+            .map(|ts| ts.iter().map(|t| Vector2::new(t[0], t[1])).collect());
+        let colors = attr("COLOR_0")
+            .map(|a| doc.vertex_accessor(a, 3, n, "COLOR_0"))
+            .transpose()?
+            // Alpha, if there is any, is dropped
+            .map(|cs| cs.iter().map(|c| albedo(c)).collect());
+        if let Some(a) = attr("TANGENT") {
+            doc.vertex_accessor(a, 4, n, "TANGENT")?;
+        }
+
+        let idx: Vec<usize> = match prim.get("indices").and_then(Value::as_u64) {
+            Some(a) => doc
+                .accessor(a as usize)?
+                .iter()
+                .map(|i| i[0] as usize)
+                .collect(),
+            None => (0..positions.len()).collect(),
+        };
+        let faces: Vec<Vec<usize>> = match prim.get("mode").and_then(Value::as_u64).unwrap_or(4) {
+            4 => idx.chunks_exact(3).map(|f| f.to_vec()).collect(),
+            // Strips flip every other triangle to keep the winding
+            5 => (0..idx.len().saturating_sub(2))
+                .map(|k| {
+                    if k % 2 == 0 {
+                        vec![idx[k], idx[k + 1], idx[k + 2]]
+                    } else {
+                        vec![idx[k + 1], idx[k], idx[k + 2]]
+                    }
+                })
+                .collect(),
+            6 => (1..idx.len().saturating_sub(1))
+                .map(|k| vec![idx[0], idx[k], idx[k + 1]])
+                .collect(),
+            // Points and lines have no surface to hit
+            _ => continue,
+        };
+
+        let mat = doc.material(
+            prim.get("material")
+                .and_then(Value::as_u64)
+                .map(|m| m as usize),
+        )?;
+        let data = MeshData {
+            positions,
+            normals,
+            uvs,
+            colors,
+            faces,
+        };
+        // The spec asks for flat normals where the file has none
+        geoms.extend(TriMesh::from_data(data, mat, 0.0)?.into_geoms());
+    }
+    scene.objects.push(geoms);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector2;
+    use serde_json::{Value, json};
+
+    use crate::lighting::texture::TextureContext;
+
+    use super::{GltfError, GltfScene, load};
+
+    /// One triangle, with `node` and `material` merged into its node and material.
+    fn triangle(node: Value, material: Value) -> Result<GltfScene, GltfError> {
+        load_doc(triangle_doc(node, material))
+    }
+
+    fn triangle_doc(node: Value, material: Value) -> Value {
+        let mut node_obj = json!({ "mesh": 0 });
+        node_obj
+            .as_object_mut()
+            .unwrap()
+            .extend(node.as_object().unwrap().clone());
+        json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{
+                "byteLength": 36,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+            }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+            "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }],
+            "materials": [material],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+            "nodes": [node_obj],
+        })
+    }
+
+    fn load_doc(doc: Value) -> Result<GltfScene, GltfError> {
+        let path = std::env::temp_dir().join(format!(
+            "gltf_test_{}_{:x}.gltf",
+            std::process::id(),
+            super::checkpoint::hash_bytes(doc.to_string().as_bytes())
+        ));
+        std::fs::write(&path, doc.to_string()).unwrap();
+        let loaded = load(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn short_arrays_are_invalid() {
+        assert!(triangle(json!({ "translation": [1.0, 2.0, 3.0] }), json!({})).is_ok());
+        let short = [
+            (json!({ "translation": [1.0, 2.0] }), json!({})),
+            (json!({ "rotation": [0.0, 0.0, 1.0] }), json!({})),
+            (json!({ "matrix": [1.0, 0.0, 0.0] }), json!({})),
+            (json!({}), json!({ "emissiveFactor": [1.0] })),
+            (
+                json!({}),
+                json!({ "pbrMetallicRoughness": { "baseColorFactor": [1.0, 1.0] } }),
+            ),
+        ];
+        for (node, material) in short {
+            assert!(matches!(
+                triangle(node, material),
+                Err(GltfError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn tangents_are_checked() {
+        // Tangents are only validated, so the positions stand in for them: three
+        // components where four are needed
+        let mut doc = triangle_doc(json!({}), json!({}));
+        doc["meshes"][0]["primitives"][0]["attributes"]["TANGENT"] = json!(0);
+        assert!(matches!(load_doc(doc), Err(GltfError::Invalid(_))));
+    }
+
+    #[test]
+    fn emissive_strength_isnt_capped() {
+        let material = json!({
+            "emissiveFactor": [1.0, 0.5, 0.0],
+            "extensions": { "KHR_materials_emissive_strength": { "emissiveStrength": 4.0 } }
+        });
+        let scene = triangle(json!({}), material).unwrap();
+        let emit = scene.materials[0]
+            .emit(&TextureContext::from_uv(Vector2::zeros()))
+            .inner_vec();
+        assert_eq!((emit.x, emit.y, emit.z), (4.0, 2.0, 0.0));
+    }
+}
@@ -1,6 +1,17 @@
+pub mod accumulator;
+pub mod aov;
 pub mod camera;
+pub mod checkpoint;
+pub mod controls;
+pub mod denoise;
+pub mod gltf;
+pub mod output;
 pub mod par_buffer;
+pub mod picking;
+pub mod preview;
+pub mod progress;
 pub mod render_surface;
 pub mod renderer;
 pub mod scene;
-mod scenedesc;
+pub mod scenedesc;
+pub mod tiles;
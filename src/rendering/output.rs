@@ -0,0 +1,141 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use exr::prelude::f16;
+use image::{ImageBuffer, Rgb, codecs::hdr::HdrEncoder};
+
+use super::par_buffer::ParBuffer;
+
+/// Image formats a finished render can be written in. The HDR formats get the
+/// linear radiance as-is; the PNGs are sRGB encoded like the viewer.
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Exr { half: bool },
+    Hdr,
+    Pfm,
+    Png16,
+}
+
+impl Format {
+    /// Pick a format from the file extension: `.exr` (32-bit float, or 16-bit
+    /// for names ending in `.half.exr`), `.hdr`, `.pfm` or `.png` (16-bit).
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "exr" => {
+                let stem = Path::new(path.file_stem()?);
+                let half = stem
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("half"));
+                Some(Format::Exr { half })
+            }
+            "hdr" => Some(Format::Hdr),
+            "pfm" => Some(Format::Pfm),
+            "png" => Some(Format::Png16),
+            _ => None,
+        }
+    }
+}
+
+fn linear(buf: &ParBuffer, x: usize, y: usize) -> (f32, f32, f32) {
+    let v = buf.get(x, y).inner_vec();
+    (v.x as f32, v.y as f32, v.z as f32)
+}
+
+/// Save `buf` in the format implied by the extension of `path`.
+pub fn save(buf: &ParBuffer, path: &Path) -> io::Result<()> {
+    let format = Format::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("don't know what format to write {}", path.display()),
+        )
+    })?;
+    save_as(buf, path, format)
+}
+
+pub fn save_as(buf: &ParBuffer, path: &Path, format: Format) -> io::Result<()> {
+    let (w, h) = (buf.cols(), buf.rows());
+    match format {
+        Format::Exr { half: true } => exr::prelude::write_rgb_file(path, w, h, |x, y| {
+            let (r, g, b) = linear(buf, x, y);
+            (f16::from_f32(r), f16::from_f32(g), f16::from_f32(b))
+        })
+        .map_err(io::Error::other),
+        Format::Exr { half: false } => {
+            exr::prelude::write_rgb_file(path, w, h, |x, y| linear(buf, x, y))
+                .map_err(io::Error::other)
+        }
+        Format::Hdr => {
+            let mut px = Vec::with_capacity(w * h);
+            for y in 0..h {
+                for x in 0..w {
+                    let (r, g, b) = linear(buf, x, y);
+                    px.push(Rgb([r, g, b]));
+                }
+            }
+            let out = BufWriter::new(File::create(path)?);
+            HdrEncoder::new(out)
+                .encode(&px, w, h)
+                .map_err(io::Error::other)
+        }
+        Format::Pfm => write_pfm(buf, path),
+        Format::Png16 => {
+            let mut img: ImageBuffer<Rgb<u16>, Vec<u16>> = ImageBuffer::new(w as u32, h as u32);
+            for y in 0..h {
+                for x in 0..w {
+                    img.put_pixel(x as u32, y as u32, buf.get(x, y).gamma().into());
+                }
+            }
+            img.save(path).map_err(io::Error::other)
+        }
+    }
+}
+
+/// Portable float map: a tiny text header, then little-endian f32 RGB with
+/// the bottom row first.
+fn write_pfm(buf: &ParBuffer, path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    // A negative scale marks the data as little-endian.
+    write!(out, "PF\n{} {}\n-1.0\n", buf.cols(), buf.rows())?;
+    for y in (0..buf.rows()).rev() {
+        for x in 0..buf.cols() {
+            let (r, g, b) = linear(buf, x, y);
+            for c in [r, g, b] {
+                out.write_all(&c.to_le_bytes())?;
+            }
+        }
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::Format;
+
+    #[test]
+    fn half_exr_from_name() {
+        let format = |p: &str| Format::from_path(Path::new(p));
+        assert!(matches!(
+            format("out.exr"),
+            Some(Format::Exr { half: false })
+        ));
+        assert!(matches!(
+            format("out.half.exr"),
+            Some(Format::Exr { half: true })
+        ));
+        assert!(matches!(
+            format("out.HALF.EXR"),
+            Some(Format::Exr { half: true })
+        ));
+        assert!(matches!(
+            format("half.exr"),
+            Some(Format::Exr { half: false })
+        ));
+        assert!(matches!(format("out.png"), Some(Format::Png16)));
+    }
+}
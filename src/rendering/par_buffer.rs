@@ -46,6 +46,22 @@ impl ParBuffer {
         ParBuffer { rows, cols, data }
     }
 
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.data[y * self.cols + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, c: Color) {
+        self.data[y * self.cols + x] = c;
+    }
+
     pub fn blit_to<T: RenderSurface>(&mut self, surf: &mut T) {
         for y in 0..self.rows {
             for x in 0..self.cols {
@@ -0,0 +1,114 @@
+use std::fmt;
+
+use nalgebra::{Unit, Vector2, Vector3};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{lighting::color::Color, math::interval::Interval};
+
+use super::{camera::Camera, par_buffer::ParBuffer, scene::Scene};
+
+/// What the primary ray through a pixel hit.
+pub struct Pick {
+    pub object_id: usize,
+    pub geom: String,
+    pub material: &'static str,
+    pub point: Vector3<f64>,
+    pub normal: Unit<Vector3<f64>>,
+    pub uv: Vector2<f64>,
+    pub dist: f64,
+}
+
+impl fmt::Display for Pick {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "object   #{} {}", self.object_id, self.geom)?;
+        writeln!(f, "material {}", self.material)?;
+        writeln!(
+            f,
+            "point    ({:.4}, {:.4}, {:.4})",
+            self.point.x, self.point.y, self.point.z
+        )?;
+        writeln!(
+            f,
+            "normal   ({:.4}, {:.4}, {:.4})",
+            self.normal.x, self.normal.y, self.normal.z
+        )?;
+        writeln!(f, "uv       ({:.4}, {:.4})", self.uv.x, self.uv.y)?;
+        write!(f, "dist     {:.4}", self.dist)
+    }
+}
+
+/// Object id hit by the ray through the center of pixel (x, y), if any.
+fn object_at(camera: &Camera, scene: &Scene, x: usize, y: usize) -> Option<usize> {
+    let ray = camera.ray_through(x as f64, y as f64);
+    scene
+        .intersect(ray, Interval::new(0.0, f64::MAX))?
+        .object_id()
+}
+
+pub fn pick(camera: &Camera, scene: &Scene, x: usize, y: usize) -> Option<Pick> {
+    let ray = camera.ray_through(x as f64, y as f64);
+    let inter = scene.intersect(ray, Interval::new(0.0, f64::MAX))?;
+    let object_id = inter.object_id()?;
+    let geom = scene.describe_object(object_id)?;
+
+    Some(Pick {
+        object_id,
+        geom,
+        material: inter.material().name(),
+        point: inter.point(),
+        normal: inter.normal(),
+        uv: inter.uv(),
+        dist: inter.dist(),
+    })
+}
+
+/// Pixels on the silhouette of an object: covered by it, with a 4-neighbour that isn't.
+pub struct Outline {
+    rows: usize,
+    cols: usize,
+    edge: Vec<bool>, //stored row-major
+}
+
+impl Outline {
+    pub fn new(camera: &Camera, scene: &Scene, object_id: usize, rows: usize, cols: usize) -> Self {
+        let covered: Vec<bool> = (0..rows * cols)
+            .into_par_iter()
+            .map(|i| object_at(camera, scene, i % cols, i / cols) == Some(object_id))
+            .collect();
+
+        let is_covered = |x: i64, y: i64| {
+            0 <= x
+                && x < cols as i64
+                && 0 <= y
+                && y < rows as i64
+                && covered[y as usize * cols + x as usize]
+        };
+
+        let mut edge = vec![false; rows * cols];
+        for y in 0..rows as i64 {
+            for x in 0..cols as i64 {
+                if is_covered(x, y)
+                    && !(is_covered(x - 1, y)
+                        && is_covered(x + 1, y)
+                        && is_covered(x, y - 1)
+                        && is_covered(x, y + 1))
+                {
+                    edge[y as usize * cols + x as usize] = true;
+                }
+            }
+        }
+
+        Outline { rows, cols, edge }
+    }
+
+    pub fn draw_on(&self, buf: &mut ParBuffer, color: Color) {
+        assert!(buf.rows() == self.rows && buf.cols() == self.cols);
+        for y in 0..self.rows {
+            for x in 0..self.cols {
+                if self.edge[y * self.cols + x] {
+                    buf.set(x, y, color);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    sync::{
+        Arc,
+        mpsc::{Receiver, sync_channel},
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use super::{
+    accumulator::Accumulator, camera::Camera, checkpoint::Checkpoint, par_buffer::ParBuffer,
+    progress::CancelToken, renderer::Renderer, scene::Scene,
+};
+
+/// Progressive renderer for the interactive viewer. A background thread renders
+/// one-sample passes with rayon and hands them over a channel, so the thread
+/// owning the window only has to poll for new passes and blit.
+pub struct Preview {
+    renderer: Arc<Renderer>,
+    scene: Arc<Scene>,
+    camera: Camera,
+    accum: Accumulator,
+    seed: u64,
+    started: Instant,
+    passes_at_start: u64,
+
+    cancel: CancelToken,
+    passes: Option<Receiver<ParBuffer>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Preview {
+    pub fn start(renderer: Arc<Renderer>, scene: Arc<Scene>, camera: Camera) -> Self {
+        let accum = Accumulator::new(renderer.window_height(), renderer.window_width());
+        let seed = rand::random();
+        Self::from_accum(renderer, scene, camera, accum, seed)
+    }
+
+    /// Pick up a render from a checkpoint, which should already have been checked
+    /// against this scene and these settings (see `Checkpoint::resume`).
+    pub fn resume(
+        renderer: Arc<Renderer>,
+        scene: Arc<Scene>,
+        camera: Camera,
+        checkpoint: Checkpoint,
+    ) -> Self {
+        Self::from_accum(renderer, scene, camera, checkpoint.accum, checkpoint.seed)
+    }
+
+    fn from_accum(
+        renderer: Arc<Renderer>,
+        scene: Arc<Scene>,
+        camera: Camera,
+        accum: Accumulator,
+        seed: u64,
+    ) -> Self {
+        let mut preview = Preview {
+            renderer,
+            scene,
+            camera: camera.clone(),
+            accum,
+            seed,
+            started: Instant::now(),
+            passes_at_start: 0,
+            cancel: CancelToken::new(),
+            passes: None,
+            worker: None,
+        };
+        preview.spawn(camera);
+        preview
+    }
+
+    /// Snapshot of the render so far, to be saved and resumed from later.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint::new(
+            &self.scene,
+            &self.renderer,
+            &self.camera,
+            self.seed,
+            &self.accum,
+        )
+    }
+
+    fn spawn(&mut self, camera: Camera) {
+        // Only keep one finished pass in flight: the worker blocks until the viewer catches up.
+        let (tx, rx) = sync_channel(1);
+        let cancel = CancelToken::new();
+        let renderer = self.renderer.clone();
+        let scene = self.scene.clone();
+        let worker_cancel = cancel.clone();
+        // Pass n of the render always gets seed + n, however many times it's been resumed.
+        let mut pass_seed = self.seed.wrapping_add(self.accum.passes());
+
+        self.camera = camera.clone();
+        let worker = thread::spawn(move || {
+            while !worker_cancel.is_cancelled() {
+                let pass = renderer.render_pass(&camera, &scene, pass_seed, &worker_cancel);
+                pass_seed = pass_seed.wrapping_add(1);
+                if worker_cancel.is_cancelled() || tx.send(pass).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.cancel = cancel;
+        self.passes = Some(rx);
+        self.worker = Some(worker);
+        self.started = Instant::now();
+        self.passes_at_start = self.accum.passes();
+    }
+
+    /// Cancel the worker's current pass and stop listening to it.
+    fn halt(&mut self) -> Option<JoinHandle<()>> {
+        self.cancel.cancel();
+        // Dropping the receiver unblocks a worker waiting to send.
+        self.passes = None;
+        self.worker.take()
+    }
+
+    /// Throw away the accumulated passes and start over from a new viewpoint.
+    pub fn restart(&mut self, camera: Camera) {
+        // Don't wait for the old worker: it exits on its own once it notices the
+        // cancellation, and blocking here would make the viewer stutter while dragging.
+        self.halt();
+        self.accum.reset();
+        self.spawn(camera);
+    }
+
+    /// The current accumulated image.
+    pub fn snapshot(&self) -> ParBuffer {
+        self.accum.resolve()
+    }
+
+    /// Drain any finished passes into the accumulation buffer. Returns the
+    /// resolved image if anything new arrived.
+    pub fn poll(&mut self) -> Option<ParBuffer> {
+        let rx = self.passes.as_ref()?;
+        let mut updated = false;
+        while let Ok(pass) = rx.try_recv() {
+            self.accum.add_pass(&pass);
+            updated = true;
+        }
+        if updated {
+            Some(self.accum.resolve())
+        } else {
+            None
+        }
+    }
+
+    pub fn passes(&self) -> u64 {
+        self.accum.passes()
+    }
+
+    pub fn samples_per_sec(&self) -> f64 {
+        let px = (self.renderer.window_width() * self.renderer.window_height()) as f64;
+        let secs = self.started.elapsed().as_secs_f64();
+        if secs <= 0.0 {
+            return 0.0;
+        }
+        (self.accum.passes() - self.passes_at_start) as f64 * px / secs
+    }
+
+    pub fn title(&self) -> String {
+        format!(
+            "raytracer - pass {} - {:.2} Msamples/s",
+            self.passes(),
+            self.samples_per_sec() / 1e6
+        )
+    }
+}
+
+impl Drop for Preview {
+    fn drop(&mut self) {
+        if let Some(worker) = self.halt() {
+            worker.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+/// Shared flag for aborting a render from another thread. Cloning gives another
+/// handle to the same flag.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Reported each time a tile finishes.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f64 {
+        self.tiles_done as f64 / self.tiles_total as f64
+    }
+
+    /// Estimated time left, assuming the remaining tiles take as long as the finished ones on average.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.tiles_done == 0 {
+            return None;
+        }
+        let per_tile = self.elapsed.as_secs_f64() / self.tiles_done as f64;
+        let left = (self.tiles_total - self.tiles_done) as f64;
+        Some(Duration::from_secs_f64(per_tile * left))
+    }
+}
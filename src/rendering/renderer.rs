@@ -1,17 +1,31 @@
-use nalgebra::{Unit, Vector3};
-use rand::Rng;
-use rayon::iter::{
-    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+use std::{
+    hash::{Hash, Hasher},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Instant,
 };
 
-// use itertools::Itertools;
+use nalgebra::Vector3;
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+
 use crate::{
     geom::intersectable::{self, Intersectable},
     lighting::color::Color,
     math::{interval::Interval, onlinemean::OnlineMean, ray::Ray},
 };
 
-use super::{camera::Camera, par_buffer::ParBuffer, scene::Scene};
+use super::{
+    aov::{Aov, AovBuffers, AovPixel, AovSample},
+    camera::Camera,
+    denoise::Features,
+    par_buffer::ParBuffer,
+    progress::{CancelToken, Progress},
+    scene::Scene,
+    tiles::{self, TileOrder},
+};
 
 pub struct Renderer {
     //Metadata
@@ -24,6 +38,10 @@ pub struct Renderer {
     //adaptive AA data
     samples_per_batch: u64,
     conv_cutoff: f64,
+
+    //Tiling
+    tile_size: usize,
+    tile_order: TileOrder,
 }
 
 impl Renderer {
@@ -40,23 +58,125 @@ impl Renderer {
             window_height: window_height,
             samples_per_batch,
             conv_cutoff,
+            tile_size: 32,
+            tile_order: TileOrder::Spiral,
         }
     }
 
+    pub fn with_tiling(mut self, tile_size: usize, tile_order: TileOrder) -> Self {
+        assert!(tile_size > 0);
+        self.tile_size = tile_size;
+        self.tile_order = tile_order;
+        self
+    }
+
     fn sample_uv() -> (f64, f64) {
-        let mut rng = rand::rng();
+        Renderer::sample_uv_with(&mut rand::rng())
+    }
+
+    fn sample_uv_with(rng: &mut impl Rng) -> (f64, f64) {
         let du: f64 = rng.random::<f64>() - 0.5;
         let dv: f64 = rng.random::<f64>() - 0.5;
         (du, dv)
     }
 
     pub fn render(&self, camera: &Camera, scene: &Scene) -> ParBuffer {
-        let mut buffer = ParBuffer::new(self.window_height as usize, self.window_width as usize);
+        self.render_tiled(camera, scene, &|_| {}, &CancelToken::new())
+    }
 
+    /// Render tile by tile, calling `progress` as each tile finishes. If `cancel`
+    /// fires, returns early with whatever tiles were already done; the rest stay black.
+    pub fn render_tiled(
+        &self,
+        camera: &Camera,
+        scene: &Scene,
+        progress: &(dyn Fn(Progress) + Sync),
+        cancel: &CancelToken,
+    ) -> ParBuffer {
+        let tiles = tiles::tiles(
+            self.window_width,
+            self.window_height,
+            self.tile_size,
+            self.tile_order,
+        );
+        let buffer = Mutex::new(ParBuffer::new(self.window_height, self.window_width));
+
+        // Workers pull tiles off a shared counter rather than splitting the list up
+        // front, so tiles are started in the requested order.
+        let next = AtomicUsize::new(0);
+        let done = AtomicUsize::new(0);
+        let start = Instant::now();
+
+        rayon::scope(|s| {
+            for _ in 0..rayon::current_num_threads() {
+                s.spawn(|_| {
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        if idx >= tiles.len() || cancel.is_cancelled() {
+                            break;
+                        }
+                        let tile = tiles[idx];
+
+                        let mut colors = Vec::with_capacity(tile.width * tile.height);
+                        for (x, y) in tile.pixels() {
+                            if cancel.is_cancelled() {
+                                return;
+                            }
+                            colors.push(((x, y), self.render_px(camera, scene, x, y)));
+                        }
+
+                        let mut buffer = buffer.lock().unwrap();
+                        for ((x, y), c) in colors {
+                            buffer.set(x, y, c);
+                        }
+                        drop(buffer);
+
+                        let tiles_done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                        progress(Progress {
+                            tiles_done,
+                            tiles_total: tiles.len(),
+                            elapsed: start.elapsed(),
+                        });
+                    }
+                });
+            }
+        });
+
+        buffer.into_inner().unwrap()
+    }
+
+    /// A single progressive pass: one jittered sample per pixel, for accumulating into a preview.
+    /// The jitter is derived from `seed`, so a pass can be reproduced (e.g. when resuming).
+    /// Pixels left once `cancel` fires are left black, so the caller should drop the pass.
+    pub fn render_pass(
+        &self,
+        camera: &Camera,
+        scene: &Scene,
+        seed: u64,
+        cancel: &CancelToken,
+    ) -> ParBuffer {
+        let mut buffer = ParBuffer::new(self.window_height, self.window_width);
+        let cols = self.window_width as u64;
+
+        buffer.par_iter_mut().for_each(|((x_idx, y_idx), c)| {
+            if cancel.is_cancelled() {
+                return;
+            }
+            let px = y_idx as u64 * cols + x_idx as u64;
+            let mut rng = SmallRng::seed_from_u64(seed ^ px.wrapping_mul(0x9e3779b97f4a7c15));
+            let (du, dv) = Renderer::sample_uv_with(&mut rng);
+            let ray = camera.ray_through(x_idx as f64 + du, y_idx as f64 + dv);
+            *c = self.trace(ray, scene, self.recursion_depth);
+        });
         buffer
-            .par_iter_mut()
-            .for_each(|((x_idx, y_idx), c)| *c = self.render_px(camera, scene, x_idx, y_idx));
-        buffer
+    }
+
+    pub fn window_width(&self) -> usize {
+        self.window_width
+    }
+
+    pub fn window_height(&self) -> usize {
+        self.window_height
     }
 
     /// Adaptive rendering. Estimate the pixel color online, stop when it converges in L2 norm.
@@ -76,12 +196,140 @@ impl Renderer {
         Color::from_vec(estimator.mean())
     }
 
+    /// Like `render`, also filling in the requested AOVs.
+    pub fn render_aovs(
+        &self,
+        camera: &Camera,
+        scene: &Scene,
+        aovs: &[Aov],
+    ) -> (ParBuffer, AovBuffers) {
+        let (rows, cols) = (self.window_height, self.window_width);
+        let px: Vec<(Color, AovPixel)> = (0..rows * cols)
+            .into_par_iter()
+            .map(|i| self.render_px_aovs(camera, scene, i % cols, i / cols))
+            .collect();
+
+        let mut buffer = ParBuffer::new(rows, cols);
+        let mut aov_buffers = AovBuffers::new(rows, cols, aovs);
+        for (i, (c, a)) in px.into_iter().enumerate() {
+            buffer.set(i % cols, i / cols, c);
+            aov_buffers.set(i % cols, i / cols, &a);
+        }
+        (buffer, aov_buffers)
+    }
+
+    /// Guide images for the denoiser, from one sample through the center of each pixel.
+    pub fn render_features(&self, camera: &Camera, scene: &Scene) -> Features {
+        let (rows, cols) = (self.window_height, self.window_width);
+        let px: Vec<AovPixel> = (0..rows * cols)
+            .into_par_iter()
+            .map(|i| {
+                let ray = camera.ray_through((i % cols) as f64, (i / cols) as f64);
+                let mut px = AovPixel::new();
+                px.add_sample(&self.trace_aovs(ray, scene).1);
+                px
+            })
+            .collect();
+
+        let mut aovs = AovBuffers::new(rows, cols, &Features::AOVS);
+        for (i, a) in px.iter().enumerate() {
+            aovs.set(i % cols, i / cols, a);
+        }
+        Features::from_aovs(&aovs, rows, cols)
+    }
+
+    fn render_px_aovs(
+        &self,
+        camera: &Camera,
+        scene: &Scene,
+        x_idx: usize,
+        y_idx: usize,
+    ) -> (Color, AovPixel) {
+        let mut estimator = OnlineMean::new();
+        let mut aovs = AovPixel::new();
+
+        while estimator.convergence_delta() > self.conv_cutoff {
+            for _ in 0..self.samples_per_batch {
+                let (du, dv) = Renderer::sample_uv();
+                let ray = camera.ray_through(x_idx as f64 + du, y_idx as f64 + dv);
+
+                let (color, sample) = self.trace_aovs(ray, scene);
+                estimator.add_sample(color);
+                aovs.add_sample(&sample);
+            }
+        }
+
+        (Color::from_vec(estimator.mean()), aovs)
+    }
+
+    /// Trace a camera ray, recording the first hit's AOVs and splitting the light
+    /// arriving there into its direct and indirect parts.
+    fn trace_aovs(&self, ray: Ray, scene: &Scene) -> (Vector3<f64>, AovSample) {
+        let mut sample = AovSample::default();
+
+        let Some(inter) = scene.intersect(ray, Interval::new(0.0, f64::MAX)) else {
+            let bg = scene.background_color();
+            sample.set_scalar(Aov::Depth, f64::INFINITY);
+            sample.set_color(Aov::Emission, bg);
+            return (bg.inner_vec(), sample);
+        };
+
+        let emit = inter.material().emit(&inter.tex_context(&ray));
+        sample.set_scalar(Aov::Depth, inter.dist());
+        sample.set(Aov::Position, inter.point());
+        sample.set(Aov::Normal, *inter.normal());
+        sample.set(Aov::Uv, Vector3::new(inter.uv().x, inter.uv().y, 0.0));
+        sample.set_scalar(Aov::MaterialId, scene.material_id(inter.material()));
+        sample.set_scalar(Aov::ObjectId, inter.object_id().unwrap_or(0) as f64);
+        sample.set_color(Aov::Emission, emit);
+
+        let Some(scatter) = inter.material().scatter(&ray, &inter) else {
+            return (emit.inner_vec(), sample);
+        };
+        let albedo = scatter.color().inner_vec();
+        sample.set(Aov::Albedo, albedo);
+
+        // What the bounced ray sees: the light emitted where it lands is direct
+        // lighting for the first hit, anything that bounced again is indirect.
+        let (seen_emit, seen_total) = if self.recursion_depth <= 1 {
+            (Vector3::zeros(), Vector3::zeros())
+        } else {
+            match scene.intersect(*scatter.ray(), Interval::new(0.0, f64::MAX)) {
+                None => {
+                    let bg = scene.background_color().inner_vec();
+                    (bg, bg)
+                }
+                Some(next) => {
+                    let e = next
+                        .material()
+                        .emit(&next.tex_context(scatter.ray()))
+                        .inner_vec();
+                    let total = match next.material().scatter(scatter.ray(), &next) {
+                        None => e,
+                        Some(s) => {
+                            let rest = self.trace(*s.ray(), scene, self.recursion_depth - 2);
+                            e + rest.inner_vec().component_mul(&s.color().inner_vec())
+                        }
+                    };
+                    (e, total)
+                }
+            }
+        };
+
+        let direct = seen_emit.component_mul(&albedo);
+        let indirect = (seen_total - seen_emit).component_mul(&albedo);
+        sample.set(Aov::Direct, direct);
+        sample.set(Aov::Indirect, indirect);
+
+        (emit.inner_vec() + direct + indirect, sample)
+    }
+
     fn trace(&self, ray: Ray, scene: &Scene, depth: u64) -> Color {
-        if depth <= 0 {
+        if depth == 0 {
             Color::black()
         } else {
-            if let Some(inter) = scene.bvh().intersect(ray, Interval::new(0.001, f64::MAX)) {
-                let emit = inter.material().emit(&inter.uv());
+            if let Some(inter) = scene.intersect(ray, Interval::new(0.0, f64::MAX)) {
+                let emit = inter.material().emit(&inter.tex_context(&ray));
                 match inter.material().scatter(&ray, &inter) {
                     None => emit,
                     Some(scatter) => {
@@ -95,3 +343,122 @@ impl Renderer {
         }
     }
 }
+
+// Only what changes the image a progressive render converges to, so batching
+// and tiling can change between a checkpoint and its resume
+impl Hash for Renderer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.recursion_depth.hash(state);
+        self.window_width.hash(state);
+        self.window_height.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use nalgebra::Vector3;
+
+    use crate::{
+        geom::sphere::Sphere,
+        lighting::{color::Color, lambertian::Lambertian, texture::solidcolor::SolidColor},
+        rendering::{
+            camera::Camera,
+            progress::CancelToken,
+            scene::Scene,
+            tiles::{self, TileOrder},
+        },
+    };
+
+    use super::Renderer;
+
+    const ORDERS: [TileOrder; 3] = [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert];
+
+    #[test]
+    fn every_order_covers_each_pixel_once() {
+        for (width, height, size) in [(100, 37, 8), (7, 13, 3), (33, 65, 16), (1, 1, 4), (5, 5, 5)]
+        {
+            for order in ORDERS {
+                let tiles = tiles::tiles(width, height, size, order);
+                assert_eq!(
+                    tiles.len(),
+                    width.div_ceil(size) * height.div_ceil(size),
+                    "{:?} {}x{}/{}",
+                    order,
+                    width,
+                    height,
+                    size
+                );
+                let mut hits = vec![0; width * height];
+                for (x, y) in tiles.iter().flat_map(|t| t.pixels()) {
+                    assert!(x < width && y < height);
+                    hits[y * width + x] += 1;
+                }
+                assert!(
+                    hits.iter().all(|&n| n == 1),
+                    "{:?} {}x{}/{}",
+                    order,
+                    width,
+                    height,
+                    size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cancelling_keeps_finished_tiles() {
+        let (rows, cols) = (30, 40);
+        let camera = Camera::new(
+            cols,
+            rows,
+            Vector3::new(0.0, 0.0, 5.0),
+            -Vector3::z_axis(),
+            Vector3::y_axis(),
+            1.0,
+            60.0,
+        );
+        // Nothing in view, so every rendered pixel is the background
+        let mat = Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::white()))));
+        let background = Color::new(0.5, 0.5, 0.5);
+        let scene = Scene::new(
+            Sphere::new(Vector3::new(0.0, 0.0, 50.0), 1.0, mat),
+            background,
+        );
+        let renderer = Renderer::new(4, cols, rows, 4, 0.01).with_tiling(4, TileOrder::Spiral);
+
+        let cancel = CancelToken::new();
+        let finished = AtomicUsize::new(0);
+        let buf = renderer.render_tiled(
+            &camera,
+            &scene,
+            &|_| {
+                finished.fetch_add(1, Ordering::Relaxed);
+                cancel.cancel();
+            },
+            &cancel,
+        );
+
+        let tiles = tiles::tiles(cols, rows, 4, TileOrder::Spiral);
+        let mut full = 0;
+        for tile in &tiles {
+            let colors: Vec<_> = tile
+                .pixels()
+                .map(|(x, y)| buf.get(x, y).inner_vec())
+                .collect();
+            if colors.iter().all(|c| *c == background.inner_vec()) {
+                full += 1;
+            } else {
+                // A tile that was cut short isn't written at all
+                assert!(colors.iter().all(|c| *c == Vector3::zeros()));
+            }
+        }
+        assert_eq!(full, finished.load(Ordering::Relaxed));
+        assert!(full >= 1);
+        assert!(full < tiles.len());
+    }
+}
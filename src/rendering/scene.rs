@@ -1,172 +1,726 @@
-use std::{collections::HashMap, fs::File, sync::Arc};
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    path::Path,
+    sync::Arc,
+};
+
+use nalgebra::{Unit, Vector3};
 
 use crate::{
     geom::{
-        Geom, Geomable, aabb::AABB, bvh::BVH, cube::Cube, intersectable::Intersectable,
-        intersection::Intersection, quad::Quad, sphere::Sphere, translation::Translation,
+        Geom, Geomable,
+        bbox::Bbox,
+        bvh::BVH,
+        cone::Cone,
+        csg::Csg,
+        cube::{Cube, Face},
+        cylinder::Cylinder,
+        disk::Disk,
+        intersectable::Intersectable,
+        intersection::Intersection,
+        plane::Plane,
+        quad::Quad,
+        sphere::Sphere,
+        torus::Torus,
+        translation::Translation,
+        trimesh::TriMesh,
     },
     lighting::{
-        color::Color,
+        color::{Color, ColorSpace},
         diffuselight::DiffuseLight,
         lambertian::Lambertian,
         material::Material,
         metal::Metal,
         texture::{
-            Texture, checkerboard::Checkerboard, image::Image, scaletex::ScaleTex,
-            solidcolor::SolidColor,
+            Texture, checker3d::Checker3d, checkerboard::Checkerboard, combine::Combine,
+            granite::Granite, image::Image, invert::Invert, marble::Marble, mix::Mix, noise::Noise,
+            ramp::Ramp, remap::Remap, scaletex::ScaleTex, solidcolor::SolidColor,
+            uvtransform::UvTransform, vertexcolor::VertexColor, wood::Wood,
         },
     },
     math::{interval::Interval, ray::Ray},
 };
 
-use super::scenedesc::{GeomDesc, MaterialDesc, SceneDesc, TextureDesc};
+use super::checkpoint::Fnv;
+use super::gltf;
+use super::scenedesc::{GeomDesc, MaterialDesc, SceneDesc, SceneDescError, TextureDesc};
 
 pub struct Scene {
     bvh: BVH<Geom>,
+    // First prim id of each source object, then the prim count: an object's
+    // prims are consecutive
+    object_starts: Vec<usize>,
+    // Unbounded, so they can't go in the BVH
+    planes: Vec<Plane>,
     background_color: Color,
+    // Of the file the scene came from, if any
+    source_hash: Option<u64>,
+    // Kept alive so their addresses can't be reused by another material
+    materials: Vec<Arc<dyn Material>>,
+    // Material address to id
+    material_ids: HashMap<usize, usize>,
+}
+
+fn material_addr(mat: &dyn Material) -> usize {
+    mat as *const dyn Material as *const () as usize
 }
 
 impl Scene {
+    /// A scene where every geom is an object of its own.
     pub fn new(geoms: impl Geomable, background_color: Color) -> Self {
-        let geoms = geoms.into_geoms().collect();
+        Scene::from_objects(geoms.into_geoms().map(|g| vec![g]), background_color)
+    }
+
+    /// A scene of source objects (a mesh, a scene file entry, ...), each made of
+    /// any number of geoms. Picking and the object id AOV work per object.
+    pub fn from_objects(
+        objects: impl IntoIterator<Item = Vec<Geom>>,
+        background_color: Color,
+    ) -> Self {
+        let mut geoms = Vec::new();
+        let mut object_starts = Vec::new();
+        for object in objects {
+            if !object.is_empty() {
+                object_starts.push(geoms.len());
+                geoms.extend(object);
+            }
+        }
+        object_starts.push(geoms.len());
         Scene {
             bvh: BVH::construct(geoms),
+            object_starts,
+            planes: Vec::new(),
             background_color,
+            source_hash: None,
+            materials: Vec::new(),
+            material_ids: HashMap::new(),
+        }
+    }
+
+    /// Planes get the object ids after the BVH's objects, in order.
+    pub fn with_planes(mut self, planes: Vec<Plane>) -> Self {
+        self.planes = planes;
+        self
+    }
+
+    pub fn with_source_hash(mut self, source_hash: u64) -> Self {
+        self.source_hash = Some(source_hash);
+        self
+    }
+
+    /// Give materials ids 1, 2, ... in the order given, skipping ones already
+    /// numbered. The order comes from the scene description, so ids stay the same
+    /// from run to run.
+    pub fn with_materials(mut self, mats: impl IntoIterator<Item = Arc<dyn Material>>) -> Self {
+        for mat in mats {
+            let addr = material_addr(mat.as_ref());
+            if !self.material_ids.contains_key(&addr) {
+                self.materials.push(mat);
+                self.material_ids.insert(addr, self.materials.len());
+            }
+        }
+        self
+    }
+
+    /// Id for the material AOV; 0 for materials that weren't given to `with_materials`.
+    pub fn material_id(&self, mat: &dyn Material) -> f64 {
+        self.material_ids
+            .get(&material_addr(mat))
+            .copied()
+            .unwrap_or(0) as f64
+    }
+
+    /// The closest hit along `ray`, from the BVH or any of the planes.
+    pub fn intersect<'r>(&'r self, ray: Ray, i: Interval) -> Option<Intersection<'r>> {
+        let mut closest = self.bvh.intersect(ray, i);
+        if let Some(hit) = &mut closest
+            && let Some(prim) = hit.prim_id()
+        {
+            hit.set_object_id(self.object_of(prim));
+        }
+        for (k, plane) in self.planes.iter().enumerate() {
+            let max = closest.as_ref().map_or(i.max, |c| c.dist());
+            if let Some(mut hit) = plane.intersect(ray, Interval::new(i.min, max)) {
+                hit.set_object_id(self.object_count() + k);
+                closest = Some(hit);
+            }
         }
+        closest
     }
 
     pub fn background_color(&self) -> Color {
         self.background_color
     }
 
-    pub fn bvh(&self) -> &BVH<Geom> {
-        &self.bvh
+    // Objects in the BVH; planes are numbered after them
+    fn object_count(&self) -> usize {
+        self.object_starts.len() - 1
+    }
+
+    /// The source object a BVH primitive belongs to.
+    fn object_of(&self, prim_id: usize) -> usize {
+        self.object_starts
+            .partition_point(|&start| start <= prim_id)
+            - 1
+    }
+
+    /// What an object is made of, e.g. `mesh triangle (5000 prims)`.
+    pub fn describe_object(&self, object_id: usize) -> Option<String> {
+        if let Some(k) = object_id.checked_sub(self.object_count()) {
+            return self.planes.get(k).map(|_| String::from("plane"));
+        }
+        let start = *self.object_starts.get(object_id)?;
+        let end = *self.object_starts.get(object_id + 1)?;
+        let first = self.bvh.prim(start)?.describe();
+        Some(if end - start > 1 {
+            format!("{} ({} prims)", first, end - start)
+        } else {
+            first
+        })
+    }
+
+    /// Hash of the scene's contents, for telling whether a checkpoint was rendered
+    /// from it. Materials and textures only show up through the source hash, so
+    /// hand-built scenes are compared by geometry alone.
+    pub fn fingerprint(&self) -> u64 {
+        let mut h = Fnv::new();
+        self.source_hash.hash(&mut h);
+        self.background_color
+            .inner_vec()
+            .map(f64::to_bits)
+            .hash(&mut h);
+        self.bvh.for_each(|id, geom| {
+            id.hash(&mut h);
+            geom.describe().hash(&mut h);
+            let bb = geom.bbox();
+            bb.min().map(f64::to_bits).hash(&mut h);
+            bb.max().map(f64::to_bits).hash(&mut h);
+        });
+        for plane in &self.planes {
+            plane.point().map(f64::to_bits).hash(&mut h);
+            plane.normal().map(f64::to_bits).hash(&mut h);
+        }
+        h.finish()
+    }
+
+    /// Advance to the next frame of an animation: `f` moves each geom, the BVH
+    /// is refit around the new positions and rebuilt if it has degraded too far.
+    pub fn animate<F>(self, f: F) -> Self
+    where
+        F: FnMut(&mut Geom),
+    {
+        let mut bvh = self.bvh;
+        bvh.update(f);
+        Scene {
+            bvh: bvh.rebuild_if_degraded(),
+            object_starts: self.object_starts,
+            planes: self.planes,
+            background_color: self.background_color,
+            source_hash: self.source_hash,
+            materials: self.materials,
+            material_ids: self.material_ids,
+        }
+    }
+
+    /// Move every translation by its velocity, one frame on.
+    pub fn next_frame(self) -> Self {
+        self.animate(step)
+    }
+}
+
+fn step(g: &mut Geom) {
+    if let Geom::Trans(t) = g {
+        t.set_trans(t.trans() + t.velocity());
+        step(t.inner_mut());
     }
 }
 
-// fn construct_geom(gd: &GeomDesc, mat_map: &HashMap<String, Arc<dyn Material>>) -> Arc<dyn Intersectable> {
-//     match gd {
-//         GeomDesc::Cube { c, r, mat } => {
-//             let mat = mat_map
-//                 .get(mat)
-//                 .expect(format!("material {} to be defined", mat).as_str())
-//                 .clone();
-//             Arc::new(Cube::new(*c, *r, mat))
-//         }
-//         // GeomDesc::Plane {
-//         //     center,
-//         //     normal,
-//         //     u_hat,
-//         //     v_hat,
-//         //     mat,
-//         // } => {
-//         //     let mat = mat_map
-//         //         .get(mat)
-//         //         .expect(format!("material {} to be defined", mat).as_str())
-//         //         .clone();
-//         //     Arc::new(Plane::new(
-//         //         *center,
-//         //         Unit::new_normalize(*normal),
-//         //         *u_hat,
-//         //         *v_hat,
-//         //         mat,
-//         //     ))
-//         // }
-//         GeomDesc::Quad { q, u, v, mat } => {
-//             let mat = mat_map
-//                 .get(mat)
-//                 .expect(format!("material {} to be defined", mat).as_str())
-//                 .clone();
-//             Arc::new(Quad::new(*q, *u, *v, mat))
-//         }
-//         GeomDesc::Sphere { c, r, mat } => {
-//             let mat = mat_map
-//                 .get(mat)
-//                 .expect(format!("material {} to be defined", mat).as_str())
-//                 .clone();
-//             Arc::new(Sphere::new(*c, *r, mat))
-//         }
-//         GeomDesc::Translation { by, gd } => {
-//             let geom = construct_geom(gd, mat_map);
-//             Arc::new(Translation::new(*by, geom.into()))
-//         }
-//     }
-// }
-
-// impl<'a> From<&'a SceneDesc> for Scene {
-//     fn from(sd: &'a SceneDesc) -> Self {
-//         let mut tex_map: HashMap<String, Arc<dyn Texture>> = HashMap::new();
-
-//         for (name, desc) in &sd.textures {
-//             let tex: Arc<dyn Texture> = match desc {
-//                 TextureDesc::Solid { albedo } => Arc::new(SolidColor::new(*albedo)),
-//                 TextureDesc::Checkerboard {
-//                     tex1,
-//                     tex2,
-//                     checker_size,
-//                 } => {
-//                     let tex1 = tex_map
-//                         .get(tex1)
-//                         .expect(format!("{} to be defined before {}", tex1, name).as_str())
-//                         .clone();
-//                     let tex2 = tex_map
-//                         .get(tex2)
-//                         .expect(format!("{} to be defined before {}", tex2, name).as_str())
-//                         .clone();
-//                     Arc::new(Checkerboard::new(*checker_size, tex1, tex2))
-//                 }
-//                 TextureDesc::Image { fname } => Arc::new(Image::from_fname(fname.as_str())),
-//                 TextureDesc::ScaleTex {
-//                     scale_u,
-//                     scale_v,
-//                     tex,
-//                 } => {
-//                     let tex = tex_map
-//                         .get(tex)
-//                         .expect(format!("{} to be defined before {}", tex, name).as_str())
-//                         .clone();
-//                     Arc::new(ScaleTex::new(*scale_u, *scale_v, tex))
-//                 }
-//             };
-
-//             tex_map.insert(name.clone(), tex);
-//         }
-
-//         let mut mat_map: HashMap<String, Arc<dyn Material>> = HashMap::new();
-
-//         for (name, desc) in &sd.materials {
-//             let mat: Arc<dyn Material> = match desc {
-//                 MaterialDesc::DiffuseLight { tex } => {
-//                     let tex = tex_map
-//                         .get(tex)
-//                         .expect(format!("{} to be defined before {}", tex, name).as_str())
-//                         .clone();
-//                     Arc::new(DiffuseLight::new(tex))
-//                 }
-//                 MaterialDesc::Lambertian { tex } => {
-//                     let tex = tex_map
-//                         .get(tex)
-//                         .expect(format!("{} to be defined before {}", tex, name).as_str())
-//                         .clone();
-//                     Arc::new(Lambertian::new(tex))
-//                 }
-//                 MaterialDesc::Metal { albedo, fuzz } => Arc::new(Metal::new(*albedo, *fuzz)),
-//             };
-
-//             mat_map.insert(name.clone(), mat);
-//         }
-
-//         let mut geoms: Vec<Arc<dyn Intersectable>> = Vec::with_capacity(sd.geoms.len());
-
-//         for geom_desc in &sd.geoms {
-//             geoms.push(construct_geom(geom_desc, &mat_map));
-//         }
-
-//         let geoms = BVH::construct(geoms);
-
-//         Scene {
-//             geoms: geoms,
-//             background_color: sd.background_color,
-//         }
-//     }
-// }
+fn lookup<T: ?Sized>(
+    map: &HashMap<String, Arc<T>>,
+    name: &str,
+    user: &str,
+) -> Result<Arc<T>, SceneDescError> {
+    map.get(name)
+        .cloned()
+        .ok_or_else(|| SceneDescError::Undefined {
+            name: name.to_string(),
+            user: user.to_string(),
+        })
+}
+
+/// `loaded` collects the materials that came from mesh files, in load order.
+fn construct_geom(
+    gd: &GeomDesc,
+    mat_map: &HashMap<String, Arc<dyn Material>>,
+    loaded: &mut Vec<Arc<dyn Material>>,
+) -> Result<Vec<Geom>, SceneDescError> {
+    let geoms = match gd {
+        GeomDesc::Cube {
+            min,
+            max,
+            mat,
+            face_mats,
+        } => {
+            let mut cube = Cube::from_corners(*min, *max, lookup(mat_map, mat, "cube")?);
+            for (face, mat) in Face::ALL.into_iter().zip(face_mats) {
+                if let Some(mat) = mat {
+                    cube = cube.with_face_mat(face, lookup(mat_map, mat, "cube")?);
+                }
+            }
+            cube.into_geoms().collect()
+        }
+        GeomDesc::Quad { q, u, v, mat } => {
+            let mat = lookup(mat_map, mat, "quad")?;
+            Quad::new(*q, *u, *v, mat).into_geoms().collect()
+        }
+        GeomDesc::Sphere { c, r, mat } => {
+            let mat = lookup(mat_map, mat, "sphere")?;
+            Sphere::new(*c, *r, mat).into_geoms().collect()
+        }
+        GeomDesc::Disk {
+            c,
+            normal,
+            r,
+            inner_r,
+            mat,
+        } => {
+            let mat = lookup(mat_map, mat, "disk")?;
+            Disk::new(*c, Unit::new_normalize(*normal), *r, mat)
+                .with_inner_radius(*inner_r)
+                .into_geoms()
+                .collect()
+        }
+        GeomDesc::Cylinder {
+            base,
+            axis,
+            r,
+            capped,
+            mat,
+        } => {
+            let mat = lookup(mat_map, mat, "cylinder")?;
+            Cylinder::new(*base, *axis, *r, mat)
+                .with_caps(*capped)
+                .into_geoms()
+                .collect()
+        }
+        GeomDesc::Cone {
+            base,
+            axis,
+            r,
+            capped,
+            mat,
+        } => {
+            let mat = lookup(mat_map, mat, "cone")?;
+            Cone::new(*base, *axis, *r, mat)
+                .with_caps(*capped)
+                .into_geoms()
+                .collect()
+        }
+        GeomDesc::Torus {
+            c,
+            axis,
+            major_r,
+            minor_r,
+            mat,
+        } => {
+            let mat = lookup(mat_map, mat, "torus")?;
+            Torus::new(*c, Unit::new_normalize(*axis), *major_r, *minor_r, mat)
+                .into_geoms()
+                .collect()
+        }
+        GeomDesc::Plane { .. } => {
+            return Err(SceneDescError::Invalid(String::from(
+                "planes can only be at the top level of a scene",
+            )));
+        }
+        GeomDesc::Csg { op, a, b } => {
+            let a = construct_geom(a, mat_map, loaded)?;
+            let b = construct_geom(b, mat_map, loaded)?;
+            Csg::new(*op, a, b).into_geoms().collect()
+        }
+        GeomDesc::Translation { by, velocity, gd } => {
+            let inner = construct_geom(gd, mat_map, loaded)?;
+            Translation::new(*by, inner)
+                .with_velocity(*velocity)
+                .into_geoms()
+                .collect()
+        }
+        GeomDesc::Gltf { fname } => {
+            let g = load_gltf(fname)?;
+            loaded.extend(g.materials);
+            g.objects.into_iter().flatten().collect()
+        }
+        GeomDesc::Mesh {
+            fname,
+            mat,
+            crease_angle,
+            f32,
+        } => {
+            let mat = lookup(mat_map, mat, fname)?;
+            let mesh = TriMesh::from_path(fname, mat, *crease_angle).map_err(|error| {
+                SceneDescError::Mesh {
+                    fname: fname.clone(),
+                    error,
+                }
+            })?;
+            let mesh = if *f32 { mesh.into_f32() } else { mesh };
+            loaded.extend(mesh.materials().cloned());
+            mesh.into_geoms().collect()
+        }
+    };
+    Ok(geoms)
+}
+
+fn load_gltf(fname: &str) -> Result<gltf::GltfScene, SceneDescError> {
+    gltf::load(fname).map_err(|error| SceneDescError::Gltf {
+        fname: fname.to_string(),
+        error,
+    })
+}
+
+fn construct_texture(
+    name: &str,
+    desc: &TextureDesc,
+    tex_map: &HashMap<String, Arc<dyn Texture>>,
+) -> Result<Arc<dyn Texture>, SceneDescError> {
+    let tex: Arc<dyn Texture> = match desc {
+        TextureDesc::Solid { albedo } => Arc::new(SolidColor::new(*albedo)),
+        TextureDesc::Checkerboard {
+            tex1,
+            tex2,
+            checker_size,
+        } => {
+            let tex1 = lookup(tex_map, tex1, name)?;
+            let tex2 = lookup(tex_map, tex2, name)?;
+            Arc::new(Checkerboard::new(*checker_size, tex1, tex2))
+        }
+        TextureDesc::Checker3d {
+            tex1,
+            tex2,
+            checker_size,
+        } => {
+            let tex1 = lookup(tex_map, tex1, name)?;
+            let tex2 = lookup(tex_map, tex2, name)?;
+            Arc::new(Checker3d::new(*checker_size, tex1, tex2))
+        }
+        TextureDesc::Image {
+            fname,
+            color_space,
+            wrap,
+            filter,
+            flip_u,
+            flip_v,
+        } => {
+            let space = color_space.unwrap_or(ColorSpace::from_path(Path::new(fname)));
+            let image =
+                Image::open(Path::new(fname), space).map_err(|error| SceneDescError::Image {
+                    fname: fname.clone(),
+                    error,
+                })?;
+            Arc::new(
+                image
+                    .with_wrap(*wrap)
+                    .with_filter(*filter)
+                    .with_flip(*flip_u, *flip_v),
+            )
+        }
+        TextureDesc::ScaleTex {
+            scale_u,
+            scale_v,
+            tex,
+        } => {
+            let tex = lookup(tex_map, tex, name)?;
+            Arc::new(ScaleTex::new(*scale_u, *scale_v, tex))
+        }
+        TextureDesc::Mix {
+            tex1,
+            tex2,
+            mask,
+            factor,
+        } => {
+            let tex1 = lookup(tex_map, tex1, name)?;
+            let tex2 = lookup(tex_map, tex2, name)?;
+            let mask: Arc<dyn Texture> = match mask {
+                Some(mask) => lookup(tex_map, mask, name)?,
+                None => {
+                    let mut c = Color::from_vec(Vector3::repeat(*factor));
+                    c.clamp();
+                    Arc::new(SolidColor::new(c))
+                }
+            };
+            Arc::new(Mix::new(tex1, tex2, mask))
+        }
+        TextureDesc::Combine { op, tex1, tex2 } => {
+            let tex1 = lookup(tex_map, tex1, name)?;
+            let tex2 = lookup(tex_map, tex2, name)?;
+            Arc::new(Combine::new(*op, tex1, tex2))
+        }
+        TextureDesc::Ramp { tex, stops } => {
+            Arc::new(Ramp::new(lookup(tex_map, tex, name)?, stops.clone()))
+        }
+        TextureDesc::Remap { tex, from, to } => {
+            Arc::new(Remap::new(lookup(tex_map, tex, name)?, *from, *to))
+        }
+        TextureDesc::Invert { tex } => Arc::new(Invert::new(lookup(tex_map, tex, name)?)),
+        TextureDesc::VertexColor { fallback } => {
+            let fallback: Arc<dyn Texture> = match fallback {
+                Some(fallback) => lookup(tex_map, fallback, name)?,
+                None => Arc::new(SolidColor::new(Color::new(0.8, 0.8, 0.8))),
+            };
+            Arc::new(VertexColor::new(fallback))
+        }
+        TextureDesc::UvTransform {
+            tex,
+            tile,
+            rotation,
+            offset,
+        } => {
+            let tex = lookup(tex_map, tex, name)?;
+            Arc::new(UvTransform::new(*tile, *rotation, *offset, tex))
+        }
+        TextureDesc::Noise { seed, scale, kind } => Arc::new(Noise::new(*seed, *scale, *kind)),
+        TextureDesc::Marble {
+            seed,
+            scale,
+            turbulence,
+            base,
+            vein,
+        } => Arc::new(Marble::new(*seed, *scale, *turbulence, *base, *vein)),
+        TextureDesc::Wood {
+            seed,
+            scale,
+            rings,
+            light,
+            dark,
+        } => Arc::new(Wood::new(*seed, *scale, *rings, *light, *dark)),
+        TextureDesc::Granite {
+            seed,
+            scale,
+            base,
+            grain,
+            fleck,
+        } => Arc::new(Granite::new(*seed, *scale, *base, *grain, *fleck)),
+    };
+    Ok(tex)
+}
+
+impl<'a> TryFrom<&'a SceneDesc> for Scene {
+    type Error = SceneDescError;
+
+    fn try_from(sd: &'a SceneDesc) -> Result<Self, SceneDescError> {
+        let mut tex_map: HashMap<String, Arc<dyn Texture>> = HashMap::new();
+
+        for (name, desc) in &sd.textures {
+            let tex = construct_texture(name, desc, &tex_map)?;
+            tex_map.insert(name.clone(), tex);
+        }
+
+        let mut mat_map: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+        for (name, desc) in &sd.materials {
+            let mat: Arc<dyn Material> = match desc {
+                MaterialDesc::DiffuseLight { tex } => {
+                    Arc::new(DiffuseLight::new(lookup(&tex_map, tex, name)?))
+                }
+                MaterialDesc::Lambertian { tex } => {
+                    Arc::new(Lambertian::new(lookup(&tex_map, tex, name)?))
+                }
+                MaterialDesc::Metal { albedo, fuzz } => Arc::new(Metal::new(*albedo, *fuzz)),
+            };
+
+            mat_map.insert(name.clone(), mat);
+        }
+
+        let mut planes = Vec::new();
+        let mut objects = Vec::new();
+        let mut loaded = Vec::new();
+        for gd in &sd.geoms {
+            match gd {
+                GeomDesc::Plane {
+                    point,
+                    normal,
+                    uv_scale,
+                    mat,
+                } => {
+                    let mat = lookup(&mat_map, mat, "plane")?;
+                    planes.push(
+                        Plane::new(*point, Unit::new_normalize(*normal), mat)
+                            .with_uv_scale(*uv_scale),
+                    );
+                }
+                // A glTF file brings its own objects
+                GeomDesc::Gltf { fname } => {
+                    let g = load_gltf(fname)?;
+                    loaded.extend(g.materials);
+                    objects.extend(g.objects);
+                }
+                _ => objects.push(construct_geom(gd, &mat_map, &mut loaded)?),
+            }
+        }
+
+        // The scene's own materials first, in the order they're described
+        let described = sd.materials.iter().map(|(name, _)| mat_map[name].clone());
+        Ok(Scene::from_objects(objects, sd.background_color)
+            .with_planes(planes)
+            .with_source_hash(sd.source_hash)
+            .with_materials(described.chain(loaded)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::Vector3;
+    use serde_json::{Value, json};
+
+    use crate::{
+        geom::{Geomable, plane::Plane, quad::Quad, sphere::Sphere},
+        lighting::{
+            color::Color, lambertian::Lambertian, material::Material,
+            texture::solidcolor::SolidColor,
+        },
+        math::{interval::Interval, ray::Ray},
+        rendering::scenedesc::{SceneDesc, SceneDescError},
+    };
+
+    use super::Scene;
+
+    fn build(textures: Value, geoms: Value) -> Result<Scene, SceneDescError> {
+        let sd = SceneDesc::try_from(json!({
+            "background_color": [0.0, 0.0, 0.0],
+            "textures": textures,
+            "materials": [{ "type": "lambert", "name": "white", "tex": "white" }],
+            "geoms": geoms,
+        }))
+        .unwrap();
+        Scene::try_from(&sd)
+    }
+
+    #[test]
+    fn bad_references_and_files_are_errors() {
+        let white = json!({ "type": "solid", "name": "white", "albedo": [1.0, 1.0, 1.0] });
+        let sphere =
+            |mat: &str| json!({ "type": "sphere", "c": [0.0, 0.0, 0.0], "r": 1.0, "mat": mat });
+
+        assert!(build(json!([white]), json!([sphere("white")])).is_ok());
+        assert!(matches!(
+            build(json!([]), json!([sphere("white")])),
+            Err(SceneDescError::Undefined { .. })
+        ));
+        assert!(matches!(
+            build(json!([white]), json!([sphere("chrome")])),
+            Err(SceneDescError::Undefined { .. })
+        ));
+
+        let missing = |ext: &str| format!("no_such_file_{}.{}", std::process::id(), ext);
+        let mesh = json!({ "type": "mesh", "fname": missing("obj"), "mat": "white" });
+        assert!(matches!(
+            build(json!([white]), json!([mesh])),
+            Err(SceneDescError::Mesh { .. })
+        ));
+        let gltf = json!({ "type": "gltf", "fname": missing("gltf") });
+        assert!(matches!(
+            build(json!([white]), json!([gltf])),
+            Err(SceneDescError::Gltf { .. })
+        ));
+        let image = json!({ "type": "image", "name": "white", "fname": missing("png") });
+        assert!(matches!(
+            build(json!([image]), json!([])),
+            Err(SceneDescError::Image { .. })
+        ));
+    }
+
+    #[test]
+    fn hits_report_their_source_object() {
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::new(Arc::new(SolidColor::new(
+            Color::new(0.5, 0.5, 0.5),
+        ))));
+        let quad = |x: f64| {
+            Quad::new(
+                Vector3::new(x, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                mat.clone(),
+            )
+        };
+        let sphere = Sphere::new(Vector3::new(5.0, 0.5, 0.0), 0.5, mat.clone());
+        let scene = Scene::from_objects(
+            [
+                sphere.into_geoms().collect(),
+                [quad(0.0), quad(1.0)].into_geoms().collect(),
+            ],
+            Color::black(),
+        )
+        .with_planes(vec![Plane::new(
+            Vector3::new(0.0, 0.0, -1.0),
+            Vector3::z_axis(),
+            mat.clone(),
+        )]);
+
+        let id_at = |x: f64| {
+            let ray = Ray::new(Vector3::new(x, 0.5, 5.0), -Vector3::z_axis());
+            scene
+                .intersect(ray, Interval::new(0.0, f64::MAX))
+                .and_then(|h| h.object_id())
+        };
+        assert_eq!(id_at(5.0), Some(0));
+        assert_eq!(id_at(0.5), Some(1));
+        assert_eq!(id_at(1.5), Some(1));
+        assert_eq!(scene.describe_object(1).as_deref(), Some("quad (2 prims)"));
+        // Planes come after the BVH's objects
+        assert_eq!(id_at(10.0), Some(2));
+        assert_eq!(scene.describe_object(2).as_deref(), Some("plane"));
+        assert_eq!(scene.describe_object(3), None);
+    }
+
+    #[test]
+    fn material_ids_follow_registration_order() {
+        let mat = |g: f64| -> Arc<dyn Material> {
+            Arc::new(Lambertian::new(Arc::new(SolidColor::new(Color::new(
+                g, g, g,
+            )))))
+        };
+        let (a, b, unlisted) = (mat(0.1), mat(0.2), mat(0.3));
+        let scene = Scene::new(
+            Sphere::new(Vector3::zeros(), 1.0, a.clone()),
+            Color::black(),
+        )
+        .with_materials([b.clone(), a.clone(), b.clone()]);
+        assert_eq!(scene.material_id(b.as_ref()), 1.0);
+        assert_eq!(scene.material_id(a.as_ref()), 2.0);
+        assert_eq!(scene.material_id(unlisted.as_ref()), 0.0);
+    }
+
+    #[test]
+    fn next_frame_moves_translations_by_their_velocity() {
+        let white = json!({ "type": "solid", "name": "white", "albedo": [1.0, 1.0, 1.0] });
+        let moving = json!({
+            "type": "translation",
+            "by": [0.0, 0.0, 0.0],
+            "velocity": [3.0, 0.0, 0.0],
+            "geom": { "type": "sphere", "c": [0.0, 0.0, 0.0], "r": 1.0, "mat": "white" },
+        });
+        let still = json!({
+            "type": "translation",
+            "by": [0.0, 5.0, 0.0],
+            "geom": { "type": "sphere", "c": [0.0, 0.0, 0.0], "r": 1.0, "mat": "white" },
+        });
+        let mut scene = build(json!([white]), json!([moving, still])).unwrap();
+
+        let dist_at = |scene: &Scene, x: f64, y: f64| {
+            let ray = Ray::new(Vector3::new(x, y, 10.0), -Vector3::z_axis());
+            scene
+                .intersect(ray, Interval::new(0.0, f64::MAX))
+                .map(|h| h.dist())
+        };
+        for frame in 0..4 {
+            let x = frame as f64 * 3.0;
+            assert_eq!(dist_at(&scene, x, 0.0), Some(9.0));
+            assert_eq!(dist_at(&scene, x + 1.5, 0.0), None);
+            assert_eq!(dist_at(&scene, 0.0, 5.0), Some(9.0));
+            scene = scene.next_frame();
+        }
+    }
+}
@@ -1,9 +1,24 @@
-use std::collections::HashMap;
+use std::{fmt, fs::File, io, io::BufReader};
 
-use nalgebra::Vector3;
+use nalgebra::{Vector2, Vector3};
 use serde_json::Value;
 
-use crate::{lighting::color::Color, math::interval::Interval};
+use crate::{
+    geom::csg::CsgOp,
+    lighting::{
+        color::{Color, ColorSpace},
+        texture::{
+            combine::CombineOp,
+            image::{Filter, Wrap},
+            noise::NoiseKind,
+        },
+    },
+    math::interval::Interval,
+};
+
+use crate::geom::trimesh::MeshError;
+
+use super::{checkpoint, gltf::GltfError};
 
 pub enum TextureDesc {
     Solid {
@@ -14,14 +29,84 @@ pub enum TextureDesc {
         tex2: String,
         checker_size: f64,
     },
+    Checker3d {
+        tex1: String,
+        tex2: String,
+        checker_size: f64,
+    },
     Image {
         fname: String,
+        // None means guess from the file extension
+        color_space: Option<ColorSpace>,
+        wrap: Wrap,
+        filter: Filter,
+        flip_u: bool,
+        flip_v: bool,
     },
     ScaleTex {
         scale_u: f64,
         scale_v: f64,
         tex: String,
     },
+    Mix {
+        tex1: String,
+        tex2: String,
+        // Either a mask texture or a constant factor
+        mask: Option<String>,
+        factor: f64,
+    },
+    Combine {
+        op: CombineOp,
+        tex1: String,
+        tex2: String,
+    },
+    Ramp {
+        tex: String,
+        stops: Vec<(f64, Color)>,
+    },
+    Remap {
+        tex: String,
+        from: (f64, f64),
+        to: (f64, f64),
+    },
+    Invert {
+        tex: String,
+    },
+    VertexColor {
+        fallback: Option<String>,
+    },
+    UvTransform {
+        tex: String,
+        tile: Vector2<f64>,
+        rotation: f64,
+        offset: Vector2<f64>,
+    },
+    Noise {
+        seed: u64,
+        scale: f64,
+        kind: NoiseKind,
+    },
+    Marble {
+        seed: u64,
+        scale: f64,
+        turbulence: f64,
+        base: Color,
+        vein: Color,
+    },
+    Wood {
+        seed: u64,
+        scale: f64,
+        rings: f64,
+        light: Color,
+        dark: Color,
+    },
+    Granite {
+        seed: u64,
+        scale: f64,
+        base: Color,
+        grain: Color,
+        fleck: Color,
+    },
 }
 pub enum MaterialDesc {
     DiffuseLight { tex: String },
@@ -30,18 +115,20 @@ pub enum MaterialDesc {
 }
 
 pub enum GeomDesc {
+    // Any face can have its own material
     Cube {
-        c: Vector3<f64>,
-        r: f64,
+        min: Vector3<f64>,
+        max: Vector3<f64>,
+        mat: String,
+        face_mats: [Option<String>; 6],
+    },
+    // Infinite, so it's kept out of the BVH
+    Plane {
+        point: Vector3<f64>,
+        normal: Vector3<f64>,
+        uv_scale: f64,
         mat: String,
     },
-    // Plane {
-    //     center: Vector3<f64>,
-    //     normal: Vector3<f64>,
-    //     u_hat: Vector3<f64>,
-    //     v_hat: Vector3<f64>,
-    //     mat: String,
-    // },
     Quad {
         q: Vector3<f64>,
         u: Vector3<f64>,
@@ -53,17 +140,144 @@ pub enum GeomDesc {
         r: f64,
         mat: String,
     },
+    Disk {
+        c: Vector3<f64>,
+        normal: Vector3<f64>,
+        r: f64,
+        inner_r: f64,
+        mat: String,
+    },
+    Cylinder {
+        base: Vector3<f64>,
+        axis: Vector3<f64>,
+        r: f64,
+        capped: bool,
+        mat: String,
+    },
+    Cone {
+        base: Vector3<f64>,
+        axis: Vector3<f64>,
+        r: f64,
+        capped: bool,
+        mat: String,
+    },
+    Torus {
+        c: Vector3<f64>,
+        axis: Vector3<f64>,
+        major_r: f64,
+        minor_r: f64,
+        mat: String,
+    },
     Translation {
         by: Vector3<f64>,
+        // Per frame, when animating
+        velocity: Vector3<f64>,
         gd: Box<GeomDesc>,
     },
+    Csg {
+        op: CsgOp,
+        a: Box<GeomDesc>,
+        b: Box<GeomDesc>,
+    },
+    // Everything in a glTF file, with its own materials
+    Gltf {
+        fname: String,
+    },
+    // An OBJ, PLY or STL file
+    Mesh {
+        fname: String,
+        mat: String,
+        crease_angle: f64,
+        f32: bool,
+    },
 }
 
+pub struct CameraDesc {
+    pub pos: Vector3<f64>,
+    pub fwd: Vector3<f64>,
+    pub up: Vector3<f64>,
+    pub focal_length: f64,
+    pub vfov: f64,
+}
+
+#[derive(Debug)]
+pub enum SceneDescError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// A texture, material or geom with a type we don't know.
+    UnknownType {
+        kind: &'static str,
+        typ: String,
+    },
+    /// Valid JSON describing something we can't build.
+    Invalid(String),
+    /// A texture or material used by `user` that isn't defined before it.
+    Undefined {
+        name: String,
+        user: String,
+    },
+    Gltf {
+        fname: String,
+        error: GltfError,
+    },
+    Mesh {
+        fname: String,
+        error: MeshError,
+    },
+    Image {
+        fname: String,
+        error: image::ImageError,
+    },
+}
+
+impl From<io::Error> for SceneDescError {
+    fn from(e: io::Error) -> Self {
+        SceneDescError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SceneDescError {
+    fn from(e: serde_json::Error) -> Self {
+        SceneDescError::Json(e)
+    }
+}
+
+impl fmt::Display for SceneDescError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneDescError::Io(e) => write!(f, "{}", e),
+            SceneDescError::Json(e) => write!(f, "bad json: {}", e),
+            SceneDescError::UnknownType { kind, typ } => write!(f, "unknown {} type {}", kind, typ),
+            SceneDescError::Invalid(e) => write!(f, "invalid scene: {}", e),
+            SceneDescError::Undefined { name, user } => {
+                write!(f, "{} has to be defined before {}", name, user)
+            }
+            SceneDescError::Gltf { fname, error } => {
+                write!(f, "couldn't load {}: {}", fname, error)
+            }
+            SceneDescError::Mesh { fname, error } => {
+                write!(f, "couldn't load {}: {}", fname, error)
+            }
+            SceneDescError::Image { fname, error } => {
+                write!(f, "couldn't load {}: {}", fname, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneDescError {}
+
+// Textures and materials are kept in the order they're defined, since they
+// can only refer to ones defined before them.
 pub struct SceneDesc {
-    pub textures: HashMap<String, TextureDesc>,
-    pub materials: HashMap<String, MaterialDesc>,
+    pub textures: Vec<(String, TextureDesc)>,
+    pub materials: Vec<(String, MaterialDesc)>,
     pub geoms: Vec<GeomDesc>,
     pub background_color: Color,
+    pub camera: Option<CameraDesc>,
+    /// Hash of the whole description, so checkpoints notice edits to materials
+    /// and textures as well as to geometry.
+    pub source_hash: u64,
 }
 
 fn parse_color(obj: &serde_json::Value) -> Color {
@@ -87,19 +301,237 @@ fn parse_vec3(obj: &serde_json::Value) -> Vector3<f64> {
     Vector3::new(x, y, z)
 }
 
-fn parse_texture(typ: String, obj: &serde_json::Value) -> TextureDesc {
-    if typ == "solid" {
+fn parse_pair_or(obj: &Value, key: &str, default: (f64, f64)) -> (f64, f64) {
+    obj.get(key)
+        .map(|v| {
+            let arr = v
+                .as_array()
+                .unwrap_or_else(|| panic!("{} is an array", key));
+            assert!(arr.len() == 2, "{} has two elements", key);
+            let x = arr[0].as_f64().expect("pair element is f64");
+            let y = arr[1].as_f64().expect("pair element is f64");
+            (x, y)
+        })
+        .unwrap_or(default)
+}
+
+fn parse_f64(obj: &Value, key: &str) -> f64 {
+    obj.get(key)
+        .unwrap_or_else(|| panic!("{} is given", key))
+        .as_f64()
+        .unwrap_or_else(|| panic!("{} is a number", key))
+}
+
+fn parse_f64_or(obj: &Value, key: &str, default: f64) -> f64 {
+    obj.get(key)
+        .map(|v| v.as_f64().unwrap_or_else(|| panic!("{} is a number", key)))
+        .unwrap_or(default)
+}
+
+fn parse_str(obj: &Value, key: &str) -> String {
+    obj.get(key)
+        .unwrap_or_else(|| panic!("{} is given", key))
+        .as_str()
+        .unwrap_or_else(|| panic!("{} is a string", key))
+        .to_string()
+}
+
+fn parse_seed(obj: &Value) -> u64 {
+    obj.get("seed")
+        .map(|v| v.as_u64().expect("seed is a non-negative integer"))
+        .unwrap_or(0)
+}
+
+fn parse_color_or(obj: &Value, key: &str, default: Color) -> Color {
+    obj.get(key).map(parse_color).unwrap_or(default)
+}
+
+fn parse_bool_or(obj: &Value, key: &str, default: bool) -> bool {
+    obj.get(key)
+        .map(|v| v.as_bool().unwrap_or_else(|| panic!("{} is a bool", key)))
+        .unwrap_or(default)
+}
+
+fn parse_texture(typ: String, obj: &serde_json::Value) -> Result<TextureDesc, SceneDescError> {
+    let desc = if typ == "solid" {
         let albedo = obj.get("albedo").expect("Solid texture has albedo");
-        return TextureDesc::Solid {
-            albedo: parse_color(&albedo),
+        TextureDesc::Solid {
+            albedo: parse_color(albedo),
+        }
+    } else if typ == "checkerboard" {
+        TextureDesc::Checkerboard {
+            tex1: parse_str(obj, "tex1"),
+            tex2: parse_str(obj, "tex2"),
+            checker_size: parse_f64(obj, "checker_size"),
+        }
+    } else if typ == "checker3d" {
+        TextureDesc::Checker3d {
+            tex1: parse_str(obj, "tex1"),
+            tex2: parse_str(obj, "tex2"),
+            checker_size: parse_f64(obj, "checker_size"),
+        }
+    } else if typ == "image" {
+        let wrap = match obj.get("wrap").and_then(|w| w.as_str()).unwrap_or("repeat") {
+            "repeat" => Wrap::Repeat,
+            "clamp" => Wrap::Clamp,
+            "mirror" => Wrap::Mirror,
+            "border" => Wrap::Border(parse_color_or(obj, "border", Color::black())),
+            w => return Err(SceneDescError::Invalid(format!("unknown wrap mode {}", w))),
+        };
+        let filter = match obj
+            .get("filter")
+            .and_then(|f| f.as_str())
+            .unwrap_or("trilinear")
+        {
+            "nearest" => Filter::Nearest,
+            "bilinear" => Filter::Bilinear,
+            "trilinear" => Filter::Trilinear,
+            "bicubic" => Filter::Bicubic,
+            f => return Err(SceneDescError::Invalid(format!("unknown filter {}", f))),
         };
+        let color_space = match obj
+            .get("color_space")
+            .map(|c| c.as_str().expect("color_space is a string"))
+        {
+            None => None,
+            Some("srgb") => Some(ColorSpace::Srgb),
+            Some("linear") => Some(ColorSpace::Linear),
+            Some(c) => {
+                return Err(SceneDescError::Invalid(format!(
+                    "unknown color space {}",
+                    c
+                )));
+            }
+        };
+        TextureDesc::Image {
+            fname: parse_str(obj, "fname"),
+            color_space,
+            wrap,
+            filter,
+            flip_u: parse_bool_or(obj, "flip_u", false),
+            flip_v: parse_bool_or(obj, "flip_v", false),
+        }
+    } else if typ == "scale" {
+        TextureDesc::ScaleTex {
+            scale_u: parse_f64(obj, "scale_u"),
+            scale_v: parse_f64(obj, "scale_v"),
+            tex: parse_str(obj, "tex"),
+        }
+    } else if typ == "mix" {
+        TextureDesc::Mix {
+            tex1: parse_str(obj, "tex1"),
+            tex2: parse_str(obj, "tex2"),
+            mask: obj.get("mask").map(|_| parse_str(obj, "mask")),
+            factor: parse_f64_or(obj, "factor", 0.5),
+        }
+    } else if typ == "add" || typ == "multiply" {
+        TextureDesc::Combine {
+            op: if typ == "add" {
+                CombineOp::Add
+            } else {
+                CombineOp::Multiply
+            },
+            tex1: parse_str(obj, "tex1"),
+            tex2: parse_str(obj, "tex2"),
+        }
+    } else if typ == "ramp" {
+        let stops = obj
+            .get("stops")
+            .expect("ramp has stops")
+            .as_array()
+            .expect("stops is an array")
+            .iter()
+            .map(|s| {
+                let s = s.as_array().expect("stop is [position, color]");
+                assert!(s.len() == 2);
+                (
+                    s[0].as_f64().expect("stop position is f64"),
+                    parse_color(&s[1]),
+                )
+            })
+            .collect();
+        TextureDesc::Ramp {
+            tex: parse_str(obj, "tex"),
+            stops,
+        }
+    } else if typ == "remap" {
+        TextureDesc::Remap {
+            tex: parse_str(obj, "tex"),
+            from: parse_pair_or(obj, "from", (0.0, 1.0)),
+            to: parse_pair_or(obj, "to", (0.0, 1.0)),
+        }
+    } else if typ == "invert" {
+        TextureDesc::Invert {
+            tex: parse_str(obj, "tex"),
+        }
+    } else if typ == "vertex_color" {
+        TextureDesc::VertexColor {
+            fallback: obj.get("fallback").map(|_| parse_str(obj, "fallback")),
+        }
+    } else if typ == "uv_transform" {
+        let (tu, tv) = parse_pair_or(obj, "tile", (1.0, 1.0));
+        let (ou, ov) = parse_pair_or(obj, "offset", (0.0, 0.0));
+        TextureDesc::UvTransform {
+            tex: parse_str(obj, "tex"),
+            tile: Vector2::new(tu, tv),
+            rotation: parse_f64_or(obj, "rotation_deg", 0.0).to_radians(),
+            offset: Vector2::new(ou, ov),
+        }
+    } else if typ == "noise" {
+        let kind = match obj.get("kind").and_then(|k| k.as_str()).unwrap_or("plain") {
+            "plain" => NoiseKind::Plain,
+            "turbulence" => NoiseKind::Turbulence {
+                depth: parse_f64_or(obj, "depth", 7.0) as u32,
+            },
+            "fbm" => NoiseKind::Fbm {
+                octaves: parse_f64_or(obj, "octaves", 6.0) as u32,
+                lacunarity: parse_f64_or(obj, "lacunarity", 2.0),
+                gain: parse_f64_or(obj, "gain", 0.5),
+            },
+            k => return Err(SceneDescError::Invalid(format!("unknown noise kind {}", k))),
+        };
+        TextureDesc::Noise {
+            seed: parse_seed(obj),
+            scale: parse_f64_or(obj, "scale", 1.0),
+            kind,
+        }
+    } else if typ == "marble" {
+        TextureDesc::Marble {
+            seed: parse_seed(obj),
+            scale: parse_f64_or(obj, "scale", 1.0),
+            turbulence: parse_f64_or(obj, "turbulence", 10.0),
+            base: parse_color_or(obj, "base", Color::new(0.9, 0.9, 0.88)),
+            vein: parse_color_or(obj, "vein", Color::new(0.2, 0.2, 0.25)),
+        }
+    } else if typ == "wood" {
+        TextureDesc::Wood {
+            seed: parse_seed(obj),
+            scale: parse_f64_or(obj, "scale", 1.0),
+            rings: parse_f64_or(obj, "rings", 8.0),
+            light: parse_color_or(obj, "light", Color::new(0.8, 0.6, 0.35)),
+            dark: parse_color_or(obj, "dark", Color::new(0.45, 0.28, 0.12)),
+        }
+    } else if typ == "granite" {
+        TextureDesc::Granite {
+            seed: parse_seed(obj),
+            scale: parse_f64_or(obj, "scale", 1.0),
+            base: parse_color_or(obj, "base", Color::new(0.55, 0.52, 0.5)),
+            grain: parse_color_or(obj, "grain", Color::new(0.75, 0.7, 0.68)),
+            fleck: parse_color_or(obj, "fleck", Color::new(0.1, 0.1, 0.1)),
+        }
     } else {
-        unimplemented!()
-    }
+        return Err(SceneDescError::UnknownType {
+            kind: "texture",
+            typ,
+        });
+    };
+    Ok(desc)
 }
 
-fn parse_textures(v: &Vec<serde_json::Value>) -> HashMap<String, TextureDesc> {
-    let mut m = HashMap::new();
+fn parse_textures(
+    v: &Vec<serde_json::Value>,
+) -> Result<Vec<(String, TextureDesc)>, SceneDescError> {
+    let mut m = Vec::new();
     for obj in v {
         let typ = obj
             .get("type")
@@ -111,13 +543,13 @@ fn parse_textures(v: &Vec<serde_json::Value>) -> HashMap<String, TextureDesc> {
             .expect("Texture has a name")
             .as_str()
             .expect("Name is a string");
-        let tex = parse_texture(String::from(typ), obj);
-        m.insert(String::from(name), tex);
+        let tex = parse_texture(String::from(typ), obj)?;
+        m.push((String::from(name), tex));
     }
-    m
+    Ok(m)
 }
-fn parse_material(typ: String, obj: &serde_json::Value) -> MaterialDesc {
-    if typ == "diffuselight" {
+fn parse_material(typ: String, obj: &serde_json::Value) -> Result<MaterialDesc, SceneDescError> {
+    let desc = if typ == "diffuselight" {
         let tex = obj
             .get("tex")
             .expect("DiffuseLight Material has a tex")
@@ -132,7 +564,7 @@ fn parse_material(typ: String, obj: &serde_json::Value) -> MaterialDesc {
             .expect("Lambert Material has a tex")
             .as_str()
             .expect("tex is a string");
-        MaterialDesc::DiffuseLight {
+        MaterialDesc::Lambertian {
             tex: String::from(tex),
         }
     } else if typ == "metal" {
@@ -147,12 +579,18 @@ fn parse_material(typ: String, obj: &serde_json::Value) -> MaterialDesc {
             fuzz: fuzz,
         }
     } else {
-        unimplemented!()
-    }
+        return Err(SceneDescError::UnknownType {
+            kind: "material",
+            typ,
+        });
+    };
+    Ok(desc)
 }
 
-fn parse_materials(v: &Vec<serde_json::Value>) -> HashMap<String, MaterialDesc> {
-    let mut m = HashMap::new();
+fn parse_materials(
+    v: &Vec<serde_json::Value>,
+) -> Result<Vec<(String, MaterialDesc)>, SceneDescError> {
+    let mut m = Vec::new();
     for obj in v {
         let typ = obj
             .get("type")
@@ -164,20 +602,23 @@ fn parse_materials(v: &Vec<serde_json::Value>) -> HashMap<String, MaterialDesc>
             .expect("Material has a name")
             .as_str()
             .expect("Material name is a string");
-        let tex = parse_material(String::from(typ), obj);
-        m.insert(String::from(name), tex);
+        let tex = parse_material(String::from(typ), obj)?;
+        m.push((String::from(name), tex));
     }
 
-    m
+    Ok(m)
 }
 
-fn parse_geom(geom: &Value) -> GeomDesc {
+// Keys of a cube's "face_mats", in the order of `cube::Face`
+const CUBE_FACES: [&str; 6] = ["+x", "-x", "+y", "-y", "+z", "-z"];
+
+fn parse_geom(geom: &Value) -> Result<GeomDesc, SceneDescError> {
     let typ = geom
         .get("type")
         .expect("Geom has a type")
         .as_str()
         .expect("type is a string");
-    if typ == "quad" {
+    let desc = if typ == "quad" {
         let mat = geom
             .get("mat")
             .expect("quad has a mat")
@@ -196,7 +637,37 @@ fn parse_geom(geom: &Value) -> GeomDesc {
             mat: mat.to_string(),
         }
     } else if typ == "cube" {
-        unimplemented!()
+        // Either a center and half-width, or two corners
+        let (min, max) = match geom.get("c") {
+            Some(c) => {
+                let c = parse_vec3(c);
+                let r = Vector3::repeat(parse_f64(geom, "r"));
+                (c - r, c + r)
+            }
+            None => (
+                parse_vec3(geom.get("min").expect("cube has a c or a min")),
+                parse_vec3(geom.get("max").expect("cube has a max")),
+            ),
+        };
+        // The corners can come in any order, but mustn't share a coordinate
+        if !(max - min).iter().all(|s| s.abs() > 0.0) {
+            return Err(SceneDescError::Invalid(format!(
+                "cube from {:?} to {:?} has no volume",
+                min.as_slice(),
+                max.as_slice()
+            )));
+        }
+        let face_mats = CUBE_FACES.map(|face| {
+            geom.get("face_mats")
+                .and_then(|m| m.get(face))
+                .map(|m| m.as_str().expect("face mat is a str").to_string())
+        });
+        GeomDesc::Cube {
+            min,
+            max,
+            mat: parse_str(geom, "mat"),
+            face_mats,
+        }
     } else if typ == "sphere" {
         let mat = geom
             .get("mat")
@@ -215,15 +686,163 @@ fn parse_geom(geom: &Value) -> GeomDesc {
             r: r,
             mat: mat.to_string(),
         }
+    } else if typ == "disk" {
+        GeomDesc::Disk {
+            c: parse_vec3(geom.get("c").expect("disk has a c")),
+            normal: parse_vec3(geom.get("normal").expect("disk has a normal")),
+            r: parse_f64(geom, "r"),
+            inner_r: parse_f64_or(geom, "inner_r", 0.0),
+            mat: parse_str(geom, "mat"),
+        }
+    } else if typ == "cylinder" || typ == "cone" {
+        let base = parse_vec3(geom.get("base").expect("base is given"));
+        let axis = parse_vec3(geom.get("axis").expect("axis is given"));
+        let r = parse_f64(geom, "r");
+        let capped = parse_bool_or(geom, "capped", true);
+        let mat = parse_str(geom, "mat");
+        if typ == "cylinder" {
+            GeomDesc::Cylinder {
+                base,
+                axis,
+                r,
+                capped,
+                mat,
+            }
+        } else {
+            GeomDesc::Cone {
+                base,
+                axis,
+                r,
+                capped,
+                mat,
+            }
+        }
+    } else if typ == "torus" {
+        GeomDesc::Torus {
+            c: parse_vec3(geom.get("c").expect("torus has a c")),
+            axis: geom
+                .get("axis")
+                .map(parse_vec3)
+                .unwrap_or(Vector3::new(0.0, 1.0, 0.0)),
+            major_r: parse_f64(geom, "major_r"),
+            minor_r: parse_f64(geom, "minor_r"),
+            mat: parse_str(geom, "mat"),
+        }
+    } else if typ == "plane" {
+        GeomDesc::Plane {
+            point: parse_vec3(geom.get("point").expect("plane has a point")),
+            normal: parse_vec3(geom.get("normal").expect("plane has a normal")),
+            uv_scale: parse_f64_or(geom, "uv_scale", 1.0),
+            mat: parse_str(geom, "mat"),
+        }
+    } else if typ == "csg" {
+        let op = match parse_str(geom, "op").as_str() {
+            "union" => CsgOp::Union,
+            "intersection" => CsgOp::Intersection,
+            "difference" => CsgOp::Difference,
+            other => return Err(SceneDescError::Invalid(format!("unknown csg op {}", other))),
+        };
+        GeomDesc::Csg {
+            op,
+            a: parse_operand(geom.get("a").expect("csg has an a"))?,
+            b: parse_operand(geom.get("b").expect("csg has a b"))?,
+        }
     } else if typ == "translation" {
-        unimplemented!()
+        GeomDesc::Translation {
+            by: parse_vec3(geom.get("by").expect("translation has a by")),
+            velocity: geom
+                .get("velocity")
+                .map(parse_vec3)
+                .unwrap_or_else(Vector3::zeros),
+            gd: parse_inner(geom.get("geom").expect("translation has a geom"))?,
+        }
+    } else if typ == "gltf" {
+        GeomDesc::Gltf {
+            fname: parse_str(geom, "fname"),
+        }
+    } else if typ == "mesh" {
+        GeomDesc::Mesh {
+            fname: parse_str(geom, "fname"),
+            mat: parse_str(geom, "mat"),
+            crease_angle: parse_f64_or(geom, "crease_angle_deg", 60.0).to_radians(),
+            f32: parse_bool_or(geom, "f32", false),
+        }
     } else {
-        unimplemented!()
+        return Err(SceneDescError::UnknownType {
+            kind: "geom",
+            typ: typ.to_string(),
+        });
+    };
+    Ok(desc)
+}
+
+// A geom inside a csg or translation, which can't be a plane since those stay
+// out of the BVH
+fn parse_inner(geom: &Value) -> Result<Box<GeomDesc>, SceneDescError> {
+    match parse_geom(geom)? {
+        GeomDesc::Plane { .. } => Err(SceneDescError::Invalid(String::from(
+            "planes can only be at the top level of a scene",
+        ))),
+        gd => Ok(Box::new(gd)),
     }
 }
 
-impl From<serde_json::Value> for SceneDesc {
-    fn from(value: serde_json::Value) -> Self {
+// A side of a csg, which has to close off a volume
+fn parse_operand(geom: &Value) -> Result<Box<GeomDesc>, SceneDescError> {
+    let gd = parse_inner(geom)?;
+    if is_open(&gd) {
+        return Err(SceneDescError::Invalid(String::from(
+            "csg needs closed solids, not quads, disks or uncapped cylinders and cones",
+        )));
+    }
+    Ok(gd)
+}
+
+// Surfaces that don't enclose anything. Meshes are taken on trust.
+fn is_open(gd: &GeomDesc) -> bool {
+    match gd {
+        GeomDesc::Quad { .. } | GeomDesc::Disk { .. } | GeomDesc::Plane { .. } => true,
+        GeomDesc::Cylinder { capped, .. } | GeomDesc::Cone { capped, .. } => !capped,
+        GeomDesc::Translation { gd, .. } => is_open(gd),
+        _ => false,
+    }
+}
+
+fn parse_camera(obj: &Value) -> CameraDesc {
+    let pos = parse_vec3(obj.get("pos").expect("camera has a pos"));
+    let fwd = parse_vec3(obj.get("fwd").expect("camera has a fwd"));
+    let up = parse_vec3(obj.get("up").expect("camera has an up"));
+    let focal_length = obj
+        .get("focal_length")
+        .expect("camera has a focal_length")
+        .as_f64()
+        .expect("focal_length is a float");
+    let vfov = obj
+        .get("vfov")
+        .expect("camera has a vfov")
+        .as_f64()
+        .expect("vfov is a float");
+    CameraDesc {
+        pos,
+        fwd,
+        up,
+        focal_length,
+        vfov,
+    }
+}
+
+impl SceneDesc {
+    pub fn from_fname(fname: &str) -> Result<Self, SceneDescError> {
+        let file = File::open(fname)?;
+        let value: Value = serde_json::from_reader(BufReader::new(file))?;
+        SceneDesc::try_from(value)
+    }
+}
+
+impl TryFrom<serde_json::Value> for SceneDesc {
+    type Error = SceneDescError;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, SceneDescError> {
         let background_color = value
             .get("background_color")
             .expect("Scene has a background color");
@@ -234,7 +853,7 @@ impl From<serde_json::Value> for SceneDesc {
             .expect(" Scene has textures")
             .as_array()
             .expect("Textures is an array");
-        let textures = parse_textures(textures);
+        let textures = parse_textures(textures)?;
 
         let materials = value
             .get("materials")
@@ -242,7 +861,7 @@ impl From<serde_json::Value> for SceneDesc {
             .as_array()
             .expect(" Materials is an array");
 
-        let materials = parse_materials(materials);
+        let materials = parse_materials(materials)?;
 
         let geoms = value
             .get("geoms")
@@ -250,13 +869,100 @@ impl From<serde_json::Value> for SceneDesc {
             .as_array()
             .expect("geoms is an array");
 
-        let geoms = geoms.iter().map(parse_geom).collect();
+        let geoms = geoms.iter().map(parse_geom).collect::<Result<_, _>>()?;
 
-        SceneDesc {
+        let camera = value.get("camera").map(parse_camera);
+
+        Ok(SceneDesc {
             textures,
             materials,
             geoms,
             background_color,
-        }
+            camera,
+            source_hash: checkpoint::hash_bytes(value.to_string().as_bytes()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::{GeomDesc, SceneDesc, SceneDescError};
+
+    fn scene(geom: Value) -> Result<SceneDesc, SceneDescError> {
+        SceneDesc::try_from(json!({
+            "background_color": [0.0, 0.0, 0.0],
+            "textures": [],
+            "materials": [],
+            "geoms": [geom],
+        }))
+    }
+
+    #[test]
+    fn translations_parse() {
+        let sd = scene(json!({
+            "type": "translation",
+            "by": [1.0, 2.0, 3.0],
+            "geom": { "type": "sphere", "c": [0.0, 0.0, 0.0], "r": 1.0, "mat": "m" },
+        }))
+        .unwrap();
+        assert!(matches!(
+            &sd.geoms[..],
+            [GeomDesc::Translation { gd, .. }] if matches!(**gd, GeomDesc::Sphere { .. })
+        ));
+    }
+
+    #[test]
+    fn unknown_types_are_errors() {
+        assert!(matches!(
+            scene(json!({ "type": "teapot" })),
+            Err(SceneDescError::UnknownType { kind: "geom", .. })
+        ));
+        let nested_plane = json!({
+            "type": "translation",
+            "by": [1.0, 0.0, 0.0],
+            "geom": { "type": "plane", "point": [0.0, 0.0, 0.0], "normal": [0.0, 1.0, 0.0], "mat": "m" },
+        });
+        assert!(matches!(
+            scene(nested_plane),
+            Err(SceneDescError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn csg_needs_closed_operands() {
+        let csg = |b: Value| {
+            scene(json!({
+                "type": "csg",
+                "op": "difference",
+                "a": { "type": "sphere", "c": [0.0, 0.0, 0.0], "r": 1.0, "mat": "m" },
+                "b": b,
+            }))
+        };
+        let cylinder = |capped: bool| json!({ "type": "cylinder", "base": [0.0, -2.0, 0.0], "axis": [0.0, 4.0, 0.0], "r": 0.5, "capped": capped, "mat": "m" });
+        assert!(csg(cylinder(true)).is_ok());
+        assert!(matches!(
+            csg(cylinder(false)),
+            Err(SceneDescError::Invalid(_))
+        ));
+        let moved_disk = json!({
+            "type": "translation",
+            "by": [0.0, 1.0, 0.0],
+            "geom": { "type": "disk", "c": [0.0, 0.0, 0.0], "normal": [0.0, 1.0, 0.0], "r": 1.0, "mat": "m" },
+        });
+        assert!(matches!(csg(moved_disk), Err(SceneDescError::Invalid(_))));
+    }
+
+    #[test]
+    fn flat_cubes_are_errors() {
+        let flat =
+            json!({ "type": "cube", "min": [0.0, 0.0, 0.0], "max": [1.0, 0.0, 1.0], "mat": "m" });
+        assert!(matches!(scene(flat), Err(SceneDescError::Invalid(_))));
+        let point = json!({ "type": "cube", "c": [0.0, 0.0, 0.0], "r": 0.0, "mat": "m" });
+        assert!(matches!(scene(point), Err(SceneDescError::Invalid(_))));
+        let flipped =
+            json!({ "type": "cube", "min": [1.0, 1.0, 1.0], "max": [0.0, 0.0, 0.0], "mat": "m" });
+        assert!(scene(flipped).is_ok());
     }
 }
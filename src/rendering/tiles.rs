@@ -0,0 +1,107 @@
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy)]
+pub enum TileOrder {
+    /// Left to right, top to bottom.
+    Scanline,
+    /// Outwards from the center of the image, so the interesting part shows up first.
+    Spiral,
+    /// Along a Hilbert curve, keeping consecutive tiles next to each other for cache locality.
+    Hilbert,
+}
+
+impl TileOrder {
+    pub fn from_name(name: &str) -> Option<TileOrder> {
+        match name {
+            "scanline" => Some(TileOrder::Scanline),
+            "spiral" => Some(TileOrder::Spiral),
+            "hilbert" => Some(TileOrder::Hilbert),
+            _ => None,
+        }
+    }
+}
+
+/// A rectangle of pixels, `[x0, x0 + width) x [y0, y0 + height)`.
+#[derive(Debug, Clone, Copy)]
+pub struct Tile {
+    pub x0: usize,
+    pub y0: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> {
+        let (x0, y0, w) = (self.x0, self.y0, self.width);
+        (0..self.width * self.height).map(move |i| (x0 + i % w, y0 + i / w))
+    }
+}
+
+/// Split a `width` x `height` image into tiles of at most `size` x `size`, in the given order.
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    assert!(size > 0);
+    let ntx = width.div_ceil(size);
+    let nty = height.div_ceil(size);
+
+    let mut grid: Vec<(usize, usize)> = (0..nty)
+        .flat_map(|ty| (0..ntx).map(move |tx| (tx, ty)))
+        .collect();
+
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let cx = (ntx as f64 - 1.0) / 2.0;
+            let cy = (nty as f64 - 1.0) / 2.0;
+            let key = |&(tx, ty): &(usize, usize)| {
+                let dx = tx as f64 - cx;
+                let dy = ty as f64 - cy;
+                (f64::max(dx.abs(), dy.abs()), f64::atan2(dy, dx))
+            };
+            grid.sort_by(|a, b| {
+                let (ra, ta) = key(a);
+                let (rb, tb) = key(b);
+                ra.partial_cmp(&rb)
+                    .unwrap_or(Ordering::Equal)
+                    .then(ta.partial_cmp(&tb).unwrap_or(Ordering::Equal))
+            });
+        }
+        TileOrder::Hilbert => {
+            let n = usize::max(ntx, nty).next_power_of_two();
+            grid.sort_by_key(|&(tx, ty)| hilbert_index(n, tx, ty));
+        }
+    }
+
+    grid.into_iter()
+        .map(|(tx, ty)| {
+            let x0 = tx * size;
+            let y0 = ty * size;
+            Tile {
+                x0,
+                y0,
+                width: usize::min(size, width - x0),
+                height: usize::min(size, height - y0),
+            }
+        })
+        .collect()
+}
+
+/// Distance of (x, y) along the Hilbert curve filling an n x n grid, n a power of two.
+fn hilbert_index(n: usize, mut x: usize, mut y: usize) -> usize {
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = ((x & s) > 0) as usize;
+        let ry = ((y & s) > 0) as usize;
+        d += s * s * ((3 * rx) ^ ry);
+        // Rotate the quadrant so the curve's sub-curves join up.
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
@@ -32,3 +32,9 @@ pub fn is_small(v: Vector3<f64>) -> bool {
 pub fn reflect(v: &Vector3<f64>, about: &UnitVector3<f64>) -> Vector3<f64> {
     return *v - about.scale(2.0 * v.dot(about));
 }
+
+/// Bound on the relative error piled up by `n` rounded floating point operations.
+pub fn gamma(n: u32) -> f64 {
+    let ne = n as f64 * f64::EPSILON * 0.5;
+    ne / (1.0 - ne)
+}